@@ -0,0 +1,583 @@
+// compact bytecode for the VM. every variable is resolved to a slot number
+// in its function's frame (arrays get their own numbering), every label to
+// the index of the instruction it marks, and every callee to its function
+// index, so running it needs no string handling. array parameters take the
+// first array numbers, and calls pass them the caller's arrays by reference.
+//
+// a .ttbc file is "TTBC", a little-endian u16 format version, the encoded
+// program (starting with its overflow mode: 0 wrap, 1 trap, 2 saturate), and
+// an FNV-1a checksum of everything before it.
+use std::collections::{HashMap, HashSet};
+use crate::ir::{self, Function, Instr, Overflow};
+
+const MAGIC: &[u8; 4] = b"TTBC";
+const VERSION: u16 = 5;
+
+// the most a file may ask the VM to allocate up front: ints across all
+// globals, and slots or arrays in one frame.
+const MAX_GLOBAL_INTS: u64 = 1 << 26;
+const MAX_FRAME: u32 = 1 << 20;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Operand {
+  Slot(u32),
+  Const(i32),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Array {
+  Local(u32),
+  Global(u32),
+}
+
+#[derive(Debug, Clone)]
+pub enum Op {
+  Mov(u32, Operand),
+  Binary(u8, u32, Operand, Operand), // index into ir::BINARY_OPS
+  Array(u32, Operand),                // allocate array slot with a size, zeroed on the first run in a call
+  Store(u32, Operand, Operand),       // arr[index] = value
+  Load(u32, u32, Operand),            // dest = arr[index]
+  Out(Operand),
+  Input(u32),
+  Call(u32, u32, Vec<Operand>, Vec<Array>), // dest, function index, scalar and array arguments
+  Ret(Operand),
+  Jmp(u32),
+  BranchIf(Operand, u32),
+  BranchIfn(Operand, u32),
+  JumpTable(Operand, u32, Vec<u32>), // index, default target, targets
+  GlobalStore(u32, Operand, Operand), // globals[arr][index] = value
+  GlobalLoad(u32, u32, Operand),      // dest = globals[arr][index]
+  Check(Operand, Operand, String, u32), // index, size, array name and source line for the error
+}
+
+#[derive(Debug, Clone)]
+pub struct Code {
+  pub name: String,
+  pub params: u32,       // scalar parameters arrive in slots 0..params
+  pub array_params: u32, // and array parameters in arrays 0..array_params
+  pub slots: u32,
+  pub arrays: u32,
+  pub ops: Vec<Op>,
+}
+
+pub struct Program {
+  pub overflow: Overflow,
+  pub functions: Vec<Code>,
+  pub main: u32,
+  pub globals: Vec<u32>, // size of each global, scalars being one-element arrays
+}
+
+struct Slots {
+  scalars: HashMap<String, u32>,
+  arrays: HashMap<String, u32>,
+}
+
+impl Slots {
+  fn scalar(&mut self, name: &String) -> u32 {
+    let next = self.scalars.len() as u32;
+    return *self.scalars.entry(name.clone()).or_insert(next);
+  }
+
+  fn operand(&mut self, name: &String) -> Operand {
+    match name.parse::<i32>() {
+      Ok(value) => Operand::Const(value),
+      Err(_) => Operand::Slot(self.scalar(name)),
+    }
+  }
+
+  fn array(&mut self, name: &String) -> u32 {
+    let next = self.arrays.len() as u32;
+    return *self.arrays.entry(name.clone()).or_insert(next);
+  }
+}
+
+fn compile_function(function: &Function, indices: &HashMap<&String, u32>, globals: &HashMap<&String, u32>) -> Result<Code, String> {
+  let mut slots = Slots { scalars: HashMap::new(), arrays: HashMap::new() };
+  for param in &function.params {
+    if function.array_params.contains(param) {
+      slots.array(param);
+    } else {
+      slots.scalar(param);
+    }
+  }
+  let mut locals: HashSet<&String> = HashSet::new();
+  for instr in &function.body {
+    if let Instr::IntArray(x, _) = instr {
+      locals.insert(x);
+    }
+  }
+  let global = |arr: &String| -> Option<u32> {
+    if locals.contains(arr) {
+      return None;
+    }
+    return globals.get(arr).cloned();
+  };
+
+  // labels take no space, so each one resolves to the index of the op after it.
+  let mut targets: HashMap<&String, u32> = HashMap::new();
+  let mut count = 0;
+  for instr in &function.body {
+    match instr {
+      Instr::Label(label) => { targets.insert(label, count); }
+      Instr::Int(_) => {}
+      _ => count += 1,
+    }
+  }
+  let target = |label: &String| -> Result<u32, String> {
+    match targets.get(label) {
+      Some(index) => Ok(*index),
+      None => Err(format!("unknown label {label} in {}", function.name)),
+    }
+  };
+
+  let mut ops: Vec<Op> = vec![];
+  for instr in &function.body {
+    let op = match instr {
+      Instr::Int(_) | Instr::Label(_) => continue,
+      Instr::IntArray(x, size) => Op::Array(slots.array(x), slots.operand(size)),
+      Instr::Mov(x, y) => Op::Mov(slots.scalar(x), slots.operand(y)),
+      Instr::Store(arr, i, x) => match global(arr) {
+        Some(g) => Op::GlobalStore(g, slots.operand(i), slots.operand(x)),
+        None => Op::Store(slots.array(arr), slots.operand(i), slots.operand(x)),
+      },
+      Instr::Load(x, arr, i) => match global(arr) {
+        Some(g) => Op::GlobalLoad(slots.scalar(x), g, slots.operand(i)),
+        None => Op::Load(slots.scalar(x), slots.array(arr), slots.operand(i)),
+      },
+      Instr::Binary(op, x, y, z) => {
+        let code = match ir::BINARY_OPS.iter().position(|o| o == op) {
+          Some(code) => code as u8,
+          None => return Err(format!("unknown operation {op}")),
+        };
+        Op::Binary(code, slots.scalar(x), slots.operand(y), slots.operand(z))
+      }
+      Instr::Out(x) => Op::Out(slots.operand(x)),
+      Instr::Input(x) => Op::Input(slots.scalar(x)),
+      Instr::Call(x, f, args) => {
+        let callee = match indices.get(f) {
+          Some(callee) => *callee,
+          None => return Err(format!("call to undefined function {f}")),
+        };
+        let mut scalars: Vec<Operand> = vec![];
+        let mut arrays: Vec<Array> = vec![];
+        for arg in args {
+          match ir::array_arg(arg).map(String::from) {
+            Some(arr) => arrays.push(match global(&arr) {
+              Some(g) => Array::Global(g),
+              None => Array::Local(slots.array(&arr)),
+            }),
+            None => scalars.push(slots.operand(arg)),
+          }
+        }
+        Op::Call(slots.scalar(x), callee, scalars, arrays)
+      }
+      Instr::Ret(x) => Op::Ret(slots.operand(x)),
+      Instr::Jmp(l) => Op::Jmp(target(l)?),
+      Instr::BranchIf(x, l) => Op::BranchIf(slots.operand(x), target(l)?),
+      Instr::BranchIfn(x, l) => Op::BranchIfn(slots.operand(x), target(l)?),
+      Instr::JumpTable(x, default, table) => {
+        let targets = table.iter().map(target).collect::<Result<Vec<u32>, String>>()?;
+        Op::JumpTable(slots.operand(x), target(default)?, targets)
+      }
+      Instr::Check(i, size, arr, line) => Op::Check(slots.operand(i), slots.operand(size), arr.clone(), *line),
+      Instr::Phi(_, _) => return Err(String::from("phi instructions must be removed before code generation")),
+    };
+    ops.push(op);
+  }
+  // falling off the end returns 0.
+  ops.push(Op::Ret(Operand::Const(0)));
+
+  return Ok(Code {
+    name: function.name.clone(),
+    params: (function.params.len() - function.array_params.len()) as u32,
+    array_params: function.array_params.len() as u32,
+    slots: slots.scalars.len() as u32,
+    arrays: slots.arrays.len() as u32,
+    ops,
+  });
+}
+
+pub fn compile(program: &ir::Program) -> Result<Program, String> {
+  let mut indices: HashMap<&String, u32> = HashMap::new();
+  for (k, function) in program.functions.iter().enumerate() {
+    indices.insert(&function.name, k as u32);
+  }
+  let main = match indices.get(&String::from("main")) {
+    Some(main) => *main,
+    None => return Err(String::from("no main function")),
+  };
+  let mut globals: HashMap<&String, u32> = HashMap::new();
+  for (k, global) in program.globals.iter().enumerate() {
+    globals.insert(&global.name, k as u32);
+  }
+  let mut compiled: Vec<Code> = vec![];
+  for function in &program.functions {
+    compiled.push(compile_function(function, &indices, &globals)?);
+  }
+  let sizes = program.globals.iter().map(|global| global.size as u32).collect();
+  return Ok(Program { overflow: program.overflow, functions: compiled, main, globals: sizes });
+}
+
+pub fn checksum(bytes: &[u8]) -> u32 {
+  let mut hash: u32 = 0x811c9dc5;
+  for byte in bytes {
+    hash ^= *byte as u32;
+    hash = hash.wrapping_mul(0x01000193);
+  }
+  return hash;
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+  out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_operand(out: &mut Vec<u8>, operand: &Operand) {
+  match operand {
+    Operand::Slot(slot) => {
+      out.push(0);
+      put_u32(out, *slot);
+    }
+    Operand::Const(value) => {
+      out.push(1);
+      out.extend_from_slice(&value.to_le_bytes());
+    }
+  }
+}
+
+fn put_op(out: &mut Vec<u8>, op: &Op) {
+  match op {
+    Op::Mov(x, y) => {
+      out.push(0);
+      put_u32(out, *x);
+      put_operand(out, y);
+    }
+    Op::Binary(code, x, y, z) => {
+      out.push(1);
+      out.push(*code);
+      put_u32(out, *x);
+      put_operand(out, y);
+      put_operand(out, z);
+    }
+    Op::Array(arr, size) => {
+      out.push(2);
+      put_u32(out, *arr);
+      put_operand(out, size);
+    }
+    Op::Store(arr, i, x) => {
+      out.push(3);
+      put_u32(out, *arr);
+      put_operand(out, i);
+      put_operand(out, x);
+    }
+    Op::Load(x, arr, i) => {
+      out.push(4);
+      put_u32(out, *x);
+      put_u32(out, *arr);
+      put_operand(out, i);
+    }
+    Op::Out(x) => {
+      out.push(5);
+      put_operand(out, x);
+    }
+    Op::Input(x) => {
+      out.push(6);
+      put_u32(out, *x);
+    }
+    Op::Call(x, f, args, arrays) => {
+      out.push(7);
+      put_u32(out, *x);
+      put_u32(out, *f);
+      put_u32(out, args.len() as u32);
+      for arg in args {
+        put_operand(out, arg);
+      }
+      put_u32(out, arrays.len() as u32);
+      for arr in arrays {
+        match arr {
+          Array::Local(arr) => {
+            out.push(0);
+            put_u32(out, *arr);
+          }
+          Array::Global(arr) => {
+            out.push(1);
+            put_u32(out, *arr);
+          }
+        }
+      }
+    }
+    Op::Ret(x) => {
+      out.push(8);
+      put_operand(out, x);
+    }
+    Op::Jmp(target) => {
+      out.push(9);
+      put_u32(out, *target);
+    }
+    Op::BranchIf(x, target) => {
+      out.push(10);
+      put_operand(out, x);
+      put_u32(out, *target);
+    }
+    Op::BranchIfn(x, target) => {
+      out.push(11);
+      put_operand(out, x);
+      put_u32(out, *target);
+    }
+    Op::JumpTable(x, default, targets) => {
+      out.push(12);
+      put_operand(out, x);
+      put_u32(out, *default);
+      put_u32(out, targets.len() as u32);
+      for target in targets {
+        put_u32(out, *target);
+      }
+    }
+    Op::GlobalStore(arr, i, x) => {
+      out.push(13);
+      put_u32(out, *arr);
+      put_operand(out, i);
+      put_operand(out, x);
+    }
+    Op::GlobalLoad(x, arr, i) => {
+      out.push(14);
+      put_u32(out, *x);
+      put_u32(out, *arr);
+      put_operand(out, i);
+    }
+    Op::Check(i, size, arr, line) => {
+      out.push(15);
+      put_operand(out, i);
+      put_operand(out, size);
+      put_u32(out, arr.len() as u32);
+      out.extend_from_slice(arr.as_bytes());
+      put_u32(out, *line);
+    }
+  }
+}
+
+pub fn encode(program: &Program) -> Vec<u8> {
+  let mut out: Vec<u8> = MAGIC.to_vec();
+  out.extend_from_slice(&VERSION.to_le_bytes());
+  out.push(program.overflow as u8);
+  put_u32(&mut out, program.globals.len() as u32);
+  for size in &program.globals {
+    put_u32(&mut out, *size);
+  }
+  put_u32(&mut out, program.functions.len() as u32);
+  put_u32(&mut out, program.main);
+  for code in &program.functions {
+    put_u32(&mut out, code.name.len() as u32);
+    out.extend_from_slice(code.name.as_bytes());
+    put_u32(&mut out, code.params);
+    put_u32(&mut out, code.array_params);
+    put_u32(&mut out, code.slots);
+    put_u32(&mut out, code.arrays);
+    put_u32(&mut out, code.ops.len() as u32);
+    for op in &code.ops {
+      put_op(&mut out, op);
+    }
+  }
+  let sum = checksum(&out);
+  put_u32(&mut out, sum);
+  return out;
+}
+
+struct Reader<'a> {
+  bytes: &'a [u8],
+  pos: usize,
+}
+
+impl<'a> Reader<'a> {
+  fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+    if self.pos + n > self.bytes.len() {
+      return Err(String::from("bytecode ends unexpectedly"));
+    }
+    let slice = &self.bytes[self.pos..self.pos + n];
+    self.pos += n;
+    return Ok(slice);
+  }
+
+  fn byte(&mut self) -> Result<u8, String> {
+    return Ok(self.take(1)?[0]);
+  }
+
+  fn u32(&mut self) -> Result<u32, String> {
+    let bytes = self.take(4)?;
+    return Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+  }
+
+  fn operand(&mut self) -> Result<Operand, String> {
+    match self.byte()? {
+      0 => Ok(Operand::Slot(self.u32()?)),
+      1 => Ok(Operand::Const(self.u32()? as i32)),
+      tag => Err(format!("bad operand tag {tag}")),
+    }
+  }
+
+  fn array(&mut self) -> Result<Array, String> {
+    match self.byte()? {
+      0 => Ok(Array::Local(self.u32()?)),
+      1 => Ok(Array::Global(self.u32()?)),
+      tag => Err(format!("bad array tag {tag}")),
+    }
+  }
+
+  fn op(&mut self) -> Result<Op, String> {
+    let op = match self.byte()? {
+      0 => Op::Mov(self.u32()?, self.operand()?),
+      1 => {
+        let code = self.byte()?;
+        if code as usize >= ir::BINARY_OPS.len() {
+          return Err(format!("bad operation {code}"));
+        }
+        Op::Binary(code, self.u32()?, self.operand()?, self.operand()?)
+      }
+      2 => Op::Array(self.u32()?, self.operand()?),
+      3 => Op::Store(self.u32()?, self.operand()?, self.operand()?),
+      4 => Op::Load(self.u32()?, self.u32()?, self.operand()?),
+      5 => Op::Out(self.operand()?),
+      6 => Op::Input(self.u32()?),
+      7 => {
+        let (x, f, count) = (self.u32()?, self.u32()?, self.u32()?);
+        let mut args: Vec<Operand> = vec![];
+        for _ in 0..count {
+          args.push(self.operand()?);
+        }
+        let mut arrays: Vec<Array> = vec![];
+        for _ in 0..self.u32()? {
+          arrays.push(self.array()?);
+        }
+        Op::Call(x, f, args, arrays)
+      }
+      8 => Op::Ret(self.operand()?),
+      9 => Op::Jmp(self.u32()?),
+      10 => Op::BranchIf(self.operand()?, self.u32()?),
+      11 => Op::BranchIfn(self.operand()?, self.u32()?),
+      12 => {
+        let (x, default, count) = (self.operand()?, self.u32()?, self.u32()?);
+        let mut targets: Vec<u32> = vec![];
+        for _ in 0..count {
+          targets.push(self.u32()?);
+        }
+        Op::JumpTable(x, default, targets)
+      }
+      13 => Op::GlobalStore(self.u32()?, self.operand()?, self.operand()?),
+      14 => Op::GlobalLoad(self.u32()?, self.u32()?, self.operand()?),
+      15 => {
+        let (i, size, length) = (self.operand()?, self.operand()?, self.u32()? as usize);
+        let arr = match String::from_utf8(self.take(length)?.to_vec()) {
+          Ok(arr) => arr,
+          Err(_) => return Err(String::from("bad array name")),
+        };
+        Op::Check(i, size, arr, self.u32()?)
+      }
+      opcode => return Err(format!("bad opcode {opcode}")),
+    };
+    return Ok(op);
+  }
+}
+
+// slot numbers, array and global numbers, jump targets and callees must all
+// be in range, so the VM can index without checking, and the storage it
+// allocates before running anything has to be of a sensible size.
+fn validate(program: &Program) -> Result<(), String> {
+  let functions = program.functions.len() as u32;
+  let globals = program.globals.len() as u32;
+  if program.main >= functions {
+    return Err(String::from("main function index out of range"));
+  }
+  if program.globals.iter().map(|size| *size as u64).sum::<u64>() > MAX_GLOBAL_INTS {
+    return Err(format!("globals hold more than {MAX_GLOBAL_INTS} ints"));
+  }
+  for code in &program.functions {
+    let scalar = |operand: &Operand| matches!(operand, Operand::Slot(slot) if *slot >= code.slots);
+    let length = code.ops.len() as u32;
+    let array = |arr: &Array| match arr {
+      Array::Local(arr) => *arr >= code.arrays,
+      Array::Global(arr) => *arr >= globals,
+    };
+    if code.slots > MAX_FRAME || code.arrays > MAX_FRAME {
+      return Err(format!("frame of {} is too large", code.name));
+    }
+    if code.params > code.slots || code.array_params > code.arrays || code.ops.is_empty() {
+      return Err(format!("bad frame for {}", code.name));
+    }
+    for op in &code.ops {
+      let bad = match op {
+        Op::Mov(x, y) => *x >= code.slots || scalar(y),
+        Op::Binary(_, x, y, z) => *x >= code.slots || scalar(y) || scalar(z),
+        Op::Array(arr, size) => *arr >= code.arrays || scalar(size),
+        Op::Store(arr, i, x) => *arr >= code.arrays || scalar(i) || scalar(x),
+        Op::Load(x, arr, i) => *x >= code.slots || *arr >= code.arrays || scalar(i),
+        Op::Out(x) | Op::Ret(x) => scalar(x),
+        Op::Input(x) => *x >= code.slots,
+        Op::Call(x, f, args, arrays) => {
+          *x >= code.slots || *f >= functions || args.iter().any(scalar) || arrays.iter().any(array)
+            || args.len() as u32 != program.functions[*f as usize].params
+            || arrays.len() as u32 != program.functions[*f as usize].array_params
+        }
+        Op::Jmp(target) => *target >= length,
+        Op::BranchIf(x, target) | Op::BranchIfn(x, target) => scalar(x) || *target >= length,
+        Op::JumpTable(x, default, targets) => scalar(x) || *default >= length || targets.iter().any(|target| *target >= length),
+        Op::GlobalStore(arr, i, x) => *arr >= globals || scalar(i) || scalar(x),
+        Op::GlobalLoad(x, arr, i) => *x >= code.slots || *arr >= globals || scalar(i),
+        Op::Check(i, size, _, _) => scalar(i) || scalar(size),
+      };
+      if bad {
+        return Err(format!("invalid instruction in {}: {op:?}", code.name));
+      }
+    }
+    if !matches!(code.ops.last(), Some(Op::Ret(_)) | Some(Op::Jmp(_)) | Some(Op::JumpTable(_, _, _))) {
+      return Err(format!("{} can run past its last instruction", code.name));
+    }
+  }
+  return Ok(());
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Program, String> {
+  if bytes.len() < MAGIC.len() + 2 + 4 || &bytes[..4] != MAGIC {
+    return Err(String::from("not a .ttbc file"));
+  }
+  let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+  if version != VERSION {
+    return Err(format!("unsupported bytecode version {version} (expected {VERSION})"));
+  }
+  let (body, sum) = bytes.split_at(bytes.len() - 4);
+  if checksum(body) != u32::from_le_bytes([sum[0], sum[1], sum[2], sum[3]]) {
+    return Err(String::from("bytecode checksum mismatch"));
+  }
+
+  let mut reader = Reader { bytes: body, pos: 6 };
+  let overflow = match reader.byte()? {
+    0 => Overflow::Wrap,
+    1 => Overflow::Trap,
+    2 => Overflow::Saturate,
+    mode => return Err(format!("bad overflow mode {mode}")),
+  };
+  let mut globals: Vec<u32> = vec![];
+  for _ in 0..reader.u32()? {
+    globals.push(reader.u32()?);
+  }
+  let count = reader.u32()?;
+  let main = reader.u32()?;
+  let mut functions: Vec<Code> = vec![];
+  for _ in 0..count {
+    let length = reader.u32()? as usize;
+    let name = match String::from_utf8(reader.take(length)?.to_vec()) {
+      Ok(name) => name,
+      Err(_) => return Err(String::from("bad function name")),
+    };
+    let (params, array_params, slots, arrays, ops) = (reader.u32()?, reader.u32()?, reader.u32()?, reader.u32()?, reader.u32()?);
+    let mut code = Code { name, params, array_params, slots, arrays, ops: vec![] };
+    for _ in 0..ops {
+      code.ops.push(reader.op()?);
+    }
+    functions.push(code);
+  }
+  if reader.pos != body.len() {
+    return Err(String::from("trailing data after the program"));
+  }
+  let program = Program { overflow, functions, main, globals };
+  validate(&program)?;
+  return Ok(program);
+}
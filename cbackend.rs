@@ -0,0 +1,217 @@
+// C99 backend. one C function per %func, int32_t locals and fixed-size
+// arrays, static arrays for globals, pointers for array parameters,
+// printf/scanf for %out/%input. arithmetic
+// goes through helpers that work out the exact result in 64 bits and then
+// wrap, stop or saturate as the program's overflow mode says, and that stop on
+// division by zero, matching the VM; a failed %check stops the same way.
+use std::collections::HashSet;
+use std::path::Path;
+use std::process::Command;
+use crate::ir::{self, Function, Instr, Overflow};
+
+const PRELUDE: &str = "#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+static void tt_division_by_zero(void) {
+  printf(\"runtime error: division by zero\\n\");
+  exit(1);
+}
+
+static void tt_out_of_bounds(int32_t index, int32_t size, const char *arr, int line) {
+  printf(\"runtime error: line %d: index %d out of bounds for array %s of size %d\\n\", line, (int)index, arr, (int)size);
+  exit(1);
+}
+
+static void tt_overflow(void) {
+  printf(\"runtime error: integer overflow\\n\");
+  exit(1);
+}
+
+static void tt_out(int32_t x) { printf(\"%d\\n\", (int)x); }
+
+// 0 at the end of input, and a runtime error unless the whole word is an int.
+static int32_t tt_input(void) {
+  char word[64], *end;
+  if (scanf(\"%63s\", word) != 1) return 0;
+  long x = strtol(word, &end, 10);
+  if (end == word || *end != '\\0' || x < INT32_MIN || x > INT32_MAX) {
+    printf(\"runtime error: invalid input '%s'\\n\", word);
+    exit(1);
+  }
+  return (int32_t)x;
+}
+";
+
+fn var(name: &String) -> String {
+  if ir::is_constant(name) {
+    // INT_MIN has no literal form in C.
+    return if name == "-2147483648" { String::from("INT32_MIN") } else { name.clone() };
+  }
+  format!("v_{name}")
+}
+
+// tt_fit turns an exact 64-bit result into the int the overflow mode asks for.
+fn arithmetic(overflow: Overflow) -> String {
+  let fit = match overflow {
+    Overflow::Wrap => "  return (int32_t)(uint32_t)x;",
+    Overflow::Trap => "  if (x < INT32_MIN || x > INT32_MAX) tt_overflow();\n  return (int32_t)x;",
+    Overflow::Saturate => "  return x < INT32_MIN ? INT32_MIN : x > INT32_MAX ? INT32_MAX : (int32_t)x;",
+  };
+  return format!("static int32_t tt_fit(int64_t x) {{
+{fit}
+}}
+
+static int32_t tt_add(int32_t a, int32_t b) {{ return tt_fit((int64_t)a + b); }}
+static int32_t tt_sub(int32_t a, int32_t b) {{ return tt_fit((int64_t)a - b); }}
+static int32_t tt_mult(int32_t a, int32_t b) {{ return tt_fit((int64_t)a * b); }}
+
+static int32_t tt_div(int32_t a, int32_t b) {{
+  if (b == 0) tt_division_by_zero();
+  return tt_fit((int64_t)a / b);
+}}
+
+static int32_t tt_mod(int32_t a, int32_t b) {{
+  if (b == 0) tt_division_by_zero();
+  if (b == -1) return 0;
+  return a % b;
+}}
+");
+}
+
+fn global(name: &String) -> String {
+  format!("g_{name}")
+}
+
+fn label(name: &String) -> String {
+  format!("L_{}", &name[1..])
+}
+
+fn signature(function: &Function) -> String {
+  let params: Vec<String> = function.params.iter().map(|p| {
+    if function.array_params.contains(p) { format!("int32_t *{}", var(p)) } else { format!("int32_t {}", var(p)) }
+  }).collect();
+  let params = if params.is_empty() { String::from("void") } else { params.join(", ") };
+  format!("static int32_t f_{}({params})", function.name)
+}
+
+fn generate_function(function: &Function, globals: &HashSet<String>) -> Result<String, String> {
+  let mut code = format!("{} {{\n", signature(function));
+
+  let mut declared: Vec<&String> = function.params.iter().collect();
+  for instr in &function.body {
+    if let Instr::IntArray(x, size) = instr {
+      if declared.contains(&x) {
+        continue;
+      }
+      match size.parse::<i32>() {
+        Ok(length) if length > 0 => code += &format!("  int32_t {}[{length}] = {{0}};\n", var(x)),
+        _ => return Err(format!("array {x} in {} needs a constant size for C", function.name)),
+      }
+      declared.push(x);
+    }
+  }
+  for instr in &function.body {
+    let mut names: Vec<&String> = ir::uses(instr);
+    names.extend(ir::def(instr));
+    if let Instr::Int(x) = instr {
+      names.push(x);
+    }
+    for name in names {
+      if !ir::is_constant(name) && !declared.contains(&name) {
+        code += &format!("  int32_t {} = 0;\n", var(name));
+        declared.push(name);
+      }
+    }
+  }
+
+  let array = |arr: &String| -> String {
+    if globals.contains(arr) && !declared.contains(&arr) { global(arr) } else { var(arr) }
+  };
+  for instr in &function.body {
+    let line = match instr {
+      Instr::Int(_) | Instr::IntArray(_, _) => continue,
+      Instr::Mov(x, y) => format!("{} = {};", var(x), var(y)),
+      Instr::Store(arr, i, x) => format!("{}[{}] = {};", array(arr), var(i), var(x)),
+      Instr::Load(x, arr, i) => format!("{} = {}[{}];", var(x), array(arr), var(i)),
+      Instr::Binary(op, x, y, z) => {
+        let (y, z) = (var(y), var(z));
+        let value = match op.as_str() {
+          "lt" => format!("{y} < {z}"),
+          "le" => format!("{y} <= {z}"),
+          "gt" => format!("{y} > {z}"),
+          "ge" => format!("{y} >= {z}"),
+          "eq" => format!("{y} == {z}"),
+          "neq" => format!("{y} != {z}"),
+          _ => format!("tt_{op}({y}, {z})"),
+        };
+        format!("{} = {value};", var(x))
+      }
+      Instr::Out(x) => format!("tt_out({});", var(x)),
+      Instr::Input(x) => format!("{} = tt_input();", var(x)),
+      Instr::Call(x, f, args) => {
+        let args: Vec<String> = args.iter().map(|arg| match ir::array_arg(arg) {
+          Some(arr) => array(&arr.to_string()),
+          None => var(arg),
+        }).collect();
+        format!("{} = f_{f}({});", var(x), args.join(", "))
+      }
+      Instr::Check(i, size, arr, line) => {
+        let (i, size) = (var(i), var(size));
+        format!("if ({i} < 0 || {i} >= {size}) tt_out_of_bounds({i}, {size}, \"{arr}\", {line});")
+      }
+      Instr::Ret(x) => format!("return {};", var(x)),
+      Instr::Jmp(l) => format!("goto {};", label(l)),
+      Instr::BranchIf(x, l) => format!("if ({}) goto {};", var(x), label(l)),
+      Instr::BranchIfn(x, l) => format!("if (!{}) goto {};", var(x), label(l)),
+      Instr::JumpTable(x, default, table) => {
+        let cases: Vec<String> = table.iter().enumerate().map(|(k, l)| format!("case {k}: goto {};", label(l))).collect();
+        format!("switch ({}) {{ {} default: goto {}; }}", var(x), cases.join(" "), label(default))
+      }
+      Instr::Label(l) => {
+        code += &format!("{}:;\n", label(l));
+        continue;
+      }
+      Instr::Phi(_, _) => return Err(String::from("phi instructions must be removed before code generation")),
+    };
+    code += "  ";
+    code += &line;
+    code += "\n";
+  }
+  code += "  return 0;\n}\n";
+  return Ok(code);
+}
+
+pub fn generate(program: &ir::Program) -> Result<String, String> {
+  let globals = ir::global_names(program);
+  let mut code = String::from(PRELUDE);
+  code += "\n";
+  code += &arithmetic(program.overflow);
+  code += "\n";
+  for global in &program.globals {
+    code += &format!("static int32_t {}[{}];\n", self::global(&global.name), global.size);
+  }
+  for function in &program.functions {
+    code += &format!("{};\n", signature(function));
+  }
+  for function in &program.functions {
+    code += "\n";
+    code += &generate_function(function, &globals)?;
+  }
+  code += "\nint main(void) {\n  f_main();\n  return 0;\n}\n";
+  return Ok(code);
+}
+
+// build with the system compiler; undefined behaviour in the output is a backend bug.
+pub fn compile(source: &Path, output: &Path) -> Result<(), String> {
+  let status = Command::new("cc")
+    .args(["-std=c99", "-O1", "-fsanitize=undefined", "-fno-sanitize-recover=undefined", "-o"])
+    .arg(output)
+    .arg(source)
+    .status();
+  match status {
+    Ok(status) if status.success() => Ok(()),
+    Ok(status) => Err(format!("cc failed with {status}")),
+    Err(error) => Err(format!("could not run cc: {error}")),
+  }
+}
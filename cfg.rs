@@ -0,0 +1,214 @@
+// control flow graph over a function's instructions: basic blocks,
+// successors/predecessors and the dominator tree.
+use std::collections::HashMap;
+use crate::ir::{Function, Instr};
+
+#[derive(Debug, Clone)]
+pub struct Block {
+  pub start: usize, // index of the first instruction in the function body
+  pub end: usize,   // one past the last instruction
+  pub succs: Vec<usize>,
+  pub preds: Vec<usize>,
+}
+
+pub struct Cfg {
+  pub blocks: Vec<Block>,
+  pub idom: Vec<Option<usize>>, // immediate dominator, None for the entry and unreachable blocks
+  pub order: Vec<usize>,        // reachable blocks in reverse post-order
+}
+
+fn ends_block(instr: &Instr) -> bool {
+  matches!(instr, Instr::Jmp(_) | Instr::BranchIf(_, _) | Instr::BranchIfn(_, _) | Instr::JumpTable(_, _, _) | Instr::Ret(_))
+}
+
+pub fn build(function: &Function) -> Cfg {
+  let body = &function.body;
+  let mut blocks: Vec<Block> = vec![];
+  let mut start = 0;
+  for (i, instr) in body.iter().enumerate() {
+    let label_starts = i > start && matches!(instr, Instr::Label(_));
+    if label_starts {
+      blocks.push(Block { start, end: i, succs: vec![], preds: vec![] });
+      start = i;
+    }
+    if ends_block(instr) {
+      blocks.push(Block { start, end: i + 1, succs: vec![], preds: vec![] });
+      start = i + 1;
+    }
+  }
+  if start < body.len() || blocks.is_empty() {
+    blocks.push(Block { start, end: body.len(), succs: vec![], preds: vec![] });
+  }
+
+  let mut labels: HashMap<&String, usize> = HashMap::new();
+  for (b, block) in blocks.iter().enumerate() {
+    if let Some(Instr::Label(label)) = body.get(block.start) {
+      labels.insert(label, b);
+    }
+  }
+
+  for b in 0..blocks.len() {
+    let last = if blocks[b].end > blocks[b].start { body.get(blocks[b].end - 1) } else { None };
+    let mut succs: Vec<usize> = vec![];
+    match last {
+      Some(Instr::Jmp(label)) => succs.extend(labels.get(label)),
+      Some(Instr::BranchIf(_, label)) | Some(Instr::BranchIfn(_, label)) => {
+        succs.extend(labels.get(label));
+        if b + 1 < blocks.len() && !succs.contains(&(b + 1)) {
+          succs.push(b + 1);
+        }
+      }
+      Some(Instr::JumpTable(_, default, table)) => {
+        for label in std::iter::once(default).chain(table.iter()) {
+          if let Some(&s) = labels.get(label) {
+            if !succs.contains(&s) {
+              succs.push(s);
+            }
+          }
+        }
+      }
+      Some(Instr::Ret(_)) => {}
+      _ => {
+        if b + 1 < blocks.len() {
+          succs.push(b + 1);
+        }
+      }
+    }
+    for s in &succs {
+      blocks[*s].preds.push(b);
+    }
+    blocks[b].succs = succs;
+  }
+
+  let order = reverse_post_order(&blocks);
+  let idom = dominators(&blocks, &order);
+  return Cfg { blocks, idom, order };
+}
+
+fn reverse_post_order(blocks: &Vec<Block>) -> Vec<usize> {
+  let mut seen = vec![false; blocks.len()];
+  let mut order: Vec<usize> = vec![];
+  // iterative dfs, each stack entry is (block, next successor to visit)
+  let mut stack: Vec<(usize, usize)> = vec![(0, 0)];
+  seen[0] = true;
+  while let Some((b, next)) = stack.pop() {
+    if next < blocks[b].succs.len() {
+      stack.push((b, next + 1));
+      let s = blocks[b].succs[next];
+      if !seen[s] {
+        seen[s] = true;
+        stack.push((s, 0));
+      }
+    } else {
+      order.push(b);
+    }
+  }
+  order.reverse();
+  return order;
+}
+
+// Cooper, Harvey and Kennedy's iterative algorithm.
+fn dominators(blocks: &Vec<Block>, order: &Vec<usize>) -> Vec<Option<usize>> {
+  let mut position = vec![usize::MAX; blocks.len()];
+  for (i, b) in order.iter().enumerate() {
+    position[*b] = i;
+  }
+
+  let mut idom: Vec<Option<usize>> = vec![None; blocks.len()];
+  idom[0] = Some(0);
+  let mut changed = true;
+  while changed {
+    changed = false;
+    for &b in order.iter().skip(1) {
+      let mut new_idom: Option<usize> = None;
+      for &p in &blocks[b].preds {
+        if idom[p].is_none() {
+          continue;
+        }
+        new_idom = match new_idom {
+          None => Some(p),
+          Some(other) => {
+            let (mut x, mut y) = (p, other);
+            while x != y {
+              while position[x] > position[y] {
+                x = idom[x].unwrap();
+              }
+              while position[y] > position[x] {
+                y = idom[y].unwrap();
+              }
+            }
+            Some(x)
+          }
+        };
+      }
+      if new_idom != idom[b] {
+        idom[b] = new_idom;
+        changed = true;
+      }
+    }
+  }
+  idom[0] = None;
+  return idom;
+}
+
+impl Cfg {
+  pub fn reachable(&self, b: usize) -> bool {
+    b == 0 || self.idom[b].is_some()
+  }
+
+  // does block `a` dominate block `b`?
+  pub fn dominates(&self, a: usize, b: usize) -> bool {
+    if !self.reachable(b) {
+      return false;
+    }
+    let mut current = b;
+    loop {
+      if current == a {
+        return true;
+      }
+      match self.idom[current] {
+        Some(parent) => current = parent,
+        None => return false,
+      }
+    }
+  }
+
+  // block containing the instruction at `index`.
+  pub fn block_of(&self, index: usize) -> usize {
+    self.blocks.iter().position(|block| index >= block.start && index < block.end).unwrap_or(self.blocks.len() - 1)
+  }
+
+  // children of each block in the dominator tree.
+  pub fn dominator_tree(&self) -> Vec<Vec<usize>> {
+    let mut children: Vec<Vec<usize>> = vec![vec![]; self.blocks.len()];
+    for &b in &self.order {
+      if let Some(parent) = self.idom[b] {
+        children[parent].push(b);
+      }
+    }
+    return children;
+  }
+
+  pub fn dominance_frontiers(&self) -> Vec<Vec<usize>> {
+    let mut frontiers: Vec<Vec<usize>> = vec![vec![]; self.blocks.len()];
+    for b in 0..self.blocks.len() {
+      let preds: Vec<usize> = self.blocks[b].preds.iter().cloned().filter(|p| self.reachable(*p)).collect();
+      if !self.reachable(b) || preds.len() < 2 {
+        continue;
+      }
+      for p in preds {
+        let mut runner = p;
+        while Some(runner) != self.idom[b] {
+          if !frontiers[runner].contains(&b) {
+            frontiers[runner].push(b);
+          }
+          match self.idom[runner] {
+            Some(parent) => runner = parent,
+            None => break,
+          }
+        }
+      }
+    }
+    return frontiers;
+  }
+}
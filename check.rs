@@ -0,0 +1,45 @@
+// --check=KIND builds the program with a backend, runs it, and compares what
+// it prints with the VM's output for the same input.
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+// run a program with `input` on stdin and collect what it prints.
+pub fn run(program: &Path, args: &Vec<String>, input: &String) -> Result<String, String> {
+  // a bare file name would be looked up on PATH instead of the current
+  // directory; tools like lli that are not here still come from PATH.
+  let program = if program.is_relative() && program.exists() { Path::new(".").join(program) } else { program.to_path_buf() };
+  let child = Command::new(&program).args(args).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn();
+  let mut child = match child {
+    Ok(child) => child,
+    Err(error) => return Err(format!("could not run {}: {error}", program.display())),
+  };
+  if let Some(mut stdin) = child.stdin.take() {
+    let _ = stdin.write_all(input.as_bytes());
+  }
+  match child.wait_with_output() {
+    Ok(output) => Ok(String::from_utf8_lossy(&output.stdout).to_string()),
+    Err(error) => Err(format!("{} failed: {error}", program.display())),
+  }
+}
+
+pub fn read_stdin() -> String {
+  let mut input = String::new();
+  let _ = std::io::stdin().read_to_string(&mut input);
+  return input;
+}
+
+// print both outputs and say whether they agree.
+pub fn compare(kind: &str, expected: &String, actual: &String) -> Result<(), String> {
+  println!("VM output:");
+  print!("{expected}");
+  println!("---------------------------");
+  println!("{kind} output:");
+  print!("{actual}");
+  println!("---------------------------");
+  if expected == actual {
+    println!("outputs match");
+    return Ok(());
+  }
+  return Err(format!("{kind} output differs from the VM"));
+}
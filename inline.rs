@@ -0,0 +1,192 @@
+// function inlining. small non-recursive callees are copied into their
+// callers with every local, temp and label renamed so the copies can't clash.
+use std::collections::{HashMap, HashSet};
+use crate::ir::{self, Function, Instr};
+
+pub const DEFAULT_THRESHOLD: usize = 24;
+
+fn callees(function: &Function) -> Vec<String> {
+  let mut names: Vec<String> = vec![];
+  for instr in &function.body {
+    if let Instr::Call(_, callee, _) = instr {
+      if !names.contains(callee) {
+        names.push(callee.clone());
+      }
+    }
+  }
+  return names;
+}
+
+// instructions that do work; declarations and labels don't count towards the threshold.
+pub fn size(function: &Function) -> usize {
+  function.body.iter().filter(|instr| !matches!(instr, Instr::Int(_) | Instr::IntArray(_, _) | Instr::Label(_))).count()
+}
+
+// functions that can reach themselves through the call graph.
+fn recursive_functions(graph: &HashMap<String, Vec<String>>) -> HashSet<String> {
+  let mut recursive: HashSet<String> = HashSet::new();
+  for start in graph.keys() {
+    let mut stack: Vec<&String> = graph[start].iter().collect();
+    let mut seen: HashSet<&String> = HashSet::new();
+    while let Some(name) = stack.pop() {
+      if name == start {
+        recursive.insert(start.clone());
+        break;
+      }
+      if !seen.insert(name) {
+        continue;
+      }
+      if let Some(next) = graph.get(name) {
+        stack.extend(next.iter());
+      }
+    }
+  }
+  return recursive;
+}
+
+// whether every array the function declares has a constant size. the copy
+// clears its arrays before the body runs, when a size computed by the body
+// isn't known yet, so callees with other arrays stay calls.
+fn fixed_arrays(function: &Function) -> bool {
+  function.body.iter().all(|instr| match instr {
+    Instr::IntArray(_, size) => ir::is_constant(size),
+    _ => true,
+  })
+}
+
+// callees before callers, so inlined bodies are already expanded.
+fn post_order(name: &String, graph: &HashMap<String, Vec<String>>, seen: &mut HashSet<String>, order: &mut Vec<String>) {
+  if !seen.insert(name.clone()) {
+    return;
+  }
+  if let Some(next) = graph.get(name) {
+    for callee in next {
+      post_order(callee, graph, seen, order);
+    }
+  }
+  order.push(name.clone());
+}
+
+pub fn inline_program(functions: &mut Vec<Function>, threshold: usize) {
+  let mut graph: HashMap<String, Vec<String>> = HashMap::new();
+  for function in functions.iter() {
+    graph.insert(function.name.clone(), callees(function));
+  }
+  let recursive = recursive_functions(&graph);
+
+  let mut order: Vec<String> = vec![];
+  let mut seen: HashSet<String> = HashSet::new();
+  for function in functions.iter() {
+    post_order(&function.name, &graph, &mut seen, &mut order);
+  }
+
+  for name in &order {
+    let position = match functions.iter().position(|f| &f.name == name) {
+      Some(position) => position,
+      None => continue,
+    };
+
+    let mut body: Vec<Instr> = vec![];
+    for instr in &functions[position].body {
+      if let Instr::Call(dest, callee, args) = instr {
+        let candidate = functions.iter().find(|f| &f.name == callee);
+        if let Some(candidate) = candidate {
+          if callee != name && !recursive.contains(callee) && size(candidate) <= threshold && fixed_arrays(candidate) {
+            body.extend(expand(candidate, dest, args));
+            continue;
+          }
+        }
+      }
+      body.push(instr.clone());
+    }
+    functions[position].body = body;
+  }
+}
+
+// copy of `callee` that stores its return value into `dest`. array
+// parameters become the arrays the caller passed, since those are shared.
+fn expand(callee: &Function, dest: &String, args: &Vec<String>) -> Vec<Instr> {
+  let number = crate::create_num();
+  let mut passed: HashMap<&String, String> = HashMap::new();
+  for (param, arg) in callee.params.iter().zip(args) {
+    if let Some(arr) = ir::array_arg(arg) {
+      passed.insert(param, arr.to_string());
+    }
+  }
+  let mut locals: HashSet<&String> = callee.params.iter().collect();
+  for instr in &callee.body {
+    match instr {
+      Instr::Int(x) | Instr::IntArray(x, _) => { locals.insert(x); }
+      _ => {}
+    }
+  }
+  let rename = |operand: &String| -> String {
+    if let Some(arr) = passed.get(operand) {
+      arr.clone()
+    } else if locals.contains(operand) {
+      format!("_inline{number}_{operand}")
+    } else {
+      operand.clone()
+    }
+  };
+  let relabel = |label: &String| format!(":inline{number}_{}", &label[1..]);
+  let end = format!(":inline{number}_end");
+
+  let mut code: Vec<Instr> = vec![];
+  for (param, arg) in callee.params.iter().zip(args) {
+    if passed.contains_key(param) {
+      continue;
+    }
+    code.push(Instr::Int(rename(param)));
+    code.push(Instr::Mov(rename(param), arg.clone()));
+  }
+  // a fresh call starts its locals at zero, but the caller's frame is only
+  // zeroed once, so they are declared and cleared before the copied body.
+  for instr in &callee.body {
+    match instr {
+      Instr::Int(x) if !callee.params.contains(x) => {
+        code.push(Instr::Int(rename(x)));
+        code.push(Instr::Mov(rename(x), String::from("0")));
+      }
+      Instr::IntArray(x, size) => code.extend(clear(rename(x), &rename(size))),
+      _ => {}
+    }
+  }
+  if !matches!(callee.body.last(), Some(Instr::Ret(_))) {
+    code.push(Instr::Mov(dest.clone(), String::from("0")));
+  }
+
+  for instr in &callee.body {
+    match instr {
+      Instr::Ret(x) => {
+        code.push(Instr::Mov(dest.clone(), rename(x)));
+        code.push(Instr::Jmp(end.clone()));
+      }
+      Instr::Int(x) if !callee.params.contains(x) => {}
+      Instr::IntArray(_, _) => {}
+      _ => code.push(ir::rename(instr, &rename, &relabel)),
+    }
+  }
+  code.push(Instr::Label(end));
+  return code;
+}
+
+// declaration of `arr` followed by a loop that stores 0 in each element.
+fn clear(arr: String, size: &String) -> Vec<Instr> {
+  let number = crate::create_num();
+  let (i, more) = (crate::create_temp(), crate::create_temp());
+  let (top, done) = (format!(":clear{number}"), format!(":clear{number}_end"));
+  return vec![
+    Instr::IntArray(arr.clone(), size.clone()),
+    Instr::Int(i.clone()),
+    Instr::Int(more.clone()),
+    Instr::Mov(i.clone(), String::from("0")),
+    Instr::Label(top.clone()),
+    Instr::Binary(String::from("lt"), more.clone(), i.clone(), size.clone()),
+    Instr::BranchIfn(more, done.clone()),
+    Instr::Store(arr, i.clone(), String::from("0")),
+    Instr::Binary(String::from("add"), i.clone(), i.clone(), String::from("1")),
+    Instr::Jmp(top),
+    Instr::Label(done),
+  ];
+}
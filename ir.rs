@@ -0,0 +1,502 @@
+// structured view of the intermediate code emitted by the parser.
+// optimisation passes parse the generated text into functions and
+// instructions, rewrite them, and print them back to the same text form.
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instr {
+  Int(String),                             // %int x
+  IntArray(String, String),                // %int[] x, size
+  Mov(String, String),                     // %mov x, y
+  Store(String, String, String),           // %mov [arr + i], x
+  Load(String, String, String),            // %mov x, [arr + i]
+  Binary(String, String, String, String),  // %add x, y, z (op without '%')
+  Out(String),                             // %out x
+  Input(String),                           // %input x
+  Call(String, String, Vec<String>),       // %call x, f(a, b, [arr]) (arrays are passed by reference)
+  Ret(String),                             // %ret x
+  Jmp(String),                             // %jmp :label
+  BranchIf(String, String),                // %branch_if x, :label
+  BranchIfn(String, String),               // %branch_ifn x, :label
+  JumpTable(String, String, Vec<String>),  // %jump_table x, :default, :l0, :l1, ... (to :lx, or :default if x is out of range)
+  Label(String),                           // :label
+  Phi(String, Vec<(String, String)>),      // %phi x.2, [x.0, :pred1], [x.1, :pred2] (ssa form only)
+  Check(String, String, String, u32),      // %check i, size, arr, line (runtime error unless 0 <= i < size)
+}
+
+#[derive(Debug, Clone)]
+pub struct Function {
+  pub name: String,
+  pub params: Vec<String>,
+  pub array_params: Vec<String>, // the params declared %int[], in the order they appear
+  pub body: Vec<Instr>,
+}
+
+// module-level storage declared before the functions with %global x or
+// %global[] t, 10, zeroed before main runs. code reaches it only through
+// loads and stores, a scalar global being a one-element array read and
+// written as [x + 0].
+#[derive(Debug, Clone)]
+pub struct Global {
+  pub name: String,
+  pub size: i32,
+  pub array: bool,
+}
+
+// what add, sub, mult and div do with a result that does not fit in an int.
+// written before everything else as %overflow trap or %overflow saturate;
+// wrapping around is the default and is left out.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Overflow {
+  Wrap,
+  Trap,
+  Saturate,
+}
+
+impl Overflow {
+  pub fn parse(mode: &str) -> Option<Overflow> {
+    match mode {
+      "wrap" => Some(Overflow::Wrap),
+      "trap" => Some(Overflow::Trap),
+      "saturate" => Some(Overflow::Saturate),
+      _ => None,
+    }
+  }
+
+  pub fn name(self) -> &'static str {
+    match self {
+      Overflow::Wrap => "wrap",
+      Overflow::Trap => "trap",
+      Overflow::Saturate => "saturate",
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct Program {
+  pub overflow: Overflow,
+  pub globals: Vec<Global>,
+  pub functions: Vec<Function>,
+}
+
+pub const BINARY_OPS: [&str; 11] = ["add", "sub", "mult", "div", "mod", "lt", "le", "gt", "ge", "eq", "neq"];
+
+pub fn is_constant(operand: &str) -> bool {
+  operand.parse::<i32>().is_ok()
+}
+
+pub fn parse_program(code: &str) -> Result<Program, String> {
+  let mut overflow = Overflow::Wrap;
+  let mut globals: Vec<Global> = vec![];
+  let mut functions: Vec<Function> = vec![];
+  let mut current: Option<Function> = None;
+  for line in code.lines() {
+    let line = line.trim();
+    if line.is_empty() {
+      continue;
+    }
+
+    if let Some(mode) = line.strip_prefix("%overflow") {
+      if current.is_some() || !globals.is_empty() || !functions.is_empty() {
+        return Err(String::from("%overflow must come first"));
+      }
+      overflow = Overflow::parse(mode.trim()).ok_or(format!("malformed overflow mode '{line}'"))?;
+      continue;
+    }
+
+    if let Some(header) = line.strip_prefix("%func") {
+      if current.is_some() {
+        return Err(String::from("nested %func in intermediate code"));
+      }
+      current = Some(parse_header(header)?);
+      continue;
+    }
+
+    if line == "%endfunc" {
+      match current.take() {
+        Some(function) => functions.push(function),
+        None => return Err(String::from("%endfunc without %func")),
+      }
+      continue;
+    }
+
+    match current.as_mut() {
+      Some(function) => function.body.push(parse_instr(line)?),
+      None if line.starts_with("%global") => globals.push(parse_global(line)?),
+      None => return Err(format!("instruction outside of a function: {line}")),
+    }
+  }
+
+  if current.is_some() {
+    return Err(String::from("missing %endfunc"));
+  }
+  return Ok(Program { overflow, globals, functions });
+}
+
+fn parse_global(line: &str) -> Result<Global, String> {
+  if let Some(rest) = line.strip_prefix("%global[]") {
+    let ops = split_operands(rest);
+    let size = match ops.get(1).map(|size| size.parse::<i32>()) {
+      Some(Ok(size)) if ops.len() == 2 && size > 0 => size,
+      _ => return Err(format!("malformed global array '{line}'")),
+    };
+    return Ok(Global { name: ops[0].clone(), size, array: true });
+  }
+  let name = line["%global".len()..].trim();
+  if name.is_empty() || name.contains(',') {
+    return Err(format!("malformed global '{line}'"));
+  }
+  return Ok(Global { name: name.to_string(), size: 1, array: false });
+}
+
+fn parse_header(header: &str) -> Result<Function, String> {
+  let open = header.find('(').ok_or(String::from("expected '(' in %func"))?;
+  let close = header.rfind(')').ok_or(String::from("expected ')' in %func"))?;
+  let name = header[..open].trim().to_string();
+  let mut params: Vec<String> = vec![];
+  let mut array_params: Vec<String> = vec![];
+  for param in header[open + 1..close].split(',') {
+    let param = param.trim();
+    if param.is_empty() {
+      continue;
+    }
+    if let Some(ident) = param.strip_prefix("%int[]") {
+      params.push(ident.trim().to_string());
+      array_params.push(ident.trim().to_string());
+      continue;
+    }
+    match param.strip_prefix("%int") {
+      Some(ident) => params.push(ident.trim().to_string()),
+      None => return Err(format!("invalid parameter '{param}'")),
+    }
+  }
+  return Ok(Function { name, params, array_params, body: vec![] });
+}
+
+fn split_operands(text: &str) -> Vec<String> {
+  text.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect()
+}
+
+// "[arr + i]" -> (arr, i)
+fn parse_address(text: &str) -> Option<(String, String)> {
+  let inner = text.trim().strip_prefix('[')?.strip_suffix(']')?;
+  let (arr, idx) = inner.split_once('+')?;
+  return Some((arr.trim().to_string(), idx.trim().to_string()));
+}
+
+pub fn parse_instr(line: &str) -> Result<Instr, String> {
+  if line.starts_with(':') {
+    return Ok(Instr::Label(line.to_string()));
+  }
+
+  let (op, rest) = match line.find(char::is_whitespace) {
+    Some(i) => (&line[..i], line[i..].trim()),
+    None => (line, ""),
+  };
+
+  let bad = || format!("malformed instruction '{line}'");
+  match op {
+    "%int" => Ok(Instr::Int(rest.to_string())),
+    "%int[]" => {
+      let ops = split_operands(rest);
+      if ops.len() != 2 {
+        return Err(bad());
+      }
+      Ok(Instr::IntArray(ops[0].clone(), ops[1].clone()))
+    }
+    "%mov" => {
+      if rest.starts_with('[') {
+        let close = rest.find(']').ok_or_else(bad)?;
+        let (arr, idx) = parse_address(&rest[..=close]).ok_or_else(bad)?;
+        let src = rest[close + 1..].trim().strip_prefix(',').ok_or_else(bad)?.trim();
+        return Ok(Instr::Store(arr, idx, src.to_string()));
+      }
+      let (dest, src) = rest.split_once(',').ok_or_else(bad)?;
+      match parse_address(src) {
+        Some((arr, idx)) => Ok(Instr::Load(dest.trim().to_string(), arr, idx)),
+        None => Ok(Instr::Mov(dest.trim().to_string(), src.trim().to_string())),
+      }
+    }
+    "%out" => Ok(Instr::Out(rest.to_string())),
+    "%input" => Ok(Instr::Input(rest.to_string())),
+    "%ret" => Ok(Instr::Ret(rest.to_string())),
+    "%jmp" => Ok(Instr::Jmp(rest.to_string())),
+    "%branch_if" | "%branch_ifn" => {
+      let ops = split_operands(rest);
+      if ops.len() != 2 {
+        return Err(bad());
+      }
+      if op == "%branch_if" {
+        Ok(Instr::BranchIf(ops[0].clone(), ops[1].clone()))
+      } else {
+        Ok(Instr::BranchIfn(ops[0].clone(), ops[1].clone()))
+      }
+    }
+    "%jump_table" => {
+      let ops = split_operands(rest);
+      if ops.len() < 2 {
+        return Err(bad());
+      }
+      Ok(Instr::JumpTable(ops[0].clone(), ops[1].clone(), ops[2..].to_vec()))
+    }
+    "%phi" => {
+      let (dest, args) = rest.split_once(',').ok_or_else(bad)?;
+      let mut incoming: Vec<(String, String)> = vec![];
+      for arg in args.split(']') {
+        let arg = arg.trim().trim_start_matches(',').trim();
+        if arg.is_empty() {
+          continue;
+        }
+        let (value, label) = arg.strip_prefix('[').and_then(|a| a.split_once(',')).ok_or_else(bad)?;
+        incoming.push((value.trim().to_string(), label.trim().to_string()));
+      }
+      Ok(Instr::Phi(dest.trim().to_string(), incoming))
+    }
+    "%check" => {
+      let ops = split_operands(rest);
+      match ops.get(3).map(|line| line.parse::<u32>()) {
+        Some(Ok(line)) if ops.len() == 4 => Ok(Instr::Check(ops[0].clone(), ops[1].clone(), ops[2].clone(), line)),
+        _ => Err(bad()),
+      }
+    }
+    "%call" => {
+      let (dest, call) = rest.split_once(',').ok_or_else(bad)?;
+      let open = call.find('(').ok_or_else(bad)?;
+      let close = call.rfind(')').ok_or_else(bad)?;
+      let func = call[..open].trim().to_string();
+      Ok(Instr::Call(dest.trim().to_string(), func, split_operands(&call[open + 1..close])))
+    }
+    _ => {
+      let name = op.strip_prefix('%').unwrap_or("");
+      if !BINARY_OPS.contains(&name) {
+        return Err(format!("unknown instruction '{line}'"));
+      }
+      let ops = split_operands(rest);
+      if ops.len() != 3 {
+        return Err(bad());
+      }
+      Ok(Instr::Binary(name.to_string(), ops[0].clone(), ops[1].clone(), ops[2].clone()))
+    }
+  }
+}
+
+pub fn print_instr(instr: &Instr) -> String {
+  match instr {
+    Instr::Int(x) => format!("%int {x}"),
+    Instr::IntArray(x, size) => format!("%int[] {x}, {size}"),
+    Instr::Mov(x, y) => format!("%mov {x}, {y}"),
+    Instr::Store(arr, i, x) => format!("%mov [{arr} + {i}], {x}"),
+    Instr::Load(x, arr, i) => format!("%mov {x}, [{arr} + {i}]"),
+    Instr::Binary(op, x, y, z) => format!("%{op} {x}, {y}, {z}"),
+    Instr::Out(x) => format!("%out {x}"),
+    Instr::Input(x) => format!("%input {x}"),
+    Instr::Call(x, f, args) => format!("%call {x}, {f}({})", args.join(", ")),
+    Instr::Ret(x) => format!("%ret {x}"),
+    Instr::Jmp(label) => format!("%jmp {label}"),
+    Instr::BranchIf(x, label) => format!("%branch_if {x}, {label}"),
+    Instr::BranchIfn(x, label) => format!("%branch_ifn {x}, {label}"),
+    Instr::JumpTable(x, default, table) => {
+      let mut operands = vec![x.clone(), default.clone()];
+      operands.extend(table.iter().cloned());
+      format!("%jump_table {}", operands.join(", "))
+    }
+    Instr::Label(label) => label.clone(),
+    Instr::Phi(x, incoming) => {
+      let args: Vec<String> = incoming.iter().map(|(value, label)| format!("[{value}, {label}]")).collect();
+      format!("%phi {x}, {}", args.join(", "))
+    }
+    Instr::Check(i, size, arr, line) => format!("%check {i}, {size}, {arr}, {line}"),
+  }
+}
+
+pub fn print_function(function: &Function) -> String {
+  let params: Vec<String> = function.params.iter().map(|p| {
+    if function.array_params.contains(p) { format!("%int[] {p}") } else { format!("%int {p}") }
+  }).collect();
+  let mut code = format!("%func {} ({})\n", function.name, params.join(", "));
+  for instr in &function.body {
+    code += &print_instr(instr);
+    code += "\n";
+  }
+  code += "%endfunc\n";
+  return code;
+}
+
+pub fn print_global(global: &Global) -> String {
+  if global.array {
+    return format!("%global[] {}, {}", global.name, global.size);
+  }
+  return format!("%global {}", global.name);
+}
+
+pub fn print_program(program: &Program) -> String {
+  let mut code = String::new();
+  if program.overflow != Overflow::Wrap {
+    code += &format!("%overflow {}\n", program.overflow.name());
+  }
+  for global in &program.globals {
+    code += &print_global(global);
+    code += "\n";
+  }
+  for function in &program.functions {
+    code += &print_function(function);
+  }
+  return code;
+}
+
+pub fn global_names(program: &Program) -> HashSet<String> {
+  program.globals.iter().map(|global| global.name.clone()).collect()
+}
+
+// "[arr]" -> arr, for an array passed to a %call.
+pub fn array_arg(operand: &str) -> Option<&str> {
+  operand.strip_prefix('[')?.strip_suffix(']')
+}
+
+// variable written by an instruction, if any.
+pub fn def(instr: &Instr) -> Option<&String> {
+  match instr {
+    Instr::Mov(x, _) | Instr::Load(x, _, _) | Instr::Binary(_, x, _, _) | Instr::Input(x) | Instr::Call(x, _, _) | Instr::Phi(x, _) => Some(x),
+    _ => None,
+  }
+}
+
+// scalar operands read by an instruction (constants included).
+pub fn uses(instr: &Instr) -> Vec<&String> {
+  match instr {
+    Instr::Mov(_, y) => vec![y],
+    Instr::Store(_, i, x) | Instr::Check(i, x, _, _) => vec![i, x],
+    Instr::Load(_, _, i) => vec![i],
+    Instr::Binary(_, _, y, z) => vec![y, z],
+    Instr::Out(x) | Instr::Ret(x) | Instr::BranchIf(x, _) | Instr::BranchIfn(x, _) | Instr::JumpTable(x, _, _) => vec![x],
+    Instr::Call(_, _, args) => args.iter().filter(|arg| array_arg(arg).is_none()).collect(),
+    Instr::IntArray(_, size) => vec![size],
+    Instr::Phi(_, incoming) => incoming.iter().map(|(value, _)| value).collect(),
+    _ => vec![],
+  }
+}
+
+// mutable access to the scalar operands read by an instruction.
+pub fn uses_mut(instr: &mut Instr) -> Vec<&mut String> {
+  match instr {
+    Instr::Mov(_, y) => vec![y],
+    Instr::Store(_, i, x) | Instr::Check(i, x, _, _) => vec![i, x],
+    Instr::Load(_, _, i) => vec![i],
+    Instr::Binary(_, _, y, z) => vec![y, z],
+    Instr::Out(x) | Instr::Ret(x) | Instr::BranchIf(x, _) | Instr::BranchIfn(x, _) | Instr::JumpTable(x, _, _) => vec![x],
+    Instr::Call(_, _, args) => args.iter_mut().filter(|arg| array_arg(arg).is_none()).collect(),
+    Instr::IntArray(_, size) => vec![size],
+    Instr::Phi(_, incoming) => incoming.iter_mut().map(|(value, _)| value).collect(),
+    _ => vec![],
+  }
+}
+
+// labels targeted by a jump, branch or jump table.
+pub fn targets(instr: &Instr) -> Vec<&String> {
+  match instr {
+    Instr::Jmp(label) | Instr::BranchIf(_, label) | Instr::BranchIfn(_, label) => vec![label],
+    Instr::JumpTable(_, default, table) => std::iter::once(default).chain(table.iter()).collect(),
+    _ => vec![],
+  }
+}
+
+pub fn targets_mut(instr: &mut Instr) -> Vec<&mut String> {
+  match instr {
+    Instr::Jmp(label) | Instr::BranchIf(_, label) | Instr::BranchIfn(_, label) => vec![label],
+    Instr::JumpTable(_, default, table) => std::iter::once(default).chain(table.iter_mut()).collect(),
+    _ => vec![],
+  }
+}
+
+// division by a variable (or by zero) can fail at runtime, so it must not be moved or dropped.
+// when overflow traps, neither may arithmetic that is not known to fit.
+pub fn can_trap(instr: &Instr, overflow: Overflow) -> bool {
+  match instr {
+    Instr::Binary(op, _, y, z) if overflow == Overflow::Trap && ["add", "sub", "mult", "div"].contains(&op.as_str()) => {
+      match (y.parse::<i32>(), z.parse::<i32>()) {
+        (Ok(a), Ok(b)) => eval_binary(op, a, b, overflow).is_none(),
+        _ => true,
+      }
+    }
+    Instr::Binary(op, _, _, z) => (op == "div" || op == "mod") && (!is_constant(z) || z == "0"),
+    _ => false,
+  }
+}
+
+// what a failed %check reports.
+pub fn bounds_error(arr: &str, index: i32, size: i32, line: u32) -> String {
+  format!("line {line}: index {index} out of bounds for array {arr} of size {size}")
+}
+
+// what an add, sub, mult or div with the exact result `exact` leaves behind;
+// None when the program has to stop.
+pub fn fit(exact: i64, overflow: Overflow) -> Option<i32> {
+  if exact >= i32::MIN as i64 && exact <= i32::MAX as i64 {
+    return Some(exact as i32);
+  }
+  match overflow {
+    Overflow::Wrap => Some(exact as i32),
+    Overflow::Trap => None,
+    Overflow::Saturate => Some(if exact < 0 { i32::MIN } else { i32::MAX }),
+  }
+}
+
+// None for division by zero and for overflow that traps.
+pub fn eval_binary(op: &str, a: i32, b: i32, overflow: Overflow) -> Option<i32> {
+  let (x, y) = (a as i64, b as i64);
+  let value = match op {
+    "add" => return fit(x + y, overflow),
+    "sub" => return fit(x - y, overflow),
+    "mult" => return fit(x * y, overflow),
+    "div" => {
+      if b == 0 {
+        return None;
+      }
+      return fit(x / y, overflow);
+    }
+    "mod" => {
+      if b == 0 {
+        return None;
+      }
+      a.wrapping_rem(b)
+    }
+    "lt" => (a < b) as i32,
+    "le" => (a <= b) as i32,
+    "gt" => (a > b) as i32,
+    "ge" => (a >= b) as i32,
+    "eq" => (a == b) as i32,
+    "neq" => (a != b) as i32,
+    _ => return None,
+  };
+  return Some(value);
+}
+
+// copy of `instr` with every variable passed through `var` and every label through `label`.
+pub fn rename(instr: &Instr, var: &dyn Fn(&String) -> String, label: &dyn Fn(&String) -> String) -> Instr {
+  match instr {
+    Instr::Int(x) => Instr::Int(var(x)),
+    Instr::IntArray(x, size) => Instr::IntArray(var(x), var(size)),
+    Instr::Mov(x, y) => Instr::Mov(var(x), var(y)),
+    Instr::Store(arr, i, x) => Instr::Store(var(arr), var(i), var(x)),
+    Instr::Load(x, arr, i) => Instr::Load(var(x), var(arr), var(i)),
+    Instr::Binary(op, x, y, z) => Instr::Binary(op.clone(), var(x), var(y), var(z)),
+    Instr::Out(x) => Instr::Out(var(x)),
+    Instr::Input(x) => Instr::Input(var(x)),
+    Instr::Call(x, f, args) => {
+      let args = args.iter().map(|a| match array_arg(a) {
+        Some(arr) => format!("[{}]", var(&arr.to_string())),
+        None => var(a),
+      });
+      Instr::Call(var(x), f.clone(), args.collect())
+    }
+    Instr::Ret(x) => Instr::Ret(var(x)),
+    Instr::Jmp(l) => Instr::Jmp(label(l)),
+    Instr::BranchIf(x, l) => Instr::BranchIf(var(x), label(l)),
+    Instr::BranchIfn(x, l) => Instr::BranchIfn(var(x), label(l)),
+    Instr::JumpTable(x, default, table) => Instr::JumpTable(var(x), label(default), table.iter().map(label).collect()),
+    Instr::Label(l) => Instr::Label(label(l)),
+    Instr::Phi(x, incoming) => Instr::Phi(var(x), incoming.iter().map(|(value, l)| (var(value), label(l))).collect()),
+    // the array name is only there for the message, so it stays as written in the source.
+    Instr::Check(i, size, arr, line) => Instr::Check(var(i), var(size), arr.clone(), *line),
+  }
+}
@@ -0,0 +1,578 @@
+// x86-64 JIT for Linux. compiles the IR to machine code in an mmap'd buffer
+// and calls main directly. code generation follows x86.rs without register
+// allocation: every variable has a slot in the frame and values pass through
+// eax/ecx. globals live in Rust vectors whose addresses are baked into the
+// code, and array parameters are 8-byte addresses in the frame. %out, %input, division by zero, trapping overflow and failed bounds checks call back into Rust. anything
+// it cannot compile is reported as an error so the caller can fall back to
+// the VM.
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::sync::Mutex;
+use crate::ir::{self, Function, Instr, Overflow};
+
+const EAX: u8 = 0;
+const ECX: u8 = 1;
+const EDX: u8 = 2;
+const ESI: u8 = 6;
+const EDI: u8 = 7;
+const R8: u8 = 8;
+const R9: u8 = 9;
+const ARG_REGS: [u8; 6] = [EDI, ESI, EDX, ECX, R8, R9];
+
+static INPUT: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+extern "C" fn jit_out(x: i32) {
+  println!("{x}");
+}
+
+// whitespace-separated integers from stdin, 0 once it runs out.
+extern "C" fn jit_input() -> i32 {
+  let _ = std::io::stdout().flush();
+  let mut words = INPUT.lock().unwrap();
+  while words.is_empty() {
+    let mut line = String::new();
+    match std::io::stdin().lock().read_line(&mut line) {
+      Ok(0) | Err(_) => return 0,
+      Ok(_) => *words = line.split_whitespace().rev().map(String::from).collect(),
+    }
+  }
+  let word = words.pop().unwrap();
+  match word.parse::<i32>() {
+    Ok(value) => value,
+    Err(_) => {
+      println!("runtime error: invalid input '{word}'");
+      std::process::exit(1);
+    }
+  }
+}
+
+extern "C" fn jit_division_by_zero() {
+  println!("runtime error: division by zero");
+  std::process::exit(1);
+}
+
+extern "C" fn jit_overflow() {
+  println!("runtime error: integer overflow");
+  std::process::exit(1);
+}
+
+// array name and source line of every %check, in the order they were compiled.
+static CHECKS: Mutex<Vec<(String, u32)>> = Mutex::new(Vec::new());
+
+extern "C" fn jit_out_of_bounds(index: i32, size: i32, check: u32) {
+  let (arr, line) = CHECKS.lock().unwrap()[check as usize].clone();
+  println!("runtime error: {}", ir::bounds_error(&arr, index, size, line));
+  std::process::exit(1);
+}
+
+struct Frame {
+  slots: HashMap<String, i32>,  // variable -> offset from rbp
+  arrays: HashMap<String, i32>, // array -> offset of element 0 from rbp
+  pointers: HashMap<String, i32>, // array parameter -> offset of its address from rbp
+  size: i32,
+}
+
+fn build_frame(function: &Function) -> Result<Frame, String> {
+  let mut frame = Frame { slots: HashMap::new(), arrays: HashMap::new(), pointers: HashMap::new(), size: 0 };
+  for param in &function.array_params {
+    frame.size += 8;
+    frame.pointers.insert(param.clone(), -frame.size);
+  }
+  let mut names: Vec<&String> = function.params.iter().filter(|param| !function.array_params.contains(param)).collect();
+  for instr in &function.body {
+    match instr {
+      Instr::Int(x) => names.push(x),
+      Instr::IntArray(x, size) => {
+        let length: i32 = match size.parse() {
+          Ok(length) if length > 0 => length,
+          _ => return Err(format!("array {x} in {} needs a constant size", function.name)),
+        };
+        if !frame.arrays.contains_key(x) {
+          frame.size += 4 * length;
+          frame.arrays.insert(x.clone(), -frame.size);
+        }
+      }
+      _ => names.extend(ir::def(instr)),
+    }
+  }
+  for name in names {
+    if !frame.slots.contains_key(name) && !frame.arrays.contains_key(name) {
+      frame.size += 4;
+      frame.slots.insert(name.clone(), -frame.size);
+    }
+  }
+  frame.size = (frame.size + 15) / 16 * 16;
+  return Ok(frame);
+}
+
+struct Assembler {
+  code: Vec<u8>,
+  functions: HashMap<String, usize>,
+  calls: Vec<(usize, String)>, // rel32 to patch with a function's address
+  division_by_zero: usize,
+  overflow: usize,
+}
+
+impl Assembler {
+  fn bytes(&mut self, bytes: &[u8]) {
+    self.code.extend_from_slice(bytes);
+  }
+
+  fn imm32(&mut self, value: i32) {
+    self.code.extend_from_slice(&value.to_le_bytes());
+  }
+
+  // rel32 at `at` so that a jump ending at at + 4 lands on `target`.
+  fn patch(&mut self, at: usize, target: usize) {
+    let offset = target as i64 - (at as i64 + 4);
+    self.code[at..at + 4].copy_from_slice(&(offset as i32).to_le_bytes());
+  }
+
+  // mov r32, [rbp + disp32]
+  fn load_slot(&mut self, reg: u8, disp: i32) {
+    if reg >= 8 {
+      self.bytes(&[0x44]);
+    }
+    self.bytes(&[0x8b, 0x85 | ((reg & 7) << 3)]);
+    self.imm32(disp);
+  }
+
+  // mov [rbp + disp32], r32
+  fn store_slot(&mut self, reg: u8, disp: i32) {
+    if reg >= 8 {
+      self.bytes(&[0x44]);
+    }
+    self.bytes(&[0x89, 0x85 | ((reg & 7) << 3)]);
+    self.imm32(disp);
+  }
+
+  // 64-bit `opcode` between r64 and [rbp + disp32]: 0x8b loads, 0x89 stores, 0x8d is lea.
+  fn slot64(&mut self, opcode: u8, reg: u8, disp: i32) {
+    self.bytes(&[if reg >= 8 { 0x4c } else { 0x48 }, opcode, 0x85 | ((reg & 7) << 3)]);
+    self.imm32(disp);
+  }
+
+  // mov r32, imm32
+  fn load_imm(&mut self, reg: u8, value: i32) {
+    if reg >= 8 {
+      self.bytes(&[0x41]);
+    }
+    self.bytes(&[0xb8 + (reg & 7)]);
+    self.imm32(value);
+  }
+
+  // mov rax, address; call rax
+  fn call_hook(&mut self, address: usize) {
+    self.bytes(&[0x48, 0xb8]);
+    self.code.extend_from_slice(&(address as u64).to_le_bytes());
+    self.bytes(&[0xff, 0xd0]);
+  }
+}
+
+struct Emitter<'a> {
+  asm: &'a mut Assembler,
+  function: &'a Function,
+  frame: Frame,
+  globals: &'a HashMap<String, usize>, // global -> address of element 0
+  mode: Overflow,
+  labels: HashMap<&'a String, usize>,
+  jumps: Vec<(usize, &'a String)>, // rel32 to patch with a label's address
+}
+
+impl<'a> Emitter<'a> {
+  fn slot(&self, name: &String) -> Result<i32, String> {
+    match self.frame.slots.get(name) {
+      Some(offset) => Ok(*offset),
+      None => Err(format!("unknown variable {name} in {}", self.function.name)),
+    }
+  }
+
+  fn load(&mut self, operand: &String, reg: u8) -> Result<(), String> {
+    if ir::is_constant(operand) {
+      self.asm.load_imm(reg, operand.parse::<i32>().unwrap());
+    } else {
+      let slot = self.slot(operand)?;
+      self.asm.load_slot(reg, slot);
+    }
+    Ok(())
+  }
+
+  fn store(&mut self, reg: u8, dest: &String) -> Result<(), String> {
+    let slot = self.slot(dest)?;
+    self.asm.store_slot(reg, slot);
+    Ok(())
+  }
+
+  // address of element 0 of arr in the 64-bit `reg`.
+  fn address(&mut self, arr: &String, reg: u8) -> Result<(), String> {
+    if let Some(base) = self.frame.arrays.get(arr) {
+      let base = *base;
+      self.asm.slot64(0x8d, reg, base);
+    } else if let Some(offset) = self.frame.pointers.get(arr) {
+      let offset = *offset;
+      self.asm.slot64(0x8b, reg, offset);
+    } else if let Some(address) = self.globals.get(arr) {
+      let address = *address;
+      self.asm.bytes(&[if reg >= 8 { 0x49 } else { 0x48 }, 0xb8 + (reg & 7)]); // mov reg, imm64
+      self.asm.code.extend_from_slice(&(address as u64).to_le_bytes());
+    } else {
+      return Err(format!("unknown array {arr} in {}", self.function.name));
+    }
+    Ok(())
+  }
+
+  // index into rcx, sign-extended; returns the displacement of element 0
+  // from rbp, or None for an array whose address is in rdx.
+  fn element(&mut self, arr: &String, index: &String) -> Result<Option<i32>, String> {
+    let base = match self.frame.arrays.get(arr) {
+      Some(base) => Some(*base),
+      None => {
+        self.address(arr, EDX)?;
+        None
+      }
+    };
+    self.load(index, ECX)?;
+    self.asm.bytes(&[0x48, 0x63, 0xc9]); // movsxd rcx, ecx
+    Ok(base)
+  }
+
+  // opcode with eax and the element from `element` as operands.
+  fn access(&mut self, opcode: u8, base: Option<i32>) {
+    match base {
+      Some(base) => {
+        self.asm.bytes(&[opcode, 0x84, 0x8d]); // [rbp + rcx*4 + base]
+        self.asm.imm32(base);
+      }
+      None => self.asm.bytes(&[opcode, 0x04, 0x8a]), // [rdx + rcx*4]
+    }
+  }
+
+  fn jump(&mut self, opcode: &[u8], label: &'a String) {
+    self.asm.bytes(opcode);
+    self.jumps.push((self.asm.code.len(), label));
+    self.asm.imm32(0);
+  }
+
+  // after an add, sub, imul or neg that set the overflow flag, as in x86.rs.
+  fn overflowed(&mut self, op: &str, y: &String) -> Result<(), String> {
+    match self.mode {
+      Overflow::Wrap => {}
+      Overflow::Trap => {
+        self.asm.bytes(&[0x0f, 0x80]); // jo overflow
+        let at = self.asm.code.len();
+        self.asm.imm32(0);
+        let target = self.asm.overflow;
+        self.asm.patch(at, target);
+      }
+      Overflow::Saturate => {
+        self.asm.bytes(&[0x71, 0x00]); // jno fits
+        let start = self.asm.code.len();
+        if op == "neg" {
+          self.asm.load_imm(EAX, i32::MAX);
+        } else {
+          self.load(y, EAX)?;
+          if op == "mult" {
+            self.asm.bytes(&[0x31, 0xc8]); // xor eax, ecx
+          }
+          self.asm.bytes(&[0xc1, 0xf8, 0x1f, 0x35]); // sar eax, 31; xor eax, imm32
+          self.asm.imm32(i32::MAX);
+        }
+        self.asm.code[start - 1] = (self.asm.code.len() - start) as u8;
+      }
+    }
+    Ok(())
+  }
+
+  fn binary(&mut self, op: &String, x: &String, y: &String, z: &String) -> Result<(), String> {
+    self.load(y, EAX)?;
+    self.load(z, ECX)?;
+    match op.as_str() {
+      "add" | "sub" | "mult" => {
+        match op.as_str() {
+          "add" => self.asm.bytes(&[0x01, 0xc8]),
+          "sub" => self.asm.bytes(&[0x29, 0xc8]),
+          _ => self.asm.bytes(&[0x0f, 0xaf, 0xc1]),
+        }
+        self.overflowed(op, y)?;
+      }
+      "div" | "mod" => {
+        // idiv faults on x / 0 and on INT_MIN / -1; the second is a negation that overflows.
+        self.asm.bytes(&[0x85, 0xc9, 0x0f, 0x84]); // test ecx, ecx; je division_by_zero
+        let at = self.asm.code.len();
+        self.asm.imm32(0);
+        let target = self.asm.division_by_zero;
+        self.asm.patch(at, target);
+        self.asm.bytes(&[0x83, 0xf9, 0xff, 0x75, 0x00]); // cmp ecx, -1; jne plain
+        let start = self.asm.code.len();
+        if op == "div" {
+          self.asm.bytes(&[0xf7, 0xd8]); // neg eax
+          self.overflowed("neg", y)?;
+          self.asm.bytes(&[0xeb, 0x03]); // jmp done
+          self.asm.code[start - 1] = (self.asm.code.len() - start) as u8;
+          self.asm.bytes(&[0x99, 0xf7, 0xf9]); // plain: cdq; idiv ecx
+        } else {
+          self.asm.bytes(&[0x31, 0xc0, 0xeb, 0x05]); // xor eax, eax; jmp done
+          self.asm.code[start - 1] = (self.asm.code.len() - start) as u8;
+          self.asm.bytes(&[0x99, 0xf7, 0xf9, 0x89, 0xd0]); // plain: cdq; idiv ecx; mov eax, edx
+        }
+      }
+      _ => {
+        let set = match op.as_str() {
+          "lt" => 0x9c,
+          "le" => 0x9e,
+          "gt" => 0x9f,
+          "ge" => 0x9d,
+          "eq" => 0x94,
+          _ => 0x95,
+        };
+        self.asm.bytes(&[0x39, 0xc8, 0x0f, set, 0xc0, 0x0f, 0xb6, 0xc0]); // cmp eax, ecx; setcc al; movzx eax, al
+      }
+    }
+    self.store(EAX, x)
+  }
+
+  fn call(&mut self, dest: &String, callee: &String, args: &Vec<String>) -> Result<(), String> {
+    let stacked = if args.len() > 6 { args.len() - 6 } else { 0 };
+    let padding = if stacked % 2 == 1 { 8 } else { 0 };
+    if padding > 0 {
+      self.asm.bytes(&[0x48, 0x83, 0xec, 0x08]); // sub rsp, 8
+    }
+    for arg in args.iter().skip(6).rev() {
+      match ir::array_arg(arg) {
+        Some(arr) => self.address(&arr.to_string(), EAX)?,
+        None => self.load(arg, EAX)?,
+      }
+      self.asm.bytes(&[0x50]); // push rax
+    }
+    for (arg, reg) in args.iter().zip(ARG_REGS) {
+      match ir::array_arg(arg) {
+        Some(arr) => self.address(&arr.to_string(), reg)?,
+        None => self.load(arg, reg)?,
+      }
+    }
+    self.asm.bytes(&[0xe8]);
+    let at = self.asm.code.len();
+    self.asm.calls.push((at, callee.clone()));
+    self.asm.imm32(0);
+    if stacked > 0 {
+      self.asm.bytes(&[0x48, 0x81, 0xc4]); // add rsp, imm32
+      self.asm.imm32((8 * stacked + padding) as i32);
+    }
+    self.store(EAX, dest)
+  }
+
+  fn instr(&mut self, instr: &'a Instr) -> Result<(), String> {
+    match instr {
+      Instr::Int(_) | Instr::IntArray(_, _) => {}
+      Instr::Mov(x, y) => {
+        self.load(y, EAX)?;
+        self.store(EAX, x)?;
+      }
+      Instr::Store(arr, i, x) => {
+        let base = self.element(arr, i)?;
+        self.load(x, EAX)?;
+        self.access(0x89, base); // mov [element], eax
+      }
+      Instr::Load(x, arr, i) => {
+        let base = self.element(arr, i)?;
+        self.access(0x8b, base); // mov eax, [element]
+        self.store(EAX, x)?;
+      }
+      Instr::Binary(op, x, y, z) => self.binary(op, x, y, z)?,
+      Instr::Out(x) => {
+        self.load(x, EDI)?;
+        self.asm.call_hook(jit_out as *const () as usize);
+      }
+      Instr::Input(x) => {
+        self.asm.call_hook(jit_input as *const () as usize);
+        self.store(EAX, x)?;
+      }
+      Instr::Call(x, f, args) => self.call(x, f, args)?,
+      Instr::Check(i, size, arr, line) => {
+        // unsigned compare, so negative indices fail too.
+        let check = {
+          let mut checks = CHECKS.lock().unwrap();
+          checks.push((arr.clone(), *line));
+          checks.len() - 1
+        };
+        self.load(i, EAX)?;
+        self.load(size, ECX)?;
+        self.asm.bytes(&[0x39, 0xc8, 0x72, 27]); // cmp eax, ecx; jb ok
+        self.asm.bytes(&[0x89, 0xc7, 0x89, 0xce, 0xba]); // mov edi, eax; mov esi, ecx; mov edx, check
+        self.asm.imm32(check as i32);
+        self.asm.bytes(&[0x48, 0x83, 0xe4, 0xf0]); // and rsp, -16
+        self.asm.call_hook(jit_out_of_bounds as *const () as usize);
+        self.asm.bytes(&[0x0f, 0x0b]); // ud2
+      }
+      Instr::Ret(x) => {
+        self.load(x, EAX)?;
+        self.asm.bytes(&[0xc9, 0xc3]); // leave; ret
+      }
+      Instr::Jmp(l) => self.jump(&[0xe9], l),
+      Instr::BranchIf(x, l) | Instr::BranchIfn(x, l) => {
+        self.load(x, EAX)?;
+        self.asm.bytes(&[0x85, 0xc0]); // test eax, eax
+        let jcc = if matches!(instr, Instr::BranchIf(_, _)) { 0x85 } else { 0x84 };
+        self.jump(&[0x0f, jcc], l);
+      }
+      Instr::JumpTable(x, default, table) => {
+        // unsigned compare, so negative indices go to the default too. the
+        // table is a row of 5-byte jmp rel32 right after the indirect jump.
+        self.load(x, EAX)?;
+        self.asm.bytes(&[0x3d]); // cmp eax, imm32
+        self.asm.imm32(table.len() as i32);
+        self.jump(&[0x0f, 0x83], default); // jae default
+        self.asm.bytes(&[0x48, 0x8d, 0x0d]); // lea rcx, [rip + 9]
+        self.asm.imm32(9);
+        self.asm.bytes(&[0x48, 0x8d, 0x04, 0x80]); // lea rax, [rax + rax*4]
+        self.asm.bytes(&[0x48, 0x01, 0xc8, 0xff, 0xe0]); // add rax, rcx; jmp rax
+        for l in table {
+          self.jump(&[0xe9], l);
+        }
+      }
+      Instr::Label(l) => {
+        self.labels.insert(l, self.asm.code.len());
+      }
+      Instr::Phi(_, _) => return Err(String::from("phi instructions must be removed before code generation")),
+    }
+    Ok(())
+  }
+}
+
+fn compile_function(asm: &mut Assembler, function: &Function, globals: &HashMap<String, usize>, mode: Overflow) -> Result<(), String> {
+  let frame = build_frame(function)?;
+  let size = frame.size;
+  asm.functions.insert(function.name.clone(), asm.code.len());
+  asm.bytes(&[0x55, 0x48, 0x89, 0xe5]); // push rbp; mov rbp, rsp
+  if size > 0 {
+    // variables start out as 0, like in the VM.
+    asm.bytes(&[0x48, 0x81, 0xec]); // sub rsp, size
+    asm.imm32(size);
+    asm.bytes(&[0x4c, 0x8d, 0x95]); // lea r10, [rbp - size]
+    asm.imm32(-size);
+    let top = asm.code.len();
+    asm.bytes(&[0x49, 0xc7, 0x02, 0, 0, 0, 0]); // mov qword [r10], 0
+    asm.bytes(&[0x49, 0x83, 0xc2, 0x08]); // add r10, 8
+    asm.bytes(&[0x49, 0x39, 0xea]); // cmp r10, rbp
+    let back = top as i64 - (asm.code.len() as i64 + 2);
+    asm.bytes(&[0x75, back as i8 as u8]); // jne top
+  }
+
+  let mut emitter = Emitter { asm, function, frame, globals, mode, labels: HashMap::new(), jumps: vec![] };
+  for (k, param) in function.params.iter().enumerate() {
+    if let Some(offset) = emitter.frame.pointers.get(param) {
+      let offset = *offset;
+      if k < 6 {
+        emitter.asm.slot64(0x89, ARG_REGS[k], offset);
+      } else {
+        emitter.asm.slot64(0x8b, EAX, 16 + 8 * (k as i32 - 6));
+        emitter.asm.slot64(0x89, EAX, offset);
+      }
+    } else if k < 6 {
+      emitter.store(ARG_REGS[k], param)?;
+    } else {
+      emitter.asm.load_slot(EAX, 16 + 8 * (k as i32 - 6));
+      emitter.store(EAX, param)?;
+    }
+  }
+  for instr in &function.body {
+    emitter.instr(instr)?;
+  }
+  emitter.asm.bytes(&[0x31, 0xc0, 0xc9, 0xc3]); // xor eax, eax; leave; ret
+
+  for (at, label) in std::mem::take(&mut emitter.jumps) {
+    let target = match emitter.labels.get(label) {
+      Some(target) => *target,
+      None => return Err(format!("unknown label {label} in {}", function.name)),
+    };
+    emitter.asm.patch(at, target);
+  }
+  Ok(())
+}
+
+fn compile(functions: &Vec<Function>, globals: &HashMap<String, usize>, mode: Overflow) -> Result<(Vec<u8>, usize), String> {
+  let mut asm = Assembler { code: vec![], functions: HashMap::new(), calls: vec![], division_by_zero: 0, overflow: 0 };
+  CHECKS.lock().unwrap().clear();
+  asm.bytes(&[0x48, 0x83, 0xe4, 0xf0]); // and rsp, -16
+  asm.call_hook(jit_division_by_zero as *const () as usize);
+  asm.bytes(&[0x0f, 0x0b]); // ud2
+  asm.overflow = asm.code.len();
+  asm.bytes(&[0x48, 0x83, 0xe4, 0xf0]); // and rsp, -16
+  asm.call_hook(jit_overflow as *const () as usize);
+  asm.bytes(&[0x0f, 0x0b]); // ud2
+
+  for function in functions {
+    compile_function(&mut asm, function, globals, mode)?;
+  }
+  for (at, callee) in std::mem::take(&mut asm.calls) {
+    let target = match asm.functions.get(&callee) {
+      Some(target) => *target,
+      None => return Err(format!("call to undefined function {callee}")),
+    };
+    asm.patch(at, target);
+  }
+  let main = match asm.functions.get(&String::from("main")) {
+    Some(main) => *main,
+    None => return Err(String::from("no main function")),
+  };
+  return Ok((asm.code, main));
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+mod memory {
+  use std::ffi::c_void;
+
+  const PROT_READ: i32 = 1;
+  const PROT_WRITE: i32 = 2;
+  const PROT_EXEC: i32 = 4;
+  const MAP_PRIVATE: i32 = 0x02;
+  const MAP_ANONYMOUS: i32 = 0x20;
+
+  extern "C" {
+    fn mmap(addr: *mut c_void, length: usize, prot: i32, flags: i32, fd: i32, offset: i64) -> *mut c_void;
+    fn mprotect(addr: *mut c_void, length: usize, prot: i32) -> i32;
+    fn munmap(addr: *mut c_void, length: usize) -> i32;
+  }
+
+  // copy the code into fresh pages, make them executable, and call `entry`.
+  pub fn execute(code: &Vec<u8>, entry: usize) -> Result<i32, String> {
+    let length = code.len();
+    unsafe {
+      let buffer = mmap(std::ptr::null_mut(), length, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+      if buffer as isize == -1 {
+        return Err(String::from("mmap failed"));
+      }
+      std::ptr::copy_nonoverlapping(code.as_ptr(), buffer as *mut u8, length);
+      if mprotect(buffer, length, PROT_READ | PROT_EXEC) != 0 {
+        munmap(buffer, length);
+        return Err(String::from("mprotect failed"));
+      }
+      let main: extern "C" fn() -> i32 = std::mem::transmute((buffer as *const u8).add(entry));
+      let result = main();
+      munmap(buffer, length);
+      return Ok(result);
+    }
+  }
+}
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+mod memory {
+  pub fn execute(_code: &Vec<u8>, _entry: usize) -> Result<i32, String> {
+    Err(String::from("the JIT needs x86-64 Linux"))
+  }
+}
+
+// compile and run the program. errors mean nothing has run yet.
+pub fn run(program: &ir::Program) -> Result<(), String> {
+  // zeroed storage that has to outlive the call into the code.
+  let mut storage: Vec<Vec<i32>> = program.globals.iter().map(|global| vec![0; global.size as usize]).collect();
+  let mut globals: HashMap<String, usize> = HashMap::new();
+  for (global, values) in program.globals.iter().zip(storage.iter_mut()) {
+    globals.insert(global.name.clone(), values.as_mut_ptr() as usize);
+  }
+  let (code, main) = compile(&program.functions, &globals, program.overflow)?;
+  memory::execute(&code, main)?;
+  drop(storage);
+  let _ = std::io::stdout().flush();
+  Ok(())
+}
@@ -0,0 +1,463 @@
+// LLVM IR backend. writes a textual .ll module: every variable is an alloca
+// in the entry block, arrays are [N x i32] allocas (globals [N x i32]
+// module globals, ptr parameters for array parameters) indexed with
+// getelementptr, branches compare with icmp, and %out/%input call printf and
+// scanf (plus strtol to check what was read). division goes through helpers with the VM's semantics, as
+// do add, sub and mult unless overflow wraps, and a failed %check calls one
+// that reports it.
+use std::path::Path;
+use std::process::Command;
+use crate::ir::{self, Function, Global, Instr, Overflow};
+
+const PRELUDE: &str = "@.out = private unnamed_addr constant [4 x i8] c\"%d\\0A\\00\"
+@.in = private unnamed_addr constant [5 x i8] c\"%63s\\00\"
+@.invalid = private unnamed_addr constant [35 x i8] c\"runtime error: invalid input '%s'\\0A\\00\"
+@.division = private unnamed_addr constant [32 x i8] c\"runtime error: division by zero\\00\"
+@.bounds = private unnamed_addr constant [72 x i8] c\"runtime error: line %d: index %d out of bounds for array %s of size %d\\0A\\00\"
+@.overflow = private unnamed_addr constant [32 x i8] c\"runtime error: integer overflow\\00\"
+
+declare i32 @printf(ptr, ...)
+declare i32 @scanf(ptr, ...)
+declare i32 @puts(ptr)
+declare i64 @strtol(ptr, ptr, i32)
+declare void @exit(i32)
+
+; the next whitespace-separated word as an int, like the VM reads it: 0 at
+; the end of input, and a runtime error unless the whole word is an int.
+define internal i32 @tt_input() {
+  %word = alloca [64 x i8]
+  %end = alloca ptr
+  %read = call i32 (ptr, ...) @scanf(ptr @.in, ptr %word)
+  %found = icmp eq i32 %read, 1
+  br i1 %found, label %convert, label %none
+none:
+  ret i32 0
+convert:
+  %x = call i64 @strtol(ptr %word, ptr %end, i32 10)
+  %stop = load ptr, ptr %end
+  %empty = icmp eq ptr %stop, %word
+  %last = load i8, ptr %stop
+  %rest = icmp ne i8 %last, 0
+  %v = trunc i64 %x to i32
+  %back = sext i32 %v to i64
+  %wide = icmp ne i64 %back, %x
+  %bad = or i1 %empty, %rest
+  %invalid = or i1 %bad, %wide
+  br i1 %invalid, label %fail, label %done
+fail:
+  call i32 (ptr, ...) @printf(ptr @.invalid, ptr %word)
+  call void @exit(i32 1)
+  unreachable
+done:
+  ret i32 %v
+}
+
+define internal void @tt_division_by_zero() {
+  call i32 @puts(ptr @.division)
+  call void @exit(i32 1)
+  unreachable
+}
+
+define internal void @tt_out_of_bounds(i32 %index, i32 %size, ptr %arr, i32 %line) {
+  call i32 (ptr, ...) @printf(ptr @.bounds, i32 %line, i32 %index, ptr %arr, i32 %size)
+  call void @exit(i32 1)
+  unreachable
+}
+
+define internal void @tt_overflow() {
+  call i32 @puts(ptr @.overflow)
+  call void @exit(i32 1)
+  unreachable
+}
+
+define internal i32 @tt_add(i32 %a, i32 %b) {
+  %x = sext i32 %a to i64
+  %y = sext i32 %b to i64
+  %r = add i64 %x, %y
+  %v = call i32 @tt_fit(i64 %r)
+  ret i32 %v
+}
+
+define internal i32 @tt_sub(i32 %a, i32 %b) {
+  %x = sext i32 %a to i64
+  %y = sext i32 %b to i64
+  %r = sub i64 %x, %y
+  %v = call i32 @tt_fit(i64 %r)
+  ret i32 %v
+}
+
+define internal i32 @tt_mult(i32 %a, i32 %b) {
+  %x = sext i32 %a to i64
+  %y = sext i32 %b to i64
+  %r = mul i64 %x, %y
+  %v = call i32 @tt_fit(i64 %r)
+  ret i32 %v
+}
+
+define internal i32 @tt_div(i32 %a, i32 %b) {
+  %zero = icmp eq i32 %b, 0
+  br i1 %zero, label %fail, label %nonzero
+fail:
+  call void @tt_division_by_zero()
+  unreachable
+nonzero:
+  %minus = icmp eq i32 %b, -1
+  br i1 %minus, label %negate, label %divide
+negate:
+  %n = call i32 @tt_sub(i32 0, i32 %a)
+  ret i32 %n
+divide:
+  %q = sdiv i32 %a, %b
+  ret i32 %q
+}
+
+define internal i32 @tt_mod(i32 %a, i32 %b) {
+  %zero = icmp eq i32 %b, 0
+  br i1 %zero, label %fail, label %nonzero
+fail:
+  call void @tt_division_by_zero()
+  unreachable
+nonzero:
+  %minus = icmp eq i32 %b, -1
+  br i1 %minus, label %none, label %divide
+none:
+  ret i32 0
+divide:
+  %r = srem i32 %a, %b
+  ret i32 %r
+}
+";
+
+// @tt_fit turns an exact 64-bit result into the i32 the overflow mode asks for.
+fn fit(overflow: Overflow) -> &'static str {
+  match overflow {
+    Overflow::Wrap => "define internal i32 @tt_fit(i64 %x) {
+  %v = trunc i64 %x to i32
+  ret i32 %v
+}
+",
+    Overflow::Trap => "define internal i32 @tt_fit(i64 %x) {
+  %v = trunc i64 %x to i32
+  %back = sext i32 %v to i64
+  %fits = icmp eq i64 %back, %x
+  br i1 %fits, label %ok, label %fail
+ok:
+  ret i32 %v
+fail:
+  call void @tt_overflow()
+  unreachable
+}
+",
+    Overflow::Saturate => "define internal i32 @tt_fit(i64 %x) {
+  %low = icmp slt i64 %x, -2147483648
+  %high = icmp sgt i64 %x, 2147483647
+  %v = trunc i64 %x to i32
+  %capped = select i1 %high, i32 2147483647, i32 %v
+  %r = select i1 %low, i32 -2147483648, i32 %capped
+  ret i32 %r
+}
+",
+  }
+}
+
+fn var(name: &String) -> String {
+  format!("%v.{name}")
+}
+
+fn global(name: &String) -> String {
+  format!("@g.{name}")
+}
+
+fn name(arr: &String) -> String {
+  format!("@.name.{arr}")
+}
+
+fn block(label: &String) -> String {
+  format!("L.{}", &label[1..])
+}
+
+struct Emitter<'a> {
+  function: &'a Function,
+  globals: &'a Vec<Global>,
+  overflow: Overflow,
+  arrays: Vec<(String, i32)>,
+  code: String,
+  count: usize,
+  terminated: bool,
+}
+
+impl<'a> Emitter<'a> {
+  fn line(&mut self, text: &str) {
+    self.code += "  ";
+    self.code += text;
+    self.code += "\n";
+  }
+
+  fn temp(&mut self) -> String {
+    self.count += 1;
+    format!("%t{}", self.count)
+  }
+
+  // start a new basic block, falling through from the current one if needed.
+  fn start(&mut self, name: &String) {
+    if !self.terminated {
+      self.line(&format!("br label %{name}"));
+    }
+    self.code += &format!("{name}:\n");
+    self.terminated = false;
+  }
+
+  // a block for code after a terminator; it only runs if something jumps to a later label.
+  fn after_terminator(&mut self) {
+    self.count += 1;
+    self.code += &format!("dead{}:\n", self.count);
+  }
+
+  fn value(&mut self, operand: &String) -> String {
+    if ir::is_constant(operand) {
+      return operand.clone();
+    }
+    let temp = self.temp();
+    self.line(&format!("{temp} = load i32, ptr {}", var(operand)));
+    return temp;
+  }
+
+  // pointer to element 0 of an array, with its length if it is known here.
+  fn array(&self, arr: &String) -> Result<(Option<i32>, String), String> {
+    if let Some((_, length)) = self.arrays.iter().find(|(name, _)| name == arr) {
+      return Ok((Some(*length), var(arr)));
+    }
+    if self.function.array_params.contains(arr) {
+      return Ok((None, format!("%p.{arr}")));
+    }
+    match self.globals.iter().find(|g| &g.name == arr) {
+      Some(g) => Ok((Some(g.size), global(arr))),
+      None => Err(format!("unknown array {arr} in {}", self.function.name)),
+    }
+  }
+
+  fn element(&mut self, arr: &String, index: &String) -> Result<String, String> {
+    let (length, base) = self.array(arr)?;
+    let index = self.value(index);
+    let wide = self.temp();
+    self.line(&format!("{wide} = sext i32 {index} to i64"));
+    let address = self.temp();
+    match length {
+      Some(length) => self.line(&format!("{address} = getelementptr inbounds [{length} x i32], ptr {base}, i64 0, i64 {wide}")),
+      None => self.line(&format!("{address} = getelementptr inbounds i32, ptr {base}, i64 {wide}")),
+    }
+    return Ok(address);
+  }
+
+  fn instr(&mut self, instr: &Instr) -> Result<(), String> {
+    match instr {
+      Instr::Int(_) | Instr::IntArray(_, _) => {}
+      Instr::Mov(x, y) => {
+        let y = self.value(y);
+        self.line(&format!("store i32 {y}, ptr {}", var(x)));
+      }
+      Instr::Store(arr, i, x) => {
+        let address = self.element(arr, i)?;
+        let x = self.value(x);
+        self.line(&format!("store i32 {x}, ptr {address}"));
+      }
+      Instr::Load(x, arr, i) => {
+        let address = self.element(arr, i)?;
+        let temp = self.temp();
+        self.line(&format!("{temp} = load i32, ptr {address}"));
+        self.line(&format!("store i32 {temp}, ptr {}", var(x)));
+      }
+      Instr::Binary(op, x, y, z) => {
+        let (y, z) = (self.value(y), self.value(z));
+        let result = self.temp();
+        match op.as_str() {
+          "add" | "sub" if self.overflow == Overflow::Wrap => self.line(&format!("{result} = {op} i32 {y}, {z}")),
+          "mult" if self.overflow == Overflow::Wrap => self.line(&format!("{result} = mul i32 {y}, {z}")),
+          "add" | "sub" | "mult" | "div" | "mod" => self.line(&format!("{result} = call i32 @tt_{op}(i32 {y}, i32 {z})")),
+          _ => {
+            let predicate = match op.as_str() {
+              "lt" => "slt",
+              "le" => "sle",
+              "gt" => "sgt",
+              "ge" => "sge",
+              "eq" => "eq",
+              _ => "ne",
+            };
+            let flag = self.temp();
+            self.line(&format!("{flag} = icmp {predicate} i32 {y}, {z}"));
+            self.line(&format!("{result} = zext i1 {flag} to i32"));
+          }
+        }
+        self.line(&format!("store i32 {result}, ptr {}", var(x)));
+      }
+      Instr::Out(x) => {
+        let x = self.value(x);
+        self.line(&format!("call i32 (ptr, ...) @printf(ptr @.out, i32 {x})"));
+      }
+      Instr::Input(x) => {
+        let value = self.temp();
+        self.line(&format!("{value} = call i32 @tt_input()"));
+        self.line(&format!("store i32 {value}, ptr {}", var(x)));
+      }
+      Instr::Call(x, f, args) => {
+        let mut values: Vec<String> = vec![];
+        for arg in args {
+          if let Some(arr) = ir::array_arg(arg) {
+            let (_, base) = self.array(&arr.to_string())?;
+            values.push(format!("ptr {base}"));
+            continue;
+          }
+          let value = self.value(arg);
+          values.push(format!("i32 {value}"));
+        }
+        let result = self.temp();
+        self.line(&format!("{result} = call i32 @f_{f}({})", values.join(", ")));
+        self.line(&format!("store i32 {result}, ptr {}", var(x)));
+      }
+      Instr::Check(i, size, arr, line) => {
+        let (i, size) = (self.value(i), self.value(size));
+        // a negative index compares as a large unsigned one.
+        let flag = self.temp();
+        self.line(&format!("{flag} = icmp ult i32 {i}, {size}"));
+        self.count += 1;
+        let (ok, fail) = (format!("ok{}", self.count), format!("fail{}", self.count));
+        self.line(&format!("br i1 {flag}, label %{ok}, label %{fail}"));
+        self.code += &format!("{fail}:\n");
+        self.line(&format!("call void @tt_out_of_bounds(i32 {i}, i32 {size}, ptr {}, i32 {line})", name(arr)));
+        self.line("unreachable");
+        self.code += &format!("{ok}:\n");
+      }
+      Instr::Ret(x) => {
+        let x = self.value(x);
+        self.line(&format!("ret i32 {x}"));
+        self.after_terminator();
+      }
+      Instr::Jmp(l) => {
+        self.line(&format!("br label %{}", block(l)));
+        self.after_terminator();
+      }
+      Instr::BranchIf(x, l) | Instr::BranchIfn(x, l) => {
+        let x = self.value(x);
+        let flag = self.temp();
+        self.line(&format!("{flag} = icmp ne i32 {x}, 0"));
+        self.count += 1;
+        let fall = format!("fall{}", self.count);
+        let (taken, not_taken) = if matches!(instr, Instr::BranchIf(_, _)) { (block(l), fall.clone()) } else { (fall.clone(), block(l)) };
+        self.line(&format!("br i1 {flag}, label %{taken}, label %{not_taken}"));
+        self.terminated = true;
+        self.start(&fall);
+      }
+      Instr::JumpTable(x, default, table) => {
+        let x = self.value(x);
+        let cases: Vec<String> = table.iter().enumerate().map(|(k, l)| format!("i32 {k}, label %{}", block(l))).collect();
+        self.line(&format!("switch i32 {x}, label %{} [{}]", block(default), cases.join(" ")));
+        self.after_terminator();
+      }
+      Instr::Label(l) => self.start(&block(l)),
+      Instr::Phi(_, _) => return Err(String::from("phi instructions must be removed before code generation")),
+    }
+    Ok(())
+  }
+}
+
+fn generate_function(function: &Function, globals: &Vec<Global>, overflow: Overflow) -> Result<String, String> {
+  let params: Vec<String> = function.params.iter().map(|p| {
+    if function.array_params.contains(p) { format!("ptr %p.{p}") } else { format!("i32 %p.{p}") }
+  }).collect();
+  let mut emitter = Emitter { function, globals, overflow, arrays: vec![], code: String::new(), count: 0, terminated: false };
+  emitter.code += &format!("define i32 @f_{}({}) {{\nentry:\n", function.name, params.join(", "));
+
+  let mut scalars: Vec<&String> = vec![];
+  for instr in &function.body {
+    if let Instr::IntArray(x, size) = instr {
+      if emitter.arrays.iter().any(|(name, _)| name == x) {
+        continue;
+      }
+      let length = match size.parse::<i32>() {
+        Ok(length) if length > 0 => length,
+        _ => return Err(format!("array {x} in {} needs a constant size for LLVM", function.name)),
+      };
+      emitter.line(&format!("{} = alloca [{length} x i32]", var(x)));
+      emitter.line(&format!("store [{length} x i32] zeroinitializer, ptr {}", var(x)));
+      emitter.arrays.push((x.clone(), length));
+      continue;
+    }
+    let mut names: Vec<&String> = ir::uses(instr);
+    names.extend(ir::def(instr));
+    if let Instr::Int(x) = instr {
+      names.push(x);
+    }
+    for name in names {
+      if !ir::is_constant(name) && !scalars.contains(&name) && !function.params.contains(name) {
+        scalars.push(name);
+      }
+    }
+  }
+  for name in scalars {
+    emitter.line(&format!("{} = alloca i32", var(name)));
+    emitter.line(&format!("store i32 0, ptr {}", var(name)));
+  }
+  for param in function.params.iter().filter(|param| !function.array_params.contains(param)) {
+    emitter.line(&format!("{} = alloca i32", var(param)));
+    emitter.line(&format!("store i32 %p.{param}, ptr {}", var(param)));
+  }
+
+  for instr in &function.body {
+    emitter.instr(instr)?;
+  }
+  if !emitter.terminated {
+    emitter.line("ret i32 0");
+  }
+  emitter.code += "}\n";
+  return Ok(emitter.code);
+}
+
+pub fn generate(program: &ir::Program) -> Result<String, String> {
+  let mut code = String::from(PRELUDE);
+  code += "\n";
+  code += fit(program.overflow);
+  if !program.globals.is_empty() {
+    code += "\n";
+  }
+  for g in &program.globals {
+    code += &format!("{} = internal global [{} x i32] zeroinitializer\n", global(&g.name), g.size);
+  }
+  // the array names failed checks report.
+  let mut names: Vec<&String> = vec![];
+  for function in &program.functions {
+    for instr in &function.body {
+      if let Instr::Check(_, _, arr, _) = instr {
+        if !names.contains(&arr) {
+          names.push(arr);
+        }
+      }
+    }
+  }
+  if !names.is_empty() {
+    code += "\n";
+  }
+  for arr in names {
+    code += &format!("{} = private unnamed_addr constant [{} x i8] c\"{arr}\\00\"\n", name(arr), arr.len() + 1);
+  }
+  for function in &program.functions {
+    code += "\n";
+    code += &generate_function(function, &program.globals, program.overflow)?;
+  }
+  code += "\ndefine i32 @main() {\n  call i32 @f_main()\n  ret i32 0\n}\n";
+  return Ok(code);
+}
+
+// arguments for running a module with lli. releases before 15 only read
+// `ptr` when opaque pointers are switched on.
+pub fn lli_args(module: &Path) -> Vec<String> {
+  let mut args: Vec<String> = vec![];
+  if let Ok(output) = Command::new("lli").arg("--version").output() {
+    let version = String::from_utf8_lossy(&output.stdout).to_string();
+    let major = version.split("version ").nth(1).and_then(|v| v.split('.').next()).and_then(|v| v.trim().parse::<u32>().ok());
+    if matches!(major, Some(major) if major < 15) {
+      args.push(String::from("-opaque-pointers"));
+    }
+  }
+  args.push(module.display().to_string());
+  return args;
+}
@@ -0,0 +1,569 @@
+// loop optimisations on natural loops found in the control flow graph:
+// loop-invariant code motion into a preheader, strength reduction of
+// multiplications by a basic induction variable, and removal of bounds
+// checks that the loop condition already guarantees.
+use std::collections::{HashMap, HashSet};
+use crate::cfg::{self, Cfg};
+use crate::ir::{self, Function, Instr, Overflow};
+
+struct Loop {
+  header: usize,
+  blocks: Vec<usize>,
+}
+
+impl Loop {
+  fn contains(&self, b: usize) -> bool {
+    self.blocks.contains(&b)
+  }
+}
+
+// natural loops keyed by header, innermost (smallest) first.
+fn find_loops(cfg: &Cfg) -> Vec<Loop> {
+  let mut loops: Vec<Loop> = vec![];
+  for &b in &cfg.order {
+    for &header in &cfg.blocks[b].succs {
+      if !cfg.dominates(header, b) {
+        continue;
+      }
+      // back edge b -> header, walk predecessors back up to the header.
+      let mut body: Vec<usize> = vec![header];
+      let mut stack: Vec<usize> = vec![b];
+      while let Some(n) = stack.pop() {
+        if body.contains(&n) {
+          continue;
+        }
+        body.push(n);
+        stack.extend(cfg.blocks[n].preds.iter().filter(|p| cfg.reachable(**p)));
+      }
+      match loops.iter_mut().find(|l| l.header == header) {
+        Some(existing) => {
+          for n in body {
+            if !existing.contains(n) {
+              existing.blocks.push(n);
+            }
+          }
+        }
+        None => loops.push(Loop { header, blocks: body }),
+      }
+    }
+  }
+  loops.sort_by_key(|l| l.blocks.len());
+  return loops;
+}
+
+fn header_label(function: &Function, cfg: &Cfg, l: &Loop) -> Option<String> {
+  match function.body.get(cfg.blocks[l.header].start) {
+    Some(Instr::Label(label)) => Some(label.clone()),
+    _ => None,
+  }
+}
+
+fn find_loop(function: &Function, label: &String) -> Option<(Cfg, Loop)> {
+  let cfg = cfg::build(function);
+  let found = find_loops(&cfg).into_iter().find(|l| header_label(function, &cfg, l).as_ref() == Some(label));
+  return found.map(|l| (cfg, l));
+}
+
+pub fn optimize_loops(function: &mut Function, overflow: Overflow) {
+  let mut done: Vec<String> = vec![];
+  loop {
+    let cfg = cfg::build(function);
+    let next = find_loops(&cfg).iter().filter_map(|l| header_label(function, &cfg, l)).find(|label| !done.contains(label));
+    let header = match next {
+      Some(header) => header,
+      None => break,
+    };
+    done.push(header.clone());
+
+    let preheader = insert_preheader(function, &header);
+    while hoist_one(function, &header, &preheader, overflow) {}
+    // the running sum only equals i * c while both wrap the same way.
+    if overflow == Overflow::Wrap {
+      reduce_strength(function, &header, &preheader);
+    }
+    remove_checks(function, &header);
+  }
+}
+
+// new block that every entry from outside the loop goes through, returning its label.
+// it goes in front of the header unless the loop falls through into the header
+// from its last block, as the loops the parser writes with the condition at the
+// bottom do; there it goes in front of the loop and jumps to the header, so the
+// back edge doesn't need a jump of its own around it.
+fn insert_preheader(function: &mut Function, header: &String) -> String {
+  let (cfg, l) = find_loop(function, header).unwrap();
+  let preheader = format!(":preheader{}", crate::create_num());
+  let header_start = cfg.blocks[l.header].start;
+
+  for b in 0..cfg.blocks.len() {
+    if l.contains(b) {
+      continue;
+    }
+    for i in cfg.blocks[b].start..cfg.blocks[b].end {
+      for label in ir::targets_mut(&mut function.body[i]) {
+        if label == header {
+          *label = preheader.clone();
+        }
+      }
+    }
+  }
+
+  let ends_block = |i: usize| matches!(function.body[i], Instr::Jmp(_) | Instr::JumpTable(_, _, _) | Instr::Ret(_));
+  let mut inserted = vec![Instr::Label(preheader.clone())];
+  let mut position = header_start;
+  if l.header > 0 && l.contains(l.header - 1) && !ends_block(cfg.blocks[l.header - 1].end - 1) {
+    let first = cfg.blocks[*l.blocks.iter().min().unwrap()].start;
+    if first > 0 && ends_block(first - 1) {
+      inserted.push(Instr::Jmp(header.clone()));
+      position = first;
+    } else {
+      inserted.insert(0, Instr::Jmp(header.clone()));
+    }
+  }
+  function.body.splice(position..position, inserted);
+  return preheader;
+}
+
+// where code moved out of the loop goes: the end of the preheader, which is
+// either the header label or the preheader's jump to it.
+fn preheader_end(function: &Function, preheader: &String) -> usize {
+  let start = function.body.iter().position(|i| i == &Instr::Label(preheader.clone())).unwrap();
+  let length = function.body[start + 1..].iter().position(|i| matches!(i, Instr::Label(_) | Instr::Jmp(_))).unwrap();
+  return start + 1 + length;
+}
+
+// number of times each variable is written inside the loop.
+fn loop_defs(function: &Function, cfg: &Cfg, l: &Loop) -> HashMap<String, usize> {
+  let mut defs: HashMap<String, usize> = HashMap::new();
+  for &b in &l.blocks {
+    for instr in &function.body[cfg.blocks[b].start..cfg.blocks[b].end] {
+      if let Some(x) = ir::def(instr) {
+        *defs.entry(x.clone()).or_insert(0) += 1;
+      }
+    }
+  }
+  return defs;
+}
+
+fn invariant(operand: &String, defs: &HashMap<String, usize>) -> bool {
+  ir::is_constant(operand) || !defs.contains_key(operand)
+}
+
+// can the definition of `x` at `index` move to the preheader?
+fn hoistable(function: &Function, cfg: &Cfg, l: &Loop, defs: &HashMap<String, usize>, index: usize, overflow: Overflow) -> bool {
+  let instr = &function.body[index];
+  let x = match instr {
+    Instr::Mov(x, y) if invariant(y, defs) => x,
+    Instr::Binary(_, x, y, z) if invariant(y, defs) && invariant(z, defs) && !ir::can_trap(instr, overflow) => x,
+    _ => return false,
+  };
+  if defs.get(x) != Some(&1) {
+    return false;
+  }
+
+  let def_block = cfg.block_of(index);
+  let mut used_outside = false;
+  for (b, block) in cfg.blocks.iter().enumerate() {
+    for i in block.start..block.end {
+      if !ir::uses(&function.body[i]).contains(&x) {
+        continue;
+      }
+      if !l.contains(b) {
+        used_outside = true;
+      } else if (b == def_block && i <= index) || (b != def_block && !cfg.dominates(def_block, b)) {
+        // the loop reads a value of x from before this definition.
+        return false;
+      }
+    }
+  }
+
+  if used_outside {
+    for &b in &l.blocks {
+      let exits = cfg.blocks[b].succs.iter().any(|s| !l.contains(*s));
+      if exits && !cfg.dominates(def_block, b) {
+        return false;
+      }
+    }
+  }
+  return true;
+}
+
+// move one invariant instruction (and its declaration) into the preheader.
+fn hoist_one(function: &mut Function, header: &String, preheader: &String, overflow: Overflow) -> bool {
+  let (cfg, l) = match find_loop(function, header) {
+    Some(found) => found,
+    None => return false,
+  };
+  let defs = loop_defs(function, &cfg, &l);
+
+  let mut blocks = l.blocks.clone();
+  blocks.sort();
+  let mut chosen: Option<usize> = None;
+  for &b in &blocks {
+    for i in cfg.blocks[b].start..cfg.blocks[b].end {
+      if hoistable(function, &cfg, &l, &defs, i, overflow) {
+        chosen = Some(i);
+        break;
+      }
+    }
+    if chosen.is_some() {
+      break;
+    }
+  }
+  let index = match chosen {
+    Some(index) => index,
+    None => return false,
+  };
+
+  let instr = function.body[index].clone();
+  let x = ir::def(&instr).unwrap().clone();
+  let mut remove: Vec<usize> = vec![index];
+  let mut declared = false;
+  for &b in &blocks {
+    for i in cfg.blocks[b].start..cfg.blocks[b].end {
+      if function.body[i] == Instr::Int(x.clone()) {
+        remove.push(i);
+        declared = true;
+      }
+    }
+  }
+  remove.sort();
+  for i in remove.iter().rev() {
+    function.body.remove(*i);
+  }
+
+  let mut moved: Vec<Instr> = vec![];
+  if declared {
+    moved.push(Instr::Int(x));
+  }
+  moved.push(instr);
+  let position = preheader_end(function, preheader);
+  function.body.splice(position..position, moved);
+  return true;
+}
+
+// i = i + c, either directly or through a temp: t = i + c; i = t
+fn induction_step(function: &Function, cfg: &Cfg, l: &Loop, defs: &HashMap<String, usize>, index: usize) -> Option<(String, i32)> {
+  let step = |instr: &Instr, i: &String| -> Option<i32> {
+    match instr {
+      Instr::Binary(op, _, y, z) if op == "add" && y == i && ir::is_constant(z) => z.parse().ok(),
+      Instr::Binary(op, _, y, z) if op == "add" && z == i && ir::is_constant(y) => y.parse().ok(),
+      Instr::Binary(op, _, y, z) if op == "sub" && y == i && ir::is_constant(z) => z.parse::<i32>().ok().map(|c| c.wrapping_neg()),
+      _ => None,
+    }
+  };
+
+  let instr = &function.body[index];
+  let i = ir::def(instr)?;
+  if defs.get(i) != Some(&1) {
+    return None;
+  }
+  match instr {
+    Instr::Binary(_, _, _, _) => step(instr, i).map(|c| (i.clone(), c)),
+    Instr::Mov(_, t) if defs.get(t) == Some(&1) => {
+      for &b in &l.blocks {
+        for k in cfg.blocks[b].start..cfg.blocks[b].end {
+          if ir::def(&function.body[k]) == Some(t) {
+            return step(&function.body[k], i).map(|c| (i.clone(), c));
+          }
+        }
+      }
+      None
+    }
+    _ => None,
+  }
+}
+
+// replace j = i * c with a running sum that is bumped whenever i is.
+fn reduce_strength(function: &mut Function, header: &String, preheader: &String) {
+  let (cfg, l) = match find_loop(function, header) {
+    Some(found) => found,
+    None => return,
+  };
+  let defs = loop_defs(function, &cfg, &l);
+
+  // basic induction variable -> (index of its update, step)
+  let mut induction: HashMap<String, (usize, i32)> = HashMap::new();
+  for &b in &l.blocks {
+    for k in cfg.blocks[b].start..cfg.blocks[b].end {
+      if let Some((i, step)) = induction_step(function, &cfg, &l, &defs, k) {
+        induction.insert(i, (k, step));
+      }
+    }
+  }
+  if induction.is_empty() {
+    return;
+  }
+
+  // (iv, factor) -> running sum variable
+  let mut sums: Vec<(String, i32, String)> = vec![];
+  let mut replace: HashMap<usize, Instr> = HashMap::new();
+  for &b in &l.blocks {
+    for k in cfg.blocks[b].start..cfg.blocks[b].end {
+      let (j, i, c) = match &function.body[k] {
+        Instr::Binary(op, j, y, z) if op == "mult" && induction.contains_key(y) && ir::is_constant(z) => (j, y, z),
+        Instr::Binary(op, j, y, z) if op == "mult" && induction.contains_key(z) && ir::is_constant(y) => (j, z, y),
+        _ => continue,
+      };
+      let factor: i32 = c.parse().unwrap();
+      let existing = sums.iter().find(|(iv, f, _)| iv == i && *f == factor).map(|(_, _, s)| s.clone());
+      let sum = match existing {
+        Some(sum) => sum,
+        None => {
+          let sum = crate::create_temp();
+          sums.push((i.clone(), factor, sum.clone()));
+          sum
+        }
+      };
+      replace.insert(k, Instr::Mov(j.clone(), sum));
+    }
+  }
+  if sums.is_empty() {
+    return;
+  }
+
+  let mut body: Vec<Instr> = vec![];
+  for (k, instr) in function.body.iter().enumerate() {
+    body.push(replace.remove(&k).unwrap_or_else(|| instr.clone()));
+    for (i, factor, sum) in &sums {
+      let (update, step) = induction[i];
+      if update == k {
+        let increment = factor.wrapping_mul(step);
+        body.push(Instr::Binary(String::from("add"), sum.clone(), sum.clone(), increment.to_string()));
+      }
+    }
+  }
+
+  let mut setup: Vec<Instr> = vec![];
+  for (i, factor, sum) in &sums {
+    setup.push(Instr::Int(sum.clone()));
+    setup.push(Instr::Binary(String::from("mult"), sum.clone(), i.clone(), factor.to_string()));
+  }
+  function.body = body;
+  let position = preheader_end(function, preheader);
+  function.body.splice(position..position, setup);
+}
+
+// what `var` holds at the end of block b, following copies back up the
+// dominator tree: a constant, or a variable that still has the same value at
+// the end of b. a block's immediate dominator is only skipped to when nothing
+// on the way from it writes the variable being followed.
+fn entry_value(function: &Function, cfg: &Cfg, mut b: usize, var: &String) -> Option<String> {
+  let mut current = var.clone();
+  let mut defined: HashSet<String> = HashSet::new();
+  loop {
+    for instr in function.body[cfg.blocks[b].start..cfg.blocks[b].end].iter().rev() {
+      match ir::def(instr) {
+        Some(x) if x == &current => match instr {
+          Instr::Mov(_, y) => current = y.clone(),
+          _ => return if defined.contains(&current) { None } else { Some(current) },
+        },
+        Some(x) => {
+          defined.insert(x.clone());
+        }
+        None => {}
+      }
+      if ir::is_constant(&current) {
+        return Some(current);
+      }
+    }
+    let d = match cfg.idom[b] {
+      Some(d) => d,
+      None => break,
+    };
+    // blocks that can run between the end of d and the start of b.
+    let mut between: Vec<usize> = vec![];
+    let mut stack: Vec<usize> = cfg.blocks[b].preds.clone();
+    while let Some(p) = stack.pop() {
+      if p == d || between.contains(&p) || !cfg.reachable(p) {
+        continue;
+      }
+      between.push(p);
+      stack.extend(cfg.blocks[p].preds.iter().cloned());
+    }
+    for p in between {
+      for instr in &function.body[cfg.blocks[p].start..cfg.blocks[p].end] {
+        if let Some(x) = ir::def(instr) {
+          if x == &current {
+            return None;
+          }
+          defined.insert(x.clone());
+        }
+      }
+    }
+    b = d;
+  }
+  if defined.contains(&current) { None } else { Some(current) }
+}
+
+// a loop of the form
+//   :header  %lt t, i, n  %branch_ifn t, :exit  ...  i = i + 1 ... %jmp :header
+// where i starts at a constant >= 0 and n is invariant, so that i runs
+// through start..n. checks are the %check of i that see the same i the
+// header compared, so they only ever run with start <= i < n.
+struct Counted {
+  i: String,
+  n: String,
+  start: i32,
+  preheader: usize,
+  defs: HashMap<String, usize>,
+  checks: Vec<usize>,
+}
+
+fn counted_loop(function: &Function, cfg: &Cfg, l: &Loop) -> Option<Counted> {
+  let defs = loop_defs(function, cfg, l);
+  let h = &cfg.blocks[l.header];
+  let t = match function.body.get(h.end - 1) {
+    Some(Instr::BranchIfn(t, _)) => t,
+    _ => return None,
+  };
+  let body = l.header + 1;
+  // t may be written more than once, as long as it all happens in the header.
+  let writes = function.body[h.start..h.end].iter().filter(|instr| ir::def(instr) == Some(t)).count();
+  if defs.get(t) != Some(&writes) || !l.contains(body) || h.succs.iter().any(|s| *s != body && l.contains(*s)) {
+    return None;
+  }
+  let (i, n) = match function.body[h.start..h.end].iter().rev().find(|instr| ir::def(instr) == Some(t)) {
+    Some(Instr::Binary(op, _, i, n)) if op == "lt" && !ir::is_constant(i) && invariant(n, &defs) => (i.clone(), n.clone()),
+    _ => return None,
+  };
+
+  // the only write to i is a step of 1, outside the header and not in an inner cycle.
+  let mut update: Option<usize> = None;
+  for &b in &l.blocks {
+    for k in cfg.blocks[b].start..cfg.blocks[b].end {
+      if ir::def(&function.body[k]) == Some(&i) {
+        update = Some(k);
+      }
+    }
+  }
+  let update = match update {
+    Some(k) if induction_step(function, cfg, l, &defs, k) == Some((i.clone(), 1)) => k,
+    _ => return None,
+  };
+  let update_block = cfg.block_of(update);
+  if update_block == l.header {
+    return None;
+  }
+  // blocks reachable after the update without going back through the header.
+  let mut after: Vec<usize> = vec![];
+  let mut stack: Vec<usize> = cfg.blocks[update_block].succs.clone();
+  while let Some(b) = stack.pop() {
+    if b == l.header || !l.contains(b) || after.contains(&b) {
+      continue;
+    }
+    after.push(b);
+    stack.extend(cfg.blocks[b].succs.iter().cloned());
+  }
+  if after.contains(&update_block) {
+    return None;
+  }
+
+  let preheader = match h.preds.iter().filter(|p| !l.contains(**p)).collect::<Vec<&usize>>().as_slice() {
+    [p] => **p,
+    _ => return None,
+  };
+  let start = match entry_value(function, cfg, preheader, &i).map(|start| start.parse::<i32>()) {
+    Some(Ok(start)) if start >= 0 => start,
+    _ => return None,
+  };
+
+  let mut checks: Vec<usize> = vec![];
+  for &b in &l.blocks {
+    if b == l.header || !cfg.dominates(body, b) || after.contains(&b) {
+      continue;
+    }
+    for k in cfg.blocks[b].start..cfg.blocks[b].end {
+      if b == update_block && k > update {
+        break;
+      }
+      if matches!(&function.body[k], Instr::Check(index, _, _, _) if index == &i) {
+        checks.push(k);
+      }
+    }
+  }
+  return Some(Counted { i, n, start, preheader, defs, checks });
+}
+
+// drop the checks of a counted loop whose size is at least n. sizes are
+// compared by what they hold when the loop starts.
+fn remove_checks(function: &mut Function, header: &String) {
+  let (cfg, l) = match find_loop(function, header) {
+    Some(found) => found,
+    None => return,
+  };
+  let counted = match counted_loop(function, &cfg, &l) {
+    Some(counted) => counted,
+    None => return,
+  };
+  let bound = entry_value(function, &cfg, counted.preheader, &counted.n);
+  let mut remove: Vec<usize> = vec![];
+  for &k in &counted.checks {
+    let size = match &function.body[k] {
+      Instr::Check(_, size, _, _) => size,
+      _ => continue,
+    };
+    if !invariant(size, &counted.defs) {
+      continue;
+    }
+    let in_range = match (entry_value(function, &cfg, counted.preheader, size), &bound) {
+      (Some(size), Some(n)) if &size == n => true,
+      (Some(size), Some(n)) => matches!((size.parse::<i32>(), n.parse::<i32>()), (Ok(size), Ok(n)) if n <= size),
+      _ => false,
+    };
+    if in_range {
+      remove.push(k);
+    }
+  }
+  remove.sort();
+  for k in remove.iter().rev() {
+    function.body.remove(*k);
+  }
+}
+
+// compile-time counterpart of remove_checks: a counted loop with constant
+// bounds, no inner loops and no way out but the header runs every check
+// that executes on each iteration for i = start..n, so one with a constant
+// size below n is bound to fail.
+pub fn check_bounds(function: &Function) -> Result<(), String> {
+  let cfg = cfg::build(function);
+  let loops = find_loops(&cfg);
+  for l in &loops {
+    if loops.iter().any(|other| other.header != l.header && l.contains(other.header)) {
+      continue;
+    }
+    let leaves = l.blocks.iter().any(|&b| b != l.header && (cfg.blocks[b].succs.is_empty() || cfg.blocks[b].succs.iter().any(|s| !l.contains(*s))));
+    if leaves {
+      continue;
+    }
+    let counted = match counted_loop(function, &cfg, l) {
+      Some(counted) => counted,
+      None => continue,
+    };
+    let n = match entry_value(function, &cfg, counted.preheader, &counted.n).map(|n| n.parse::<i32>()) {
+      Some(Ok(n)) => n,
+      _ => continue,
+    };
+    let latches: Vec<usize> = cfg.blocks[l.header].preds.iter().cloned().filter(|p| l.contains(*p)).collect();
+    for &k in &counted.checks {
+      let (size, arr, line) = match &function.body[k] {
+        Instr::Check(_, size, arr, line) => (size, arr, line),
+        _ => continue,
+      };
+      let b = cfg.block_of(k);
+      if !latches.iter().all(|p| cfg.dominates(b, *p)) || !invariant(size, &counted.defs) {
+        continue;
+      }
+      let size = match entry_value(function, &cfg, counted.preheader, size).map(|size| size.parse::<i32>()) {
+        Some(Ok(size)) => size,
+        _ => continue,
+      };
+      // the first value of i that is out of range, if the loop gets that far.
+      let first = counted.start.max(size);
+      if first < n {
+        return Err(format!("{} in the loop over {}", ir::bounds_error(arr, first, size, *line), counted.i));
+      }
+    }
+  }
+  return Ok(());
+}
@@ -1,16 +1,49 @@
-use std::{env, fs};
+// the compiler is written with explicit returns, takes &Vec and &String like
+// the parser always has, and threads its tables through the parse functions
+// as arguments, so clippy isn't asked to change any of that.
+#![allow(clippy::needless_return, clippy::ptr_arg, clippy::too_many_arguments)]
 
-mod interpreter;    
+use std::{env, fs};
+use std::path::Path;
+use std::sync::Mutex;
+
+mod ir;
+mod opt;
+mod inline;
+mod cfg;
+mod loops;
+mod tailcall;
+mod ssa;
+mod x86;
+mod cbackend;
+mod check;
+mod llvm;
+mod wat;
+mod bytecode;
+mod vm;
+mod riscv;
+mod rvsim;
+mod regalloc;
+mod jit;
+#[cfg(test)]
+mod tests;
 
 #[derive(Debug, Clone)]
+#[allow(clippy::enum_variant_names)]
 enum Token {
     NotToken, //yes
 	Func, 
 	Return,
 	Int,
+	Const,
 	Print,
 	Read,
 	While,
+	For,
+	Do,
+	Switch,
+	Case,
+	Default,
 	If,
 	Else,
 	Break,
@@ -22,6 +55,7 @@ enum Token {
 	LeftBracket,
 	RightBracket,
 	Comma,
+	Colon,
 	Semicolon,
 	Plus, // yes 
 	Subtract, // yes
@@ -29,6 +63,13 @@ enum Token {
 	Divide, // yes
 	Modulus, // yes
 	Assign, // me
+	PlusAssign,
+	SubtractAssign,
+	MultiplyAssign,
+	DivideAssign,
+	ModulusAssign,
+	Increment,
+	Decrement,
 	Less, // me
 	LessEqual, // me
 	Greater, // me
@@ -41,19 +82,52 @@ enum Token {
 
 fn main() {
     // get commandline arguments.
+    // anything starting with "--" is an option, the rest is the input file.
     let args: Vec<String> = env::args().collect();
-    if args.len() == 1 {
+    let mut options: Vec<String> = vec![];
+    let mut files: Vec<String> = vec![];
+    for arg in &args[1..] {
+        if arg.starts_with("--") {
+            options.push(arg.clone());
+        } else {
+            files.push(arg.clone());
+        }
+    }
+    // "run" before the file name is accepted and means the same as leaving it out.
+    if files.len() == 2 && files[0] == "run" {
+        files.remove(0);
+    }
+
+    if files.is_empty() {
         println!("Please provide an input file.");
         return;
     }
 
-    if args.len() > 2 {
+    if files.len() > 1 {
         println!("Too many commandline arguments.");
         return;
     }
 
+    // compiled bytecode runs directly on the VM.
+    let filename = &files[0];
+    if filename.ends_with(".ttbc") {
+        let program = match fs::read(filename) {
+            Ok(bytes) => bytecode::decode(&bytes),
+            Err(error) => Err(format!("File \"{filename}\": {error}")),
+        };
+        match program {
+            Ok(program) => run_vm(&program),
+            Err(message) => {
+                println!("**Error**");
+                println!("----------------------");
+                println!("Error: {message}");
+                println!("----------------------");
+            }
+        }
+        return;
+    }
+
     // read the entire file.
-    let filename = &args[1];
     let result = fs::read_to_string(filename);
     let code = match result {
         Err(error) => {
@@ -98,17 +172,90 @@ fn main() {
     match parse_program(&tokens, &mut index) {
 
       Ok(generated_code) => {
+          let generated_code = match optimize(&generated_code, &options) {
+              Ok(code) => code,
+              Err(message) => {
+                  println!("**Error**");
+                  println!("----------------------");
+                  println!("Error: {message}");
+                  println!("----------------------");
+                  return;
+              }
+          };
+          let generated_code = if options.contains(&String::from("--ssa")) {
+              match ssa_round_trip(&generated_code) {
+                  Ok(code) => code,
+                  Err(message) => {
+                      println!("**Error**");
+                      println!("----------------------");
+                      println!("Error: {message}");
+                      println!("----------------------");
+                      return;
+                  }
+              }
+          } else {
+              generated_code
+          };
           println!("Intermediate Code:");
           println!("---------------------------");
           println!("{generated_code}");
           println!("---------------------------");
-          interpreter::execute_ir(&generated_code);
+          if options.contains(&String::from("--dump-regalloc")) {
+              dump_regalloc(&generated_code, &options);
+          }
+          if let Some(kind) = option_value(&options, "--emit") {
+              match emit(&kind, &generated_code, filename) {
+                  Ok(path) => println!("Wrote {path}"),
+                  Err(message) => {
+                      println!("**Error**");
+                      println!("----------------------");
+                      println!("Error: {message}");
+                      println!("----------------------");
+                  }
+              }
+              return;
+          }
+          if let Some(kind) = option_value(&options, "--check") {
+              if let Err(message) = check_backend(&kind, &generated_code, filename) {
+                  println!("**Error**");
+                  println!("----------------------");
+                  println!("Error: {message}");
+                  println!("----------------------");
+              }
+              return;
+          }
+          if options.contains(&String::from("--bench")) {
+              bench(&generated_code);
+              return;
+          }
+          // the VM runs whatever the JIT can't compile, so the output is the
+          // same either way; --verbose says when that happened and why.
+          if options.contains(&String::from("--jit")) {
+              match ir::parse_program(&generated_code).and_then(|program| jit::run(&program)) {
+                  Ok(()) => return,
+                  Err(message) => {
+                      if options.contains(&String::from("--verbose")) {
+                          println!("jit: {message}, falling back to the VM");
+                      }
+                  }
+              }
+          }
+          // everything else runs on the VM, --vm or not.
+          match ir::parse_program(&generated_code).and_then(|program| bytecode::compile(&program)) {
+              Ok(program) => run_vm(&program),
+              Err(message) => {
+                  println!("**Error**");
+                  println!("----------------------");
+                  println!("Error: {message}");
+                  println!("----------------------");
+              }
+          }
       }
 
       Err(message) => {
           println!("**Error**");
           println!("----------------------");
-          if tokens.len() == 0 {
+          if tokens.is_empty() {
               println!("No code has been provided.");
           } else {
               println!("Error: {message}");
@@ -118,10 +265,247 @@ fn main() {
     }
 }
 
+// value of an option given as "--name=value".
+fn option_value(options: &Vec<String>, name: &str) -> Option<String> {
+    for option in options {
+        if let Some(value) = option.strip_prefix(name) {
+            if let Some(value) = value.strip_prefix("=") {
+                return Some(value.to_string());
+            }
+        }
+    }
+    None
+}
+
+// run the optimisation passes over the generated code.
+// --no-opt skips them, --inline-threshold=N sets the largest callee that gets inlined.
+// tail calls are eliminated even without optimisations unless --no-tco is given.
+// --overflow=wrap|trap|saturate is recorded in the code for everything that runs it.
+fn optimize(code: &String, options: &Vec<String>) -> Result<String, String> {
+    let mut program = ir::parse_program(code)?;
+    if let Some(mode) = option_value(options, "--overflow") {
+        program.overflow = match ir::Overflow::parse(&mode) {
+            Some(overflow) => overflow,
+            None => return Err(format!("unknown --overflow mode '{mode}' (expected wrap, trap or saturate)")),
+        };
+    }
+    // out of range accesses in simple loops are found on a cleaned-up copy, with or without --no-opt.
+    for function in &program.functions {
+        let mut function = function.clone();
+        opt::cleanup(&mut function, program.overflow);
+        loops::check_bounds(&function)?;
+    }
+    if !options.contains(&String::from("--no-tco")) {
+        for function in program.functions.iter_mut() {
+            tailcall::eliminate_tail_calls(function);
+        }
+    }
+
+    if options.contains(&String::from("--no-opt")) {
+        return Ok(ir::print_program(&program));
+    }
+
+    let threshold = match option_value(options, "--inline-threshold") {
+        None => inline::DEFAULT_THRESHOLD,
+        Some(value) => match value.parse::<usize>() {
+            Ok(threshold) => threshold,
+            Err(_) => return Err(format!("invalid inline threshold '{value}'")),
+        },
+    };
+
+    inline::inline_program(&mut program.functions, threshold);
+    let overflow = program.overflow;
+    for function in program.functions.iter_mut() {
+        opt::cleanup(function, overflow);
+        loops::optimize_loops(function, overflow);
+        opt::cleanup(function, overflow);
+    }
+    return Ok(ir::print_program(&program));
+}
+
+// --ssa: print the code in ssa form, then continue with it translated back out.
+fn ssa_round_trip(code: &String) -> Result<String, String> {
+    let mut program = ir::parse_program(code)?;
+    for function in program.functions.iter_mut() {
+        ssa::construct(function);
+    }
+    println!("SSA Form:");
+    println!("---------------------------");
+    println!("{}", ir::print_program(&program));
+    println!("---------------------------");
+    for function in program.functions.iter_mut() {
+        ssa::destruct(function);
+    }
+    return Ok(ir::print_program(&program));
+}
+
+// --emit=KIND writes the program next to the input file instead of running it.
+// asm: x86-64 assembly (.s), exe: assembled and linked native executable,
+// c: C99 source (.c), llvm: LLVM IR (.ll), wat: WebAssembly text (.wat),
+// bc: bytecode for the VM (.ttbc), riscv: RV32IM assembly (.rv.s).
+fn emit(kind: &str, code: &String, filename: &String) -> Result<String, String> {
+    let program = ir::parse_program(code)?;
+    let input = Path::new(filename);
+    match kind {
+        "c" => {
+            let source = input.with_extension("c");
+            if let Err(error) = fs::write(&source, cbackend::generate(&program)?) {
+                return Err(format!("could not write {}: {error}", source.display()));
+            }
+            return Ok(source.display().to_string());
+        }
+        "llvm" => {
+            let module = input.with_extension("ll");
+            if let Err(error) = fs::write(&module, llvm::generate(&program)?) {
+                return Err(format!("could not write {}: {error}", module.display()));
+            }
+            return Ok(module.display().to_string());
+        }
+        "wat" => {
+            let module = input.with_extension("wat");
+            if let Err(error) = fs::write(&module, wat::generate(&program)?) {
+                return Err(format!("could not write {}: {error}", module.display()));
+            }
+            return Ok(module.display().to_string());
+        }
+        "riscv" => {
+            let assembly = input.with_extension("rv.s");
+            if let Err(error) = fs::write(&assembly, riscv::generate(&program)?) {
+                return Err(format!("could not write {}: {error}", assembly.display()));
+            }
+            return Ok(assembly.display().to_string());
+        }
+        "bc" => {
+            let file = input.with_extension("ttbc");
+            if let Err(error) = fs::write(&file, bytecode::encode(&bytecode::compile(&program)?)) {
+                return Err(format!("could not write {}: {error}", file.display()));
+            }
+            return Ok(file.display().to_string());
+        }
+        "asm" | "exe" => {
+            let assembly = input.with_extension("s");
+            if let Err(error) = fs::write(&assembly, x86::generate(&program)?) {
+                return Err(format!("could not write {}: {error}", assembly.display()));
+            }
+            if kind == "asm" {
+                return Ok(assembly.display().to_string());
+            }
+            let executable = input.with_extension("");
+            x86::link(&assembly, &executable)?;
+            return Ok(executable.display().to_string());
+        }
+        _ => Err(format!("unknown --emit kind '{kind}'")),
+    }
+}
+
+// live intervals and register assignments for every function, using the
+// registers of the backend picked with --emit (x86-64 by default).
+fn dump_regalloc(code: &String, options: &Vec<String>) {
+    let program = match ir::parse_program(code) {
+        Ok(program) => program,
+        Err(message) => {
+            println!("Error: {message}");
+            return;
+        }
+    };
+    let registers: &[&str] = if option_value(options, "--emit").as_deref() == Some("riscv") { &riscv::ALLOCATABLE } else { &x86::ALLOCATABLE };
+    println!("Register Allocation:");
+    println!("---------------------------");
+    for function in &program.functions {
+        let allocation = regalloc::allocate(function, registers.len());
+        print!("{}", regalloc::dump(function, &allocation, registers));
+    }
+    println!("---------------------------");
+}
+
+fn run_vm(program: &bytecode::Program) {
+    if let Err(message) = vm::run(program) {
+        println!("runtime error: {message}");
+        std::process::exit(1);
+    }
+}
+
+// --bench runs the program with the VM and the JIT and
+// reports how long each one took, and how many ops the VM executed.
+// meant for loop-heavy programs without input.
+fn bench(code: &String) {
+    let program = match ir::parse_program(code) {
+        Ok(program) => program,
+        Err(message) => {
+            println!("Error: {message}");
+            return;
+        }
+    };
+    let mut times: Vec<(&str, Result<std::time::Duration, String>)> = vec![];
+    let start = std::time::Instant::now();
+    let result = bytecode::compile(&program).and_then(|program| vm::run(&program));
+    times.push(("vm", result.map(|_| start.elapsed())));
+    let start = std::time::Instant::now();
+    let result = jit::run(&program);
+    times.push(("jit", result.map(|_| start.elapsed())));
+    println!("Benchmark:");
+    println!("---------------------------");
+    for (engine, time) in &times {
+        match time {
+            Ok(time) => println!("{engine:<12} {:>10.3} ms", time.as_secs_f64() * 1000.0),
+            Err(message) => println!("{engine:<12} error: {message}"),
+        }
+    }
+    if let Ok(program) = bytecode::compile(&program) {
+        println!("{:<12} {:>10}", "vm ops", vm::steps(&program, ""));
+    }
+    println!("---------------------------");
+}
+
+// --check=KIND builds the program with a backend (exe, c, llvm or riscv),
+// runs it on stdin and compares the output with the VM's. llvm
+// modules run under lli, riscv assembly in the bundled simulator.
+fn check_backend(kind: &str, code: &String, filename: &String) -> Result<(), String> {
+    let (program, args) = match kind {
+        "exe" => (emit("exe", code, filename)?, vec![]),
+        "c" => {
+            let source = emit("c", code, filename)?;
+            let executable = Path::new(filename).with_extension("");
+            cbackend::compile(Path::new(&source), &executable)?;
+            (executable.display().to_string(), vec![])
+        }
+        "llvm" => {
+            let module = emit("llvm", code, filename)?;
+            (String::from("lli"), llvm::lli_args(Path::new(&module)))
+        }
+        "riscv" => (emit("riscv", code, filename)?, vec![]),
+        _ => return Err(format!("unknown --check kind '{kind}'")),
+    };
+
+    let input = check::read_stdin();
+    let expected = vm::output(&bytecode::compile(&ir::parse_program(code)?)?, &input);
+    let actual = if kind == "riscv" {
+        match fs::read_to_string(&program) {
+            Ok(assembly) => rvsim::run(&assembly, &input)?,
+            Err(error) => return Err(format!("could not read {program}: {error}")),
+        }
+    } else {
+        check::run(Path::new(&program), &args, &input)?
+    };
+    return check::compare(kind, &expected, &actual);
+}
+
 fn lex(mut code: &str) -> Result<Vec<Token>, String> {
     let mut tokens: Vec<Token> = vec![];
-    while code.len() > 0 {
-      let (success, token, rest) = lex_number(code);
+    // the source line of every token, for runtime errors. tokens never span
+    // lines, so the ones pushed last time round start on the current line.
+    let mut lines: Vec<usize> = vec![];
+    let mut line = 1;
+    let mut rest_len = code.len();
+    let source = code;
+    while !code.is_empty() {
+      lines.resize(tokens.len(), line);
+      line += source[source.len() - rest_len..source.len() - code.len()].matches('\n').count();
+      rest_len = code.len();
+      let (success, token, rest) = match lex_number(code) {
+        Ok(lexed) => lexed,
+        Err(message) => return Err(format!("line {line}: {message}")),
+      };
       if success {
         code = rest; 
         tokens.push(token);
@@ -135,7 +519,7 @@ fn lex(mut code: &str) -> Result<Vec<Token>, String> {
       }
 
       if code.starts_with("#") {
-        while code.len() > 0 {
+        while !code.is_empty() {
           code = &code[1..];
           if code.starts_with("\n") {
             break;
@@ -144,6 +528,18 @@ fn lex(mut code: &str) -> Result<Vec<Token>, String> {
         continue;
       }
   
+      if code.starts_with("+=") {
+        code = &code[2..];
+        tokens.push(Token::PlusAssign);
+        continue;
+      }
+
+      if code.starts_with("++") {
+        code = &code[2..];
+        tokens.push(Token::Increment);
+        continue;
+      }
+
       if code.starts_with("+") {
         code = &code[1..];
         tokens.push(Token::Plus);
@@ -199,6 +595,24 @@ fn lex(mut code: &str) -> Result<Vec<Token>, String> {
         continue;
       }
   
+      if code.starts_with(":") {
+        code = &code[1..];
+        tokens.push(Token::Colon);
+        continue;
+      }
+
+      if code.starts_with("-=") {
+        code = &code[2..];
+        tokens.push(Token::SubtractAssign);
+        continue;
+      }
+
+      if code.starts_with("--") {
+        code = &code[2..];
+        tokens.push(Token::Decrement);
+        continue;
+      }
+
       if code.starts_with("-") {
         code = &code[1..];
         tokens.push(Token::Subtract);
@@ -211,18 +625,36 @@ fn lex(mut code: &str) -> Result<Vec<Token>, String> {
         continue;
       }
   
+      if code.starts_with("*=") {
+        code = &code[2..];
+        tokens.push(Token::MultiplyAssign);
+        continue;
+      }
+
       if code.starts_with("*") {
         code = &code[1..];
         tokens.push(Token::Multiply);
         continue;
       }
   
+      if code.starts_with("/=") {
+        code = &code[2..];
+        tokens.push(Token::DivideAssign);
+        continue;
+      }
+
       if code.starts_with("/") {
         code = &code[1..];
         tokens.push(Token::Divide);
         continue;
       }
   
+      if code.starts_with("%=") {
+        code = &code[2..];
+        tokens.push(Token::ModulusAssign);
+        continue;
+      }
+
       if code.starts_with("%") {
         code = &code[1..];
         tokens.push(Token::Modulus);
@@ -299,9 +731,18 @@ fn lex(mut code: &str) -> Result<Vec<Token>, String> {
   
     }
   
+    lines.resize(tokens.len(), line);
+    *TOKEN_LINES.lock().unwrap() = lines;
     return Ok(tokens);
   }
 
+static TOKEN_LINES: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+
+// the source line the token at index was on.
+fn token_line(index: usize) -> usize {
+  return TOKEN_LINES.lock().unwrap().get(index).copied().unwrap_or(0);
+}
+
   fn lex_keyword(code: &str) -> (bool, Token, &str) {
     enum StateMachine {
         Start,
@@ -513,7 +954,7 @@ fn lex(mut code: &str) -> Result<Vec<Token>, String> {
           }
 
           StateMachine::Keyword => {
-            if success == true {
+            if success {
               let token = &code[..index];
               return(true, create_identifier(token), &code[index..]);
             } else {
@@ -523,7 +964,7 @@ fn lex(mut code: &str) -> Result<Vec<Token>, String> {
         }
     }
 
-    if success == true { //if we are creating a token here -> we can assume that the token spans the input
+    if success { //if we are creating a token here -> we can assume that the token spans the input
       return (true, create_identifier(code), "");
     } else {
       return (false, Token::NotToken, "");
@@ -531,17 +972,13 @@ fn lex(mut code: &str) -> Result<Vec<Token>, String> {
   }
 
   fn lex_space(code: &str) -> (bool, &str) {
-    for letter in code.chars() {
-      if letter.is_whitespace() {
-        return (true, &code[1..]);
-      } else {
-        return (false, code);
-      }
+    match code.chars().next() {
+      Some(letter) if letter.is_whitespace() => return (true, &code[1..]),
+      _ => return (false, code),
     }
-    return (false, code);
   }
 
-  fn lex_number(code: &str) -> (bool, Token, &str) {
+  fn lex_number(code: &str) -> Result<(bool, Token, &str), String> {
     enum StateMachine {
       Start,
       Number,
@@ -553,37 +990,47 @@ fn lex(mut code: &str) -> Result<Vec<Token>, String> {
     for letter in code.chars() {
       match state {
       StateMachine::Start => {
-        if letter >= '0' && letter <= '9' {
+        if letter.is_ascii_digit() {
           state = StateMachine::Number;
           success = true;
           index += 1;
         } else {
-          return (false, Token::NotToken, "");
+          return Ok((false, Token::NotToken, ""));
         }
       }
   
       StateMachine::Number => {
-        if letter >= '0' && letter <= '9' {
+        if letter.is_ascii_digit() {
           state = StateMachine::Number;
           success = true;
           index += 1;
-        } else if (letter >= '?' && letter <= 'Z') || (letter >= '^' && letter <= 'z') || letter >= '$'  && letter <= '\'' || letter == '"' || letter == '.' || letter == '\\' || letter == ':' || letter == '|' || letter == '~' {
+        } else if ('?'..='Z').contains(&letter) || ('^'..='z').contains(&letter) || ('$'..='\'').contains(&letter) || letter == '"' || letter == '.' || letter == '\\' || letter == '|' || letter == '~' {
 
-          return (false, Token::NotToken, "");
+          return Ok((false, Token::NotToken, ""));
         } else {
-          let num = code[..index].parse::<i32>().unwrap();
-          return (true, Token::Num(num), &code[index..]);
+          let num = number(&code[..index])?;
+          return Ok((true, Token::Num(num), &code[index..]));
         }
       }
   
       }
     }
   
-    if success == true {
-      let num: i32 = code.parse::<i32>().unwrap();
-      return (true, Token::Num(num), "");
+    if success {
+      let num = number(code)?;
+      return Ok((true, Token::Num(num), ""));
     } else {
-      return (false, Token::NotToken, "");
+      return Ok((false, Token::NotToken, ""));
+    }
+  }
+
+  // the value of a literal, which has to fit in an int. a minus sign is a
+  // token of its own, so the smallest int is written 0 - 2147483647 - 1 in an
+  // expression, or -2147483647 - 1 where a constant is expected.
+  fn number(digits: &str) -> Result<i32, String> {
+    match digits.parse::<i32>() {
+      Ok(num) => Ok(num),
+      Err(_) => Err(format!("integer literal {digits} is too large (the largest int is {})", i32::MAX)),
     }
   }
 
@@ -599,7 +1046,7 @@ fn lex(mut code: &str) -> Result<Vec<Token>, String> {
     for letter in code.chars() {
       match state {
       StateMachine::Start => {
-        if (letter >= 'a' && letter <= 'z') || (letter >= 'A' && letter <= 'Z'){
+        if letter.is_ascii_lowercase() || letter.is_ascii_uppercase() {
           state = StateMachine::Ident;
           success = true;
           index += 1;
@@ -609,7 +1056,7 @@ fn lex(mut code: &str) -> Result<Vec<Token>, String> {
       }
   
       StateMachine::Ident => {
-        if (letter >= 'A' && letter <= 'Z') || (letter >= 'a' && letter <= 'z') || (letter >= '0' && letter <= '9') || letter == '_' {
+        if letter.is_ascii_uppercase() || letter.is_ascii_lowercase() || letter.is_ascii_digit() || letter == '_' {
           state = StateMachine::Ident;
           success = true;
           index += 1;
@@ -622,7 +1069,7 @@ fn lex(mut code: &str) -> Result<Vec<Token>, String> {
       }
     }
   
-    if success == true {
+    if success {
       return (true, create_identifier(code), "");
     } else {
       return (false, Token::NotToken, "");
@@ -661,9 +1108,15 @@ fn create_identifier(code: &str) -> Token {
     "func" => Token::Func,
     "return" => Token::Return,
     "int" => Token::Int,
+    "const" => Token::Const,
     "print" => Token::Print,
     "read" => Token::Read,
     "while" => Token::While,
+    "for" => Token::For,
+    "do" => Token::Do,
+    "switch" => Token::Switch,
+    "case" => Token::Case,
+    "default" => Token::Default,
     "if" => Token::If,
     "else" => Token::Else,
     "break" => Token::Break,
@@ -675,6 +1128,7 @@ fn create_identifier(code: &str) -> Token {
     "[" => Token::LeftBracket,
     "]" => Token::RightBracket,
     "," => Token::Comma,
+    ":" => Token::Colon,
     ";" => Token::Semicolon,
     "+" => Token::Plus,
     "-" => Token::Subtract,
@@ -682,6 +1136,13 @@ fn create_identifier(code: &str) -> Token {
     "/" => Token::Divide,
     "%" => Token::Modulus,
     "=" => Token::Assign,
+    "+=" => Token::PlusAssign,
+    "-=" => Token::SubtractAssign,
+    "*=" => Token::MultiplyAssign,
+    "/=" => Token::DivideAssign,
+    "%=" => Token::ModulusAssign,
+    "++" => Token::Increment,
+    "--" => Token::Decrement,
     "<" => Token::Less,
     "<=" => Token::LessEqual,
     ">" => Token::Greater,
@@ -697,7 +1158,8 @@ static mut VAR_NUM: i64 = 0;
 fn create_temp() -> String {
     unsafe {
         VAR_NUM += 1;
-        format!("_temp{}", VAR_NUM)
+        let num = VAR_NUM;
+        format!("_temp{}", num)
     }
 }
 
@@ -710,7 +1172,7 @@ fn create_num() -> i32 {
   }
 }
 
-fn peek<'a>(tokens: &'a Vec<Token>, index: usize) -> Option<&'a Token> {
+fn peek(tokens: &Vec<Token>, index: usize) -> Option<&Token> {
   if index < tokens.len() {
       return Some(&tokens[index])
   } else {
@@ -718,7 +1180,7 @@ fn peek<'a>(tokens: &'a Vec<Token>, index: usize) -> Option<&'a Token> {
   }
 }
 
-fn peek_result<'a>(tokens: &'a Vec<Token>, index: usize) -> Result<&'a Token, String> {
+fn peek_result(tokens: &Vec<Token>, index: usize) -> Result<&Token, String> {
   if index < tokens.len() {
       return Ok(&tokens[index])
   } else {
@@ -750,9 +1212,20 @@ fn next_result<'a>(tokens: &'a Vec<Token>, index: &mut usize) -> Result<&'a Toke
 // loop over everything, outputting generated code.
 fn parse_program(tokens: &Vec<Token>, index: &mut usize) -> Result<String, String> {
   let mut generated_code = String::from("");
-  let mut func_table: Vec<String> = vec![];
+  let mut func_table: Vec<(String, Vec<Option<Vec<i32>>>)> = vec![];
+  let mut globals: Vec<String> = vec![];
+  let mut global_arrays: Vec<Array> = vec![];
+  let mut consts: Vec<(String, i32)> = vec![];
   loop {
-      match parse_function(tokens, index, &mut func_table)? {
+      if matches!(peek(tokens, *index), Some(Token::Int)) {
+        generated_code += &parse_global(tokens, index, &mut globals, &mut global_arrays, &consts)?;
+        continue;
+      }
+      if matches!(peek(tokens, *index), Some(Token::Const)) {
+        parse_const(tokens, index, &vec![], &global_arrays, &globals, &mut consts)?;
+        continue;
+      }
+      match parse_function(tokens, index, &mut func_table, &globals, &global_arrays, &consts)? {
       None => {
           break;
       }
@@ -762,13 +1235,232 @@ fn parse_program(tokens: &Vec<Token>, index: &mut usize) -> Result<String, Strin
       }
   }
   
-  if !in_table(&func_table, &format!("main")){
-    return Err(format!("missing 'main' function"));
+  if signature(&func_table, &String::from("main")).is_none() {
+    return Err(String::from("missing 'main' function"));
   }
 
   return Ok(generated_code);
 }
 
+// top-level `int x;` or `int[10] t;`, shared by all the functions after it.
+// globals start out as 0, and functions cannot declare anything by their names.
+fn parse_global(tokens: &Vec<Token>, index: &mut usize, globals: &mut Vec<String>, global_arrays: &mut Vec<Array>, consts: &Vec<(String, i32)>) -> Result<String, String> {
+  *index += 1;
+  let (name, code, shape) = match next_result(tokens, index)? {
+    Token::LeftBracket => {
+      let size = parse_constant(tokens, index, consts)?;
+      if size <= 0 {
+        return Err(String::from("global arrays need a positive constant size"));
+      }
+      if !matches!(next_result(tokens, index)?, Token::RightBracket) {
+        return Err(String::from("expect ']' closing array size declaration"));
+      }
+      let dims = parse_dimensions(tokens, index, consts)?;
+      let size = match dims.iter().try_fold(size, |size, dim| size.checked_mul(*dim)) {
+        Some(size) => size,
+        None => {
+          return Err(String::from("global array is too large"));
+        }
+      };
+      match next_result(tokens, index)? {
+        Token::Ident(name) => (name, format!("%global[] {name}, {size}\n"), Some((dims, size))),
+        _ => {
+          return Err(String::from("expected identifier for array name"));
+        }
+      }
+    }
+    Token::Ident(name) => (name, format!("%global {name}\n"), None),
+    _ => {
+      return Err(String::from("expected '[' for array declaration or identifier for int variable"));
+    }
+  };
+  if in_table(globals, name) || array(global_arrays, name).is_some() {
+    return Err(format!("global {name} already declared"));
+  }
+  if constant(consts, name).is_some() {
+    return Err(format!("{name} is already declared as a constant"));
+  }
+  if let Some((dims, size)) = shape {
+    global_arrays.push(Array { name: name.clone(), ir: name.clone(), dims, size: size.to_string(), in_scope: true });
+  } else {
+    globals.push(name.clone());
+  }
+  match next_result(tokens, index)? {
+    Token::Semicolon => {}
+    Token::Assign => {
+      return Err(format!("global {name} cannot have an initialiser, globals start out as 0"));
+    }
+    _ => {
+      return Err(String::from("expect ';' closing statement"));
+    }
+  }
+  return Ok(code);
+}
+
+// `const int N = 64;`. constants are folded into the code that uses them and
+// never become variables, so the initialiser has to be known at compile time.
+fn parse_const(tokens: &Vec<Token>, index: &mut usize, symbol_table: &Vec<String>, arr_table: &Vec<Array>, globals: &Vec<String>, consts: &mut Vec<(String, i32)>) -> Result<(), String> {
+  *index += 1;
+  if !matches!(next_result(tokens, index)?, Token::Int) {
+    return Err(String::from("expected 'int' after 'const'"));
+  }
+  let name = match next_result(tokens, index)? {
+    Token::Ident(name) => name,
+    _ => {
+      return Err(String::from("expected identifier for constant"));
+    }
+  };
+  if constant(consts, name).is_some() || in_table(symbol_table, name) || array(arr_table, name).is_some() || in_table(globals, name) {
+    return Err(format!("{name} is already declared"));
+  }
+  if !matches!(next_result(tokens, index)?, Token::Assign) {
+    return Err(format!("constant {name} needs an initialiser"));
+  }
+  let value = match parse_constant(tokens, index, consts) {
+    Ok(value) => value,
+    Err(error) => {
+      return Err(format!("initialiser of constant {name}: {error}"));
+    }
+  };
+  if !matches!(next_result(tokens, index)?, Token::Semicolon) {
+    return Err(String::from("expect ';' closing statement"));
+  }
+  consts.push((name.clone(), value));
+  return Ok(());
+}
+
+fn constant(consts: &Vec<(String, i32)>, name: &String) -> Option<i32> {
+  consts.iter().rev().find(|(constant, _)| constant == name).map(|(_, value)| *value)
+}
+
+// an array declared in the function. dims are the dimensions after the first,
+// which give the row lengths for indexing (a plain int[n] has none), and size
+// is the operand holding its number of elements, for bounds checks and for
+// passing it on. ir is its name in the generated code, which differs from
+// name when an earlier array of that name has gone out of scope, as the
+// backends give each array in a function a single size.
+#[derive(Clone)]
+struct Array {
+  name: String,
+  ir: String,
+  dims: Vec<i32>,
+  size: String,
+  in_scope: bool,
+}
+
+fn array<'a>(arr_table: &'a Vec<Array>, name: &String) -> Option<&'a Array> {
+  arr_table.iter().rev().find(|arr| arr.in_scope && &arr.name == name)
+}
+
+// what an element given the wrong number of indices is told.
+fn index_count_error(ident: &String, dims: &Vec<i32>) -> String {
+  if dims.is_empty() {
+    return format!("{ident} takes a single index");
+  }
+  return format!("{ident} needs {} indices, one per dimension", dims.len() + 1);
+}
+
+// the `[4][4]` following the first dimension of an array type. these have to
+// be constant, the row lengths are baked into every index computation.
+fn parse_dimensions(tokens: &Vec<Token>, index: &mut usize, consts: &Vec<(String, i32)>) -> Result<Vec<i32>, String> {
+  let mut dims: Vec<i32> = vec![];
+  while matches!(peek(tokens, *index), Some(Token::LeftBracket)) {
+    *index += 1;
+    let dim = parse_constant(tokens, index, consts)?;
+    if dim <= 0 {
+      return Err(String::from("array dimensions need a positive constant size"));
+    }
+    if !matches!(next_result(tokens, index)?, Token::RightBracket) {
+      return Err(String::from("expect ']' closing array size declaration"));
+    }
+    dims.push(dim);
+  }
+  return Ok(dims);
+}
+
+// an array parameter type as it is written, int[] or int[][4].
+fn array_type(dims: &Vec<i32>) -> String {
+  let mut text = String::from("int[]");
+  for dim in dims {
+    text += &format!("[{dim}]");
+  }
+  return text;
+}
+
+// a constant expression: numbers, constants, + - * / % and parentheses,
+// evaluated with the usual precedence.
+fn parse_constant(tokens: &Vec<Token>, index: &mut usize, consts: &Vec<(String, i32)>) -> Result<i32, String> {
+  let mut value = constant_product(tokens, index, consts)?;
+  loop {
+    let result = match peek_result(tokens, *index)? {
+      Token::Plus => {
+        *index += 1;
+        value.checked_add(constant_product(tokens, index, consts)?)
+      }
+      Token::Subtract => {
+        *index += 1;
+        value.checked_sub(constant_product(tokens, index, consts)?)
+      }
+      _ => {
+        return Ok(value);
+      }
+    };
+    value = result.ok_or(String::from("constant expression overflows"))?;
+  }
+}
+
+fn constant_product(tokens: &Vec<Token>, index: &mut usize, consts: &Vec<(String, i32)>) -> Result<i32, String> {
+  let mut value = constant_factor(tokens, index, consts)?;
+  loop {
+    let op = match peek_result(tokens, *index)? {
+      op @ (Token::Multiply | Token::Divide | Token::Modulus) => op,
+      _ => {
+        return Ok(value);
+      }
+    };
+    *index += 1;
+    let factor = constant_factor(tokens, index, consts)?;
+    if factor == 0 && !matches!(op, Token::Multiply) {
+      return Err(String::from("division by zero in constant expression"));
+    }
+    value = match op {
+      Token::Multiply => value.checked_mul(factor),
+      Token::Divide => value.checked_div(factor),
+      _ => value.checked_rem(factor),
+    }.ok_or(String::from("constant expression overflows"))?;
+  }
+}
+
+fn constant_factor(tokens: &Vec<Token>, index: &mut usize, consts: &Vec<(String, i32)>) -> Result<i32, String> {
+  match next_result(tokens, index)? {
+    Token::Num(num) => {
+      return Ok(*num);
+    }
+    Token::Ident(name) => match constant(consts, name) {
+      Some(value) => {
+        return Ok(value);
+      }
+      None => {
+        return Err(format!("{name} is not a constant"));
+      }
+    },
+    Token::Subtract => {
+      let value = constant_factor(tokens, index, consts)?;
+      return value.checked_neg().ok_or(String::from("constant expression overflows"));
+    }
+    Token::LeftParen => {
+      let value = parse_constant(tokens, index, consts)?;
+      if !matches!(next_result(tokens, index)?, Token::RightParen) {
+        return Err(String::from("expected ')'"));
+      }
+      return Ok(value);
+    }
+    _ => {
+      return Err(String::from("expected a constant expression"));
+    }
+  }
+}
+
 fn in_table(table: &Vec<String>, symbol: &String) -> bool {
   for s in table{
     if s.eq(symbol) {
@@ -778,6 +1470,12 @@ fn in_table(table: &Vec<String>, symbol: &String) -> bool {
   false
 }
 
+// the parameters of a declared function in order: None for an int, the
+// shape of the array for an array.
+fn signature<'a>(func_table: &'a Vec<(String, Vec<Option<Vec<i32>>>)>, name: &String) -> Option<&'a Vec<Option<Vec<i32>>>> {
+  func_table.iter().find(|(f, _)| f == name).map(|(_, arrays)| arrays)
+}
+
 // parse function such as:
 // func main(int a, int b) {
 //    # ... statements here...
@@ -785,10 +1483,11 @@ fn in_table(table: &Vec<String>, symbol: &String) -> bool {
 // }
 // a loop is d  one to handle statements.
 
-fn parse_function(tokens: &Vec<Token>, index: &mut usize, func_table: &mut Vec<String>) -> Result<Option<String>, String> {
+fn parse_function(tokens: &Vec<Token>, index: &mut usize, func_table: &mut Vec<(String, Vec<Option<Vec<i32>>>)>, globals: &Vec<String>, global_arrays: &Vec<Array>, global_consts: &Vec<(String, i32)>) -> Result<Option<String>, String> {
   
   let mut symbol_table: Vec<String> = vec![];
-  let mut arr_table: Vec<String> = vec![];
+  let mut arr_table: Vec<Array> = global_arrays.clone();
+  let mut consts: Vec<(String, i32)> = global_consts.clone();
   match next(tokens, index) {
     None => {
         return Ok(None);
@@ -806,10 +1505,9 @@ fn parse_function(tokens: &Vec<Token>, index: &mut usize, func_table: &mut Vec<S
   _  => {return Err(String::from("functions must have a function identifier"));}
   };
 
-  if in_table(func_table, func_ident){
+  if signature(func_table, func_ident).is_some() {
     return Err(format!("Error: Function {func_ident} already declared"));
   }
-  func_table.push(func_ident.clone());
 
   if !matches!( next_result(tokens, index)?, Token::LeftParen) {
       return Err(String::from("expected '(' "));
@@ -817,6 +1515,7 @@ fn parse_function(tokens: &Vec<Token>, index: &mut usize, func_table: &mut Vec<S
 
   let mut code = format!("%func {} (", func_ident);
   let mut params: Vec<String> = vec![];
+  let mut arrays: Vec<Option<Vec<i32>>> = vec![];
 
   loop {
      match next_result(tokens, index)? {
@@ -824,17 +1523,39 @@ fn parse_function(tokens: &Vec<Token>, index: &mut usize, func_table: &mut Vec<S
           break;
       }
       Token::Int => {
+          let mut shape = None;
+          if matches!(peek_result(tokens, *index)?, Token::LeftBracket) {
+            *index += 1;
+            if !matches!(next_result(tokens, index)?, Token::RightBracket) {
+                return Err(String::from("expected ']' after 'int[' in array parameter"));
+            }
+            shape = Some(parse_dimensions(tokens, index, &consts)?);
+          }
           match next_result(tokens, index)? {
             Token::Ident(param) => {
-                if in_table(&symbol_table, param) {
+                if in_table(&symbol_table, param) || params.contains(param) {
                   return Err(format!("Found duplicate variable {param}"));
                 }
-                symbol_table.push(param.clone());
-                code += &format!("%int {}", param);
+                if in_table(globals, param) || array(global_arrays, param).is_some() {
+                  return Err(format!("parameter {param} has the name of a global"));
+                }
+                if constant(&consts, param).is_some() {
+                  return Err(format!("parameter {param} has the name of a constant"));
+                }
+                if let Some(dims) = &shape {
+                  // the caller passes the element count along in a hidden parameter.
+                  let size = format!("_size_{param}");
+                  arr_table.push(Array { name: param.clone(), ir: param.clone(), dims: dims.clone(), size: size.clone(), in_scope: true });
+                  code += &format!("%int[] {param}, %int {size}");
+                } else {
+                  symbol_table.push(param.clone());
+                  code += &format!("%int {}", param);
+                }
                 params.push(param.clone());
+                arrays.push(shape);
                 match peek_result(tokens, *index)? {
                   Token::Comma => {
-                    code += &format!(", ");
+                    code += ", ";
                     *index += 1;
                   }
                   Token::RightParen => {}
@@ -854,15 +1575,16 @@ fn parse_function(tokens: &Vec<Token>, index: &mut usize, func_table: &mut Vec<S
       }
      }
   }
-  code += &format!(")\n");
-
+  code += ")\n";
+  // declared before the body so it can call itself
+  func_table.push((func_ident.clone(), arrays));
 
   if !matches!(next_result(tokens, index)?, Token::LeftCurly) {
       return Err(String::from("expected '{'"));
   }
 
   loop {
-      match parse_statement(tokens, index, &mut symbol_table, func_table, &mut arr_table, false, &mut 0)? {
+      match parse_statement(tokens, index, &mut symbol_table, func_table, &mut arr_table, globals, &mut consts, &mut vec![])? {
       None => {
           break;
       }
@@ -882,7 +1604,35 @@ fn parse_function(tokens: &Vec<Token>, index: &mut usize, func_table: &mut Vec<S
 }
 
 
-fn parse_statement(tokens: &Vec<Token>, index: &mut usize, symbol_table: &mut Vec<String>, func_table: &mut Vec<String>, arr_table: &mut Vec<String>, in_loop: bool, nesting_level: &mut i32) -> Result<Option<String>, String> {
+// loops and switches around the statement being parsed, innermost last.
+// break leaves the innermost of them, continue restarts the innermost loop.
+struct Breakable {
+  number: i32, // label number of the :endloop / :endswitch
+  is_loop: bool,
+  label: Option<String>, // `name:` in front of a loop, for break name; / continue name;
+}
+
+// names declared in a block go out of scope at its closing brace, which
+// restores the tables to the lengths they had at the opening one. arrays are
+// only hidden, so that a later one of the same name is given its own.
+fn leave_scope(scope: (usize, usize, usize), symbol_table: &mut Vec<String>, arr_table: &mut Vec<Array>, consts: &mut Vec<(String, i32)>) {
+  symbol_table.truncate(scope.0);
+  for arr in &mut arr_table[scope.1..] {
+    arr.in_scope = false;
+  }
+  consts.truncate(scope.2);
+}
+
+// a labelled loop finds its label already pushed, with no number yet (label
+// numbers start at 1), and claims that entry instead of pushing its own.
+fn enter_loop(breakables: &mut Vec<Breakable>, number: i32) {
+  match breakables.last_mut() {
+    Some(pending) if pending.number == 0 => pending.number = number,
+    _ => breakables.push(Breakable { number, is_loop: true, label: None }),
+  }
+}
+
+fn parse_statement(tokens: &Vec<Token>, index: &mut usize, symbol_table: &mut Vec<String>, func_table: &mut Vec<(String, Vec<Option<Vec<i32>>>)>, arr_table: &mut Vec<Array>, globals: &Vec<String>, consts: &mut Vec<(String, i32)>, breakables: &mut Vec<Breakable>) -> Result<Option<String>, String> {
   match peek(tokens, *index) {
     None => {
         return Ok(None);
@@ -896,10 +1646,25 @@ fn parse_statement(tokens: &Vec<Token>, index: &mut usize, symbol_table: &mut Ve
 
             Token::Break => {
                 let mut code: String = Default::default();
-                if !in_loop {
-                return Err(String::from("Tried to break but not in loop"));
-                }
                 *index += 1;
+                let target = match peek_result(tokens, *index)? {
+                  Token::Ident(name) => {
+                    *index += 1;
+                    match breakables.iter().rev().find(|b| b.label.as_ref() == Some(name)) {
+                      Some(Breakable { number, .. }) => format!(":endloop{number}"),
+                      None => {
+                        return Err(format!("break to {name}, which is not the label of an enclosing loop"));
+                      }
+                    }
+                  }
+                  _ => match breakables.last() {
+                    Some(Breakable { number, is_loop: true, .. }) => format!(":endloop{number}"),
+                    Some(Breakable { number, is_loop: false, .. }) => format!(":endswitch{number}"),
+                    None => {
+                      return Err(String::from("Tried to break but not in loop"));
+                    }
+                  },
+                };
                 match next_result(tokens, index)? {
 
                 Token::Semicolon => {}
@@ -907,67 +1672,48 @@ fn parse_statement(tokens: &Vec<Token>, index: &mut usize, symbol_table: &mut Ve
                   return Err(String::from("expect ';' closing statement"));
                 }
                 }
-                code += &format!("%jmp :endloop{}\n", nesting_level);
+                code += &format!("%jmp {}\n", target);
                 ast_node = Some(code);
             }
 
+            Token::Const => {
+              parse_const(tokens, index, symbol_table, arr_table, globals, consts)?;
+              ast_node = Some(String::new());
+            }
+
             Token::Int => {
-              let code = parse_declaration(tokens, index, symbol_table, func_table, arr_table)?; //removed string new declaration
+              let code = parse_declaration(tokens, index, symbol_table, func_table, arr_table, globals, consts)?; //removed string new declaration
               ast_node = Some(code);
             }
 
-            Token::Ident(ident) => {
+            Token::Ident(name) if matches!(peek(tokens, *index + 1), Some(Token::Colon)) => {
+                if !matches!(peek(tokens, *index + 2), Some(Token::While) | Some(Token::For) | Some(Token::Do)) {
+                    return Err(format!("label {name} must be followed by a loop"));
+                }
+                if breakables.iter().any(|b| b.label.as_ref() == Some(name)) {
+                    return Err(format!("label {name} is already used by an enclosing loop"));
+                }
+                *index += 2;
+                breakables.push(Breakable { number: 0, is_loop: true, label: Some(name.clone()) });
+                ast_node = parse_statement(tokens, index, symbol_table, func_table, arr_table, globals, consts, breakables)?;
+            }
+
+            Token::Ident(_) | Token::Increment | Token::Decrement => {
                 let mut code = String::new();
-                *index += 1;
-                match peek_result(tokens, *index)?{
-                  Token::LeftBracket => {
-                    *index += 1;
-                    let position = parse_expression(tokens, index, symbol_table, func_table, arr_table, &mut code)?;
-                    match next_result(tokens, index)?{
-                      Token::RightBracket =>{
-                        match next_result(tokens, index)? {
-                          Token::Assign =>{
-                            let expr = parse_expression(tokens, index, symbol_table, func_table, arr_table, &mut code)?;
-                            code += &format!("%mov [{} + {}], {}\n", ident, position, expr);
-                            match next_result(tokens, index)? { //after we have parsed the RHS expression we should close it off with a semi
-                              Token::Semicolon => {}
-                              _=> {
-                                return Err(String::from("expect ';' closing statement 0"));
-                              }
-                            }
-                          }
-                          _ => {
-                            return Err(String::from("expected '=' opperator"));
-                          }
-                        }
-                      }
-                      _ => {
-                        return Err(String::from("expected ']' "))
-                      }
-                    }
-                  }
-                  Token::Assign => {
-                    *index += 1;
-                    let exp = parse_expression(tokens, index, symbol_table, func_table, arr_table, &mut code)?;
-                    code += &format!("%mov {}, {}\n", ident, exp);
-                    match next_result(tokens, index)? {
-                      Token::Semicolon => {}
-                      _ => {
-                          return Err(String::from("expect ';' closing statement1"));
-                      }
-                    }
-                  }
-                    _ => {
-                    return Err(String::from("expect '[' or '='"));
-                  }
-                }
-              ast_node = Some(code);
-            }
-
-            Token::Return => {
+                parse_assignment(tokens, index, symbol_table, func_table, arr_table, globals, consts, &mut code)?;
+                match next_result(tokens, index)? {
+                  Token::Semicolon => {}
+                  _ => {
+                      return Err(String::from("expect ';' closing statement1"));
+                  }
+                }
+              ast_node = Some(code);
+            }
+
+            Token::Return => {
                 *index += 1;
                 let mut code = String::new();
-                let exp = parse_expression(tokens, index, symbol_table, func_table, arr_table, &mut code)?;
+                let exp = parse_expression(tokens, index, symbol_table, func_table, arr_table, globals, consts, &mut code)?;
                 code += &format!{"%ret {}\n", exp};
                 match next_result(tokens, index)? {
                     Token::Semicolon => {}
@@ -984,7 +1730,7 @@ fn parse_statement(tokens: &Vec<Token>, index: &mut usize, symbol_table: &mut Ve
                     return Err(String::from("expect '(' closing statement"));
                 }
                 let mut code: String = Default::default();
-                let exp = parse_expression(tokens, index, symbol_table, func_table, arr_table, &mut code)?;
+                let exp = parse_expression(tokens, index, symbol_table, func_table, arr_table, globals, consts, &mut code)?;
                 let dest = create_temp();
                 code += &format!("%int {dest}\n%mov {dest}, {exp}\n");
                 code += &format!("%out {}\n", dest);
@@ -1006,11 +1752,16 @@ fn parse_statement(tokens: &Vec<Token>, index: &mut usize, symbol_table: &mut Ve
                     return Err(String::from("expect '(' closing statement"));
                 }
                 let mut code: String = Default::default();
-                let term = parse_term(tokens, index, symbol_table, func_table, arr_table, &mut code)?;
+                let place = parse_place(tokens, index, symbol_table, func_table, arr_table, globals, consts, &mut code)?;
                 if !matches!(next_result(tokens, index)?, Token::RightParen) {
                     return Err(String::from("expect ')' closing statement"));
                 }
-                code += &format!("%input {}\n", term);
+                if place.starts_with('[') {
+                    let dest = create_temp();
+                    code += &format!("%int {dest}\n%input {dest}\n%mov {place}, {dest}\n");
+                } else {
+                    code += &format!("%input {}\n", place);
+                }
 
                 match next_result(tokens, index)? {
                     Token::Semicolon => {}
@@ -1023,17 +1774,31 @@ fn parse_statement(tokens: &Vec<Token>, index: &mut usize, symbol_table: &mut Ve
             
             Token::Continue => {
                 let mut code: String = Default::default();
-                if !in_loop {
-                    return Err(String::from("Tried to continue but not in loop"));
-                }
                 *index += 1;
+                let number = match peek_result(tokens, *index)? {
+                  Token::Ident(name) => {
+                    *index += 1;
+                    match breakables.iter().rev().find(|b| b.label.as_ref() == Some(name)) {
+                      Some(Breakable { number, .. }) => *number,
+                      None => {
+                        return Err(format!("continue to {name}, which is not the label of an enclosing loop"));
+                      }
+                    }
+                  }
+                  _ => match breakables.iter().rev().find(|b| b.is_loop) {
+                    Some(Breakable { number, .. }) => *number,
+                    None => {
+                      return Err(String::from("Tried to continue but not in loop"));
+                    }
+                  },
+                };
                 match next_result(tokens, index)? {
                   Token::Semicolon => {}
                   _ => {
                       return Err(String::from("expect ';' closing statement"));
                   }
                 }
-                code += &format!("%jmp :loopbegin{}\n", nesting_level);
+                code += &format!("%jmp :loopbegin{}\n", number);
                 ast_node = Some(code);
             }
 
@@ -1051,7 +1816,7 @@ fn parse_statement(tokens: &Vec<Token>, index: &mut usize, symbol_table: &mut Ve
                 let mut code = format!(":loopbegin{}\n", local_nesting_level); 
 
                 
-                parse_bool(tokens, index, symbol_table, func_table, arr_table, &mut code, &mut local_nesting_level, "while".to_string())?;
+                parse_bool(tokens, index, symbol_table, func_table, arr_table, globals, consts, &mut code, &mut local_nesting_level, "while".to_string())?;
 
                 match next_result(tokens, index)? {
                   Token::LeftCurly => {}
@@ -1059,8 +1824,10 @@ fn parse_statement(tokens: &Vec<Token>, index: &mut usize, symbol_table: &mut Ve
                       return Err(String::from("expected '{' operator : 'parse statement while_loop block'"));
                   }
                 }
+                enter_loop(breakables, local_nesting_level);
+                let scope = (symbol_table.len(), arr_table.len(), consts.len());
                 loop { //if statement is 'while bool {' search for statements
-                  match parse_statement(tokens, index, symbol_table, func_table, arr_table, true, &mut local_nesting_level)? {
+                  match parse_statement(tokens, index, symbol_table, func_table, arr_table, globals, consts, breakables)? {
                     None => {
                       break;
                     }
@@ -1069,6 +1836,8 @@ fn parse_statement(tokens: &Vec<Token>, index: &mut usize, symbol_table: &mut Ve
                     }
                   }
                 }
+                breakables.pop();
+                leave_scope(scope, symbol_table, arr_table, consts);
 
                 code += &format!("%jmp :loopbegin{}\n", local_nesting_level);
                 code += &format!(":endloop{}\n", local_nesting_level);
@@ -1082,24 +1851,130 @@ fn parse_statement(tokens: &Vec<Token>, index: &mut usize, symbol_table: &mut Ve
                 ast_node = Some(code);
             }
 
-            Token::If => {
-                *index += 1; //consumed if keyword -> validate if statement
-                let mut ifNumber = create_num();
-                let mut code: String = Default::default();
-                // Implement bool in phase 4
-                parse_bool(tokens, index, symbol_table, func_table, arr_table, &mut code, &mut ifNumber, "if".to_string())?;
-                code += &format!("%jmp :else{}\n", ifNumber);
-                code += &format!(":iftrue{}\n", ifNumber);
+            Token::Switch => {
+                // switch (expr) { case 1: ... default: ... } with C fallthrough. the case
+                // bodies are laid out in order under :case{n}_{k}, :default{n} and
+                // :endswitch{n}; dense case values dispatch through one %jump_table on
+                // expr - lowest value, sparse ones through a chain of %eq tests.
+                *index += 1;
+                let number = create_num();
+                if !matches!(next_result(tokens, index)?, Token::LeftParen) {
+                    return Err(String::from("expected '(' after 'switch'"));
+                }
+                let mut code = String::new();
+                let value = parse_expression(tokens, index, symbol_table, func_table, arr_table, globals, consts, &mut code)?;
+                if !matches!(next_result(tokens, index)?, Token::RightParen) {
+                    return Err(String::from("expected ')' closing switch value"));
+                }
+                if !matches!(next_result(tokens, index)?, Token::LeftCurly) {
+                    return Err(String::from("expected '{' operator : 'parse statement switch block'"));
+                }
+
+                let mut cases: Vec<i32> = vec![];
+                let mut has_default = false;
+                let mut body = String::new();
+                breakables.push(Breakable { number, is_loop: false, label: None });
+                let scope = (symbol_table.len(), arr_table.len(), consts.len());
+                loop {
+                  match peek_result(tokens, *index)? {
+                    Token::Case => {
+                      *index += 1;
+                      let case = match parse_constant(tokens, index, consts) {
+                        Ok(case) => case,
+                        Err(error) => {
+                          return Err(format!("case label: {error}"));
+                        }
+                      };
+                      if cases.contains(&case) {
+                        return Err(format!("duplicate case value {case} in switch"));
+                      }
+                      body += &format!(":case{}_{}\n", number, cases.len());
+                      cases.push(case);
+                    }
+                    Token::Default => {
+                      *index += 1;
+                      if has_default {
+                        return Err(String::from("more than one 'default' in switch"));
+                      }
+                      body += &format!(":default{}\n", number);
+                      has_default = true;
+                    }
+                    Token::RightCurly => {
+                      break;
+                    }
+                    _ => {
+                      if cases.is_empty() && !has_default {
+                        return Err(String::from("expected 'case' or 'default' in switch"));
+                      }
+                      match parse_statement(tokens, index, symbol_table, func_table, arr_table, globals, consts, breakables)? {
+                        None => {
+                          break;
+                        }
+                        Some(stmt) => {
+                          body += &stmt;
+                        }
+                      }
+                      continue;
+                    }
+                  }
+                  if !matches!(next_result(tokens, index)?, Token::Colon) {
+                      return Err(String::from("expected ':' after case label"));
+                  }
+                }
+                breakables.pop();
+                leave_scope(scope, symbol_table, arr_table, consts);
+                if !matches!(next_result(tokens, index)?, Token::RightCurly) {
+                    return Err(String::from("expected '}' operator"));
+                }
+
+                let otherwise = if has_default { format!(":default{number}") } else { format!(":endswitch{number}") };
+                let lowest = cases.iter().min().cloned().unwrap_or(0) as i64;
+                let highest = cases.iter().max().cloned().unwrap_or(0) as i64;
+                let span = highest - lowest + 1;
+                if cases.len() >= 3 && span <= 2 * cases.len() as i64 {
+                    let mut table: Vec<String> = vec![otherwise.clone(); span as usize];
+                    for (k, case) in cases.iter().enumerate() {
+                      table[(*case as i64 - lowest) as usize] = format!(":case{number}_{k}");
+                    }
+                    // values outside the cases go to otherwise before the subtraction,
+                    // which could overflow (and trap) for values far from lowest.
+                    let below = create_temp();
+                    let above = create_temp();
+                    code += &format!("%int {below}\n%lt {below}, {value}, {lowest}\n%branch_if {below}, {otherwise}\n");
+                    code += &format!("%int {above}\n%gt {above}, {value}, {highest}\n%branch_if {above}, {otherwise}\n");
+                    let offset = create_temp();
+                    code += &format!("%int {offset}\n%sub {offset}, {value}, {lowest}\n");
+                    code += &format!("%jump_table {offset}, {otherwise}, {}\n", table.join(", "));
+                } else {
+                    for (k, case) in cases.iter().enumerate() {
+                      let test = create_temp();
+                      code += &format!("%int {test}\n%eq {test}, {value}, {case}\n");
+                      code += &format!("%branch_if {test}, :case{number}_{k}\n");
+                    }
+                    code += &format!("%jmp {otherwise}\n");
+                }
+                code += &body;
+                code += &format!(":endswitch{}\n", number);
+                ast_node = Some(code);
+            }
+
+            Token::Do => {
+                // do { ... } while (condition); runs the body before testing, and the
+                // test sits under :loopbegin so that continue goes straight to it.
+                *index += 1;
+                let mut local_nesting_level = create_num();
+                let mut code = format!(":dobody{}\n", local_nesting_level);
 
                 match next_result(tokens, index)? {
                   Token::LeftCurly => {}
                   _ => {
-                      return Err(String::from("expected '{' operator1"));
+                      return Err(String::from("expected '{' operator : 'parse statement do_loop block'"));
                   }
                 }
-                loop { //we've consumed 'if bool {' now we can search for statements
-                  let mut local_nesting_level = *nesting_level;
-                  match parse_statement(tokens, index, symbol_table, func_table, arr_table, in_loop, &mut local_nesting_level)? {
+                enter_loop(breakables, local_nesting_level);
+                let scope = (symbol_table.len(), arr_table.len(), consts.len());
+                loop {
+                  match parse_statement(tokens, index, symbol_table, func_table, arr_table, globals, consts, breakables)? {
                     None => {
                       break;
                     }
@@ -1108,47 +1983,192 @@ fn parse_statement(tokens: &Vec<Token>, index: &mut usize, symbol_table: &mut Ve
                     }
                   }
                 }
+                breakables.pop();
+                leave_scope(scope, symbol_table, arr_table, consts);
                 match next_result(tokens, index)? {
                   Token::RightCurly => {}
                   _ => {
-                    return Err(String::from("expected '}' operator1"));
+                    return Err(String::from("expected '}' operator"));
                   }
                 }
-                code += &format!("%jmp :endif{}\n", ifNumber);
-                code += &format!(":else{}\n", ifNumber);
-                match peek_result(tokens, *index)? { //ensure statement closes THEN, peek ahead to see if end of input for if else
-                  Token::Else => {},
+
+                if !matches!(next_result(tokens, index)?, Token::While) {
+                    return Err(String::from("expected 'while' after do loop body"));
+                }
+                if !matches!(next_result(tokens, index)?, Token::LeftParen) {
+                    return Err(String::from("expected '(' after 'while'"));
+                }
+                code += &format!(":loopbegin{}\n", local_nesting_level);
+                parse_bool(tokens, index, symbol_table, func_table, arr_table, globals, consts, &mut code, &mut local_nesting_level, "while".to_string())?;
+                if !matches!(next_result(tokens, index)?, Token::RightParen) {
+                    return Err(String::from("expected ')' closing do loop condition"));
+                }
+                if !matches!(next_result(tokens, index)?, Token::Semicolon) {
+                    return Err(String::from("expect ';' closing statement"));
+                }
+                code += &format!("%jmp :dobody{}\n", local_nesting_level);
+                code += &format!(":endloop{}\n", local_nesting_level);
+                ast_node = Some(code);
+            }
+
+            Token::For => {
+                // for (init; condition; step) { ... } puts the step under :loopbegin so
+                // that continue runs it, and enters the loop at the condition:
+                //   init, %jmp :loopcond, :loopbegin, step, :loopcond, condition, body
+                *index += 1;
+                let mut local_nesting_level = create_num();
+                if !matches!(next_result(tokens, index)?, Token::LeftParen) {
+                    return Err(String::from("expected '(' after 'for'"));
+                }
+
+                // a variable declared in the init clause is only in scope inside the loop.
+                let scope = (symbol_table.len(), arr_table.len(), consts.len());
+                let mut code = String::new();
+                match peek_result(tokens, *index)? {
+                  Token::Int => {
+                    code += &parse_declaration(tokens, index, symbol_table, func_table, arr_table, globals, consts)?;
+                  }
+                  Token::Semicolon => {
+                    *index += 1;
+                  }
                   _ => {
-                    code += &format!(":endif{}\n", ifNumber);
-                    ast_node = Some(code);
-                    return Ok(ast_node);
+                    parse_assignment(tokens, index, symbol_table, func_table, arr_table, globals, consts, &mut code)?;
+                    if !matches!(next_result(tokens, index)?, Token::Semicolon) {
+                        return Err(String::from("expect ';' after for loop initializer"));
+                    }
                   }
-                };
+                }
 
-                if!matches!(next_result(tokens, index)?, Token::LeftCurly) { // if 'else' keyword consumed, then repeat validation for 'if' body as seen above
-                    *index += 1;
-                } else {
-                    return Err(String::from("expected '{' operator3"));
+                let mut condition = String::new();
+                if !matches!(peek_result(tokens, *index)?, Token::Semicolon) {
+                    parse_bool(tokens, index, symbol_table, func_table, arr_table, globals, consts, &mut condition, &mut local_nesting_level, "while".to_string())?;
+                }
+                if !matches!(next_result(tokens, index)?, Token::Semicolon) {
+                    return Err(String::from("expect ';' after for loop condition"));
                 }
 
+                let mut step = String::new();
+                if !matches!(peek_result(tokens, *index)?, Token::RightParen) {
+                    parse_assignment(tokens, index, symbol_table, func_table, arr_table, globals, consts, &mut step)?;
+                }
+                if !matches!(next_result(tokens, index)?, Token::RightParen) {
+                    return Err(String::from("expect ')' closing for loop header"));
+                }
+
+                code += &format!("%jmp :loopcond{}\n", local_nesting_level);
+                code += &format!(":loopbegin{}\n", local_nesting_level);
+                code += &step;
+                code += &format!(":loopcond{}\n", local_nesting_level);
+                code += &condition;
+
+                match next_result(tokens, index)? {
+                  Token::LeftCurly => {}
+                  _ => {
+                      return Err(String::from("expected '{' operator : 'parse statement for_loop block'"));
+                  }
+                }
+                enter_loop(breakables, local_nesting_level);
                 loop {
-                  match parse_statement(tokens, index, symbol_table, func_table, arr_table, in_loop, nesting_level)? {
-                      None => {
+                  match parse_statement(tokens, index, symbol_table, func_table, arr_table, globals, consts, breakables)? {
+                    None => {
                       break;
-                      }
-                      Some(stmt) => {
-                        code += &stmt;
-                      }
+                    }
+                    Some(stmt) => {
+                      code += &stmt;
+                    }
                   }
                 }
-
+                breakables.pop();
                 match next_result(tokens, index)? {
-                    Token::RightCurly => {}
-                    _ => {
-                        return Err(String::from("expected '}' operator2"));
+                  Token::RightCurly => {}
+                  _ => {
+                    return Err(String::from("expected '}' operator"));
+                  }
+                }
+
+                code += &format!("%jmp :loopbegin{}\n", local_nesting_level);
+                code += &format!(":endloop{}\n", local_nesting_level);
+                leave_scope(scope, symbol_table, arr_table, consts);
+                ast_node = Some(code);
+            }
+
+            Token::If => {
+                // if / else if / else chains. every branch gets its own :iftrue and
+                // :else labels, and all of them jump to the :endif of the first one.
+                let mut code: String = Default::default();
+                let end_number = create_num();
+                let mut if_number = end_number;
+                loop {
+                    *index += 1; //consumed if keyword -> validate if statement
+                    parse_bool(tokens, index, symbol_table, func_table, arr_table, globals, consts, &mut code, &mut if_number, "if".to_string())?;
+                    code += &format!("%jmp :else{}\n", if_number);
+                    code += &format!(":iftrue{}\n", if_number);
+
+                    match next_result(tokens, index)? {
+                      Token::LeftCurly => {}
+                      _ => {
+                          return Err(String::from("expected '{' operator1"));
+                      }
                     }
+                    let scope = (symbol_table.len(), arr_table.len(), consts.len());
+                    loop { //we've consumed 'if bool {' now we can search for statements
+                      match parse_statement(tokens, index, symbol_table, func_table, arr_table, globals, consts, breakables)? {
+                        None => {
+                          break;
+                        }
+                        Some(stmt) => {
+                          code += &stmt;
+                        }
+                      }
+                    }
+                    leave_scope(scope, symbol_table, arr_table, consts);
+                    match next_result(tokens, index)? {
+                      Token::RightCurly => {}
+                      _ => {
+                        return Err(String::from("expected '}' operator1"));
+                      }
+                    }
+                    code += &format!("%jmp :endif{}\n", end_number);
+                    code += &format!(":else{}\n", if_number);
+
+                    if !matches!(peek(tokens, *index), Some(Token::Else)) {
+                        break;
+                    }
+                    *index += 1; //consumed else keyword
+                    match peek_result(tokens, *index)? {
+                      Token::If => {
+                        if_number = create_num();
+                        continue;
+                      }
+                      Token::LeftCurly => {
+                        *index += 1;
+                      }
+                      _ => {
+                        return Err(String::from("expected '{' or 'if' after 'else'"));
+                      }
+                    }
+
+                    let scope = (symbol_table.len(), arr_table.len(), consts.len());
+                    loop {
+                      match parse_statement(tokens, index, symbol_table, func_table, arr_table, globals, consts, breakables)? {
+                          None => {
+                          break;
+                          }
+                          Some(stmt) => {
+                            code += &stmt;
+                          }
+                      }
+                    }
+                    leave_scope(scope, symbol_table, arr_table, consts);
+                    match next_result(tokens, index)? {
+                        Token::RightCurly => {}
+                        _ => {
+                            return Err(String::from("expected '}' operator2"));
+                        }
+                    }
+                    break;
                 }
-                code += &format!(":endif{}\n", ifNumber);
+                code += &format!(":endif{}\n", end_number);
                 ast_node = Some(code);
             }
 
@@ -1165,81 +2185,217 @@ fn parse_statement(tokens: &Vec<Token>, index: &mut usize, symbol_table: &mut Ve
 }
 
 
-fn parse_expression(tokens: &Vec<Token>, index: &mut usize, symbol_table: &mut Vec<String>, func_table: &mut Vec<String>, arr_table: &mut Vec<String>, code: &mut String) -> Result<String, String> {
-  let mut term = parse_term(tokens, index, symbol_table, func_table, arr_table, code)?;
+// an assignment without its closing ';', either "x = expr" or "x[expr] = expr".
+// x = e, x op= e, x++ and ++x, or the same on an array element, without the ';'.
+fn parse_assignment(tokens: &Vec<Token>, index: &mut usize, symbol_table: &mut Vec<String>, func_table: &mut Vec<(String, Vec<Option<Vec<i32>>>)>, arr_table: &mut Vec<Array>, globals: &Vec<String>, consts: &mut Vec<(String, i32)>, code: &mut String) -> Result<(), String> {
+  if let Token::Increment | Token::Decrement = peek_result(tokens, *index)? {
+    let step = next_result(tokens, index)?;
+    let place = parse_place(tokens, index, symbol_table, func_table, arr_table, globals, consts, code)?;
+    let old = load_place(&place, code);
+    step_place(&place, &old, step, code);
+    return Ok(());
+  }
+  let place = parse_place(tokens, index, symbol_table, func_table, arr_table, globals, consts, code)?;
+  let op = match next_result(tokens, index)? {
+    Token::Assign => {
+      let exp = parse_expression(tokens, index, symbol_table, func_table, arr_table, globals, consts, code)?;
+      *code += &format!("%mov {}, {}\n", place, exp);
+      return Ok(());
+    }
+    step @ (Token::Increment | Token::Decrement) => {
+      let old = load_place(&place, code);
+      step_place(&place, &old, step, code);
+      return Ok(());
+    }
+    Token::PlusAssign => "add",
+    Token::SubtractAssign => "sub",
+    Token::MultiplyAssign => "mult",
+    Token::DivideAssign => "div",
+    Token::ModulusAssign => "mod",
+    _ => {
+      return Err(String::from("expected '=', an assignment operator, '++' or '--'"));
+    }
+  };
+  let exp = parse_expression(tokens, index, symbol_table, func_table, arr_table, globals, consts, code)?;
+  let old = load_place(&place, code);
   let dest = create_temp();
-  *code += &format!("%int {} \n", dest); //uncessary temp variable declaration?
+  *code += &format!("%int {dest}\n%{op} {dest}, {old}, {exp}\n%mov {place}, {dest}\n");
+  return Ok(());
+}
 
-  loop{
-    match peek_result(tokens, *index)? {
-        Token::Plus => {
-            *index += 1;
-            let exp = parse_expression(tokens, index, symbol_table, func_table, arr_table, code)?;
-            // I have the term, and the expression. Now I need to create a temp, and add the term and expression into it and return
-            *code += &format!("%add {}, {}, {}\n", dest, term, exp);
-        },
-        Token::Subtract => {
-            *index += 1;
-            let exp = parse_expression(tokens, index, symbol_table, func_table, arr_table, code)?;
-            *code += &format!("%sub {}, {}, {}\n", dest, term, exp);
-        },
-        Token::Multiply => {
-            *index += 1;
-            let next_term = parse_term(tokens, index, symbol_table, func_table, arr_table, code)?;
-            *code += &format!("%mult {}, {}, {}\n", dest, term, next_term);
-        },
-        Token::Divide => {
-            *index += 1;
-            let next_term = parse_term(tokens, index, symbol_table, func_table, arr_table, code)?;
-            *code += &format!("%div {}, {}, {}\n", dest, term, next_term);
-        },
-        Token::Modulus => {
-            *index += 1;
-            let next_term = parse_term(tokens, index, symbol_table, func_table, arr_table, code)?;
-            *code += &format!("%mod {}, {}, {}\n", dest, term, next_term);
+// the target of an assignment as an operand, `x` or `[x + i]`. an element's
+// index is evaluated here, once, so that a[i] += e reads and writes the same slot.
+fn parse_place(tokens: &Vec<Token>, index: &mut usize, symbol_table: &mut Vec<String>, func_table: &mut Vec<(String, Vec<Option<Vec<i32>>>)>, arr_table: &mut Vec<Array>, globals: &Vec<String>, consts: &mut Vec<(String, i32)>, code: &mut String) -> Result<String, String> {
+  let ident = match next_result(tokens, index)? {
+    Token::Ident(ident) => ident,
+    _ => {
+      return Err(String::from("expected identifier"));
+    }
+  };
+  if constant(consts, ident).is_some() {
+    return Err(format!("cannot assign to constant {ident}"));
+  }
+  if !matches!(peek_result(tokens, *index)?, Token::LeftBracket) {
+    return scalar_place(ident, symbol_table, arr_table, globals);
+  }
+  return parse_index(tokens, index, ident, symbol_table, func_table, arr_table, globals, consts, code);
+}
+
+// `[i][j]...` after the name of an array, flattened row-major into a single
+// index. an element takes exactly one index per dimension of the array, and
+// the flat index is checked against the array's size at runtime. gives the
+// element as an operand, `[a + i]`.
+fn parse_index(tokens: &Vec<Token>, index: &mut usize, ident: &String, symbol_table: &mut Vec<String>, func_table: &mut Vec<(String, Vec<Option<Vec<i32>>>)>, arr_table: &mut Vec<Array>, globals: &Vec<String>, consts: &mut Vec<(String, i32)>, code: &mut String) -> Result<String, String> {
+  let (ir, dims, size) = match array(arr_table, ident) {
+    Some(arr) => (arr.ir.clone(), arr.dims.clone(), arr.size.clone()),
+    None => {
+      return Err(format!("Array {ident} not initialized"));
+    }
+  };
+  let line = token_line(*index);
+  // the number of rows is only known for a constant size.
+  let rows = size.parse::<i32>().ok().map(|size| size / dims.iter().product::<i32>());
+  let mut position = String::new();
+  for k in 0..=dims.len() {
+    if !matches!(peek_result(tokens, *index)?, Token::LeftBracket) {
+      return Err(index_count_error(ident, &dims));
+    }
+    *index += 1;
+    // constant expressions are folded so that they can be checked here.
+    let start = *index;
+    let exp = match parse_constant(tokens, index, consts) {
+      Ok(value) if matches!(peek(tokens, *index), Some(Token::RightBracket)) => value.to_string(),
+      _ => {
+        *index = start;
+        parse_expression(tokens, index, symbol_table, func_table, arr_table, globals, consts, code)?
+      }
+    };
+    if !matches!(next_result(tokens, index)?, Token::RightBracket) {
+      return Err(String::from("expected ']' "));
+    }
+    // a constant index has to be in range for its dimension.
+    let bound = if k == 0 { rows } else { Some(dims[k - 1]) };
+    if let (Ok(value), Some(bound)) = (exp.parse::<i32>(), bound) {
+      if value < 0 || value >= bound {
+        if dims.is_empty() {
+          return Err(ir::bounds_error(ident, value, bound, line as u32));
+        }
+        return Err(format!("line {line}: index {value} out of bounds for dimension {} of array {ident} of size {bound}", k + 1));
+      }
+    }
+    if k == 0 {
+      position = exp;
+    } else {
+      let row = create_temp();
+      let sum = create_temp();
+      *code += &format!("%int {row}\n%mult {row}, {position}, {}\n%int {sum}\n%add {sum}, {row}, {exp}\n", dims[k - 1]);
+      position = sum;
+    }
+  }
+  if matches!(peek(tokens, *index), Some(Token::LeftBracket)) {
+    return Err(index_count_error(ident, &dims));
+  }
+  *code += &format!("%check {position}, {size}, {ident}, {line}\n");
+  return Ok(format!("[{ir} + {position}]"));
+}
+
+// a global scalar lives in memory as a one-element array. anything else has
+// to be a local or parameter that is still in scope.
+fn scalar_place(ident: &String, symbol_table: &Vec<String>, arr_table: &Vec<Array>, globals: &Vec<String>) -> Result<String, String> {
+  if in_table(globals, ident) {
+    return Ok(format!("[{} + 0]", ident));
+  }
+  if in_table(symbol_table, ident) {
+    return Ok(ident.clone());
+  }
+  if array(arr_table, ident).is_some() {
+    return Err(format!("{ident} is an array and needs an index"));
+  }
+  return Err(format!("Variable {ident} not declared"));
+}
+
+fn load_place(place: &String, code: &mut String) -> String {
+  let dest = create_temp();
+  *code += &format!("%int {dest}\n%mov {dest}, {place}\n");
+  return dest;
+}
+
+// stores old + 1 or old - 1 back into `place` and returns the new value.
+fn step_place(place: &String, old: &String, step: &Token, code: &mut String) -> String {
+  let op = if matches!(step, Token::Increment) { "add" } else { "sub" };
+  let dest = create_temp();
+  *code += &format!("%int {dest}\n%{op} {dest}, {old}, 1\n%mov {place}, {dest}\n");
+  return dest;
+}
+
+// sums and differences of products, grouped from the left like parse_constant
+// does, so that a - b - c is (a - b) - c whether or not it gets folded.
+fn parse_expression(tokens: &Vec<Token>, index: &mut usize, symbol_table: &mut Vec<String>, func_table: &mut Vec<(String, Vec<Option<Vec<i32>>>)>, arr_table: &mut Vec<Array>, globals: &Vec<String>, consts: &mut Vec<(String, i32)>, code: &mut String) -> Result<String, String> {
+  let mut term = parse_product(tokens, index, symbol_table, func_table, arr_table, globals, consts, code)?;
+  loop {
+    let op = match peek_result(tokens, *index)? {
+        Token::Plus => "add",
+        Token::Subtract => "sub",
+        _ => {
+            return Ok(term);
         }
+    };
+    *index += 1;
+    let product = parse_product(tokens, index, symbol_table, func_table, arr_table, globals, consts, code)?;
+    let dest = create_temp();
+    *code += &format!("%int {dest}\n%{op} {dest}, {term}, {product}\n");
+    term = dest;
+  }
+}
+
+fn parse_product(tokens: &Vec<Token>, index: &mut usize, symbol_table: &mut Vec<String>, func_table: &mut Vec<(String, Vec<Option<Vec<i32>>>)>, arr_table: &mut Vec<Array>, globals: &Vec<String>, consts: &mut Vec<(String, i32)>, code: &mut String) -> Result<String, String> {
+  let mut term = parse_term(tokens, index, symbol_table, func_table, arr_table, globals, consts, code)?;
+  loop {
+    let op = match peek_result(tokens, *index)? {
+        Token::Multiply => "mult",
+        Token::Divide => "div",
+        Token::Modulus => "mod",
         _ => {
             return Ok(term);
         }
-      }
-      term = dest.clone();
-  };
+    };
+    *index += 1;
+    let next_term = parse_term(tokens, index, symbol_table, func_table, arr_table, globals, consts, code)?;
+    let dest = create_temp();
+    *code += &format!("%int {dest}\n%{op} {dest}, {term}, {next_term}\n");
+    term = dest;
+  }
 }
 
-fn parse_bool(tokens: &Vec<Token>, index: &mut usize, symbol_table: &mut Vec<String>, func_table: &mut Vec<String>, arr_table: &mut Vec<String>, code: &mut String, nesting_level: &mut i32, _stmt_type: String) -> Result<(), String> {
-  let expression = parse_expression(tokens, index, symbol_table, func_table, arr_table, code)?;
+fn parse_bool(tokens: &Vec<Token>, index: &mut usize, symbol_table: &mut Vec<String>, func_table: &mut Vec<(String, Vec<Option<Vec<i32>>>)>, arr_table: &mut Vec<Array>, globals: &Vec<String>, consts: &mut Vec<(String, i32)>, code: &mut String, nesting_level: &mut i32, _stmt_type: String) -> Result<(), String> {
+  let expression = parse_expression(tokens, index, symbol_table, func_table, arr_table, globals, consts, code)?;
   let dest = create_temp();
   *code += &format!("%int {} \n", dest); //uncessary temp variable declaration?
 
-  match peek_result(tokens, *index)? {
-    Token::Less => {
-      *code += &format!("%lt {}, {}, ", dest, expression);
-    }
-    Token::LessEqual => {
-      *code += &format!("%le {}, {}, ", dest, expression);
-    },
-    Token::Equality => {
-      *code += &format!("%eq {}, {}, ", dest, expression);
-    },
-    Token::NotEqual => {
-      *code += &format!("%neq {}, {}, ", dest, expression);
-    },
-    Token::GreaterEqual => {
-      *code += &format!("%ge {}, {}, ", dest, expression);
-    },
-    Token::Greater => {
-      *code += &format!("%gt {}, {}, ", dest, expression);
-    },
+  let op = match peek_result(tokens, *index)? {
+    Token::Less => "lt",
+    Token::LessEqual => "le",
+    Token::Equality => "eq",
+    Token::NotEqual => "neq",
+    Token::GreaterEqual => "ge",
+    Token::Greater => "gt",
 
+    // no comparison: the condition holds when the value isn't 0.
     _ => {
+      if _stmt_type == "while" {
+        *code += &format!("%branch_ifn {}, :endloop{}\n", expression, nesting_level);
+      } else {
+        *code += &format!("%branch_if {}, :iftrue{}\n", expression, nesting_level);
+      }
       return Ok(());
     }
 
   };
 
   *index += 1;
-  let term = parse_term(tokens, index, symbol_table, func_table, arr_table, code)?;
-  *code += &format!("{}\n", term);
+  // the term may load from memory, so it has to be emitted before the comparison.
+  let term = parse_term(tokens, index, symbol_table, func_table, arr_table, globals, consts, code)?;
+  *code += &format!("%{} {}, {}, {}\n", op, dest, expression, term);
   // *code += &format!("%branch_ifn {}, :endloop{}\n", dest, nesting_level);
 
   if _stmt_type == "while" {
@@ -1251,23 +2407,74 @@ fn parse_bool(tokens: &Vec<Token>, index: &mut usize, symbol_table: &mut Vec<Str
   return Ok(());
 }
 
-fn parse_declaration(tokens: &Vec<Token>, index: &mut usize, symbol_table: &mut Vec<String>, func_table: &mut Vec<String>, arr_table: &mut Vec<String>) -> Result<String, String> {
+fn parse_declaration(tokens: &Vec<Token>, index: &mut usize, symbol_table: &mut Vec<String>, func_table: &mut Vec<(String, Vec<Option<Vec<i32>>>)>, arr_table: &mut Vec<Array>, globals: &Vec<String>, consts: &mut Vec<(String, i32)>) -> Result<String, String> {
   match next_result(tokens, index)? {
         Token::Int => {
           let mut code: String = Default::default();
             match next_result(tokens, index)? {
                 Token::LeftBracket => {  // Array size is declared after 'int'
-                    let size = parse_term(tokens, index, symbol_table, func_table, arr_table, &mut code)?;  // Parses the size of the array
+                    // a constant expression gives a fixed size, anything else is a single term.
+                    let start = *index;
+                    let size = match parse_constant(tokens, index, consts) {
+                      Ok(size) if matches!(peek(tokens, *index), Some(Token::RightBracket)) => size.to_string(),
+                      _ => {
+                        *index = start;
+                        parse_term(tokens, index, symbol_table, func_table, arr_table, globals, consts, &mut code)?  // Parses the size of the array
+                      }
+                    };
                     if !matches!(next_result(tokens, index)?, Token::RightBracket) {
                         return Err(String::from("expect ']' closing array size declaration"));
                     }
+                    // int[n][4][4] holds n * 4 * 4 elements, stored row by row
+                    let dims = parse_dimensions(tokens, index, consts)?;
+                    let mut size = size;
+                    for dim in &dims {
+                      size = match size.parse::<i32>() {
+                        Ok(rows) => match rows.checked_mul(*dim) {
+                          Some(size) => size.to_string(),
+                          None => {
+                            return Err(String::from("array is too large"));
+                          }
+                        },
+                        Err(_) => {
+                          let total = create_temp();
+                          code += &format!("%int {total}\n%mult {total}, {size}, {dim}\n");
+                          total
+                        }
+                      };
+                    }
+                    // a variable size is copied, the array keeps it even if the variable changes
+                    if dims.is_empty() && !ir::is_constant(&size) {
+                      let copy = create_temp();
+                      code += &format!("%int {copy}\n%mov {copy}, {size}\n");
+                      size = copy;
+                    }
                     match next_result(tokens, index)? {
                         Token::Ident(name) =>{
-                            if in_table(&arr_table, name) {
+                            if in_table(globals, name) {
+                                return Err(format!("{name} is already declared as a global"));
+                            }
+                            if constant(consts, name).is_some() {
+                                return Err(format!("{name} is already declared as a constant"));
+                            }
+                            if array(arr_table, name).is_some() {
                                 return Err(format!("Found a duplicate array variable {name}"));
                             }
-                            code += &format!("%int[] {name}, {size} \n");
-                            arr_table.push(name.to_string());
+                            if in_table(symbol_table, name) {
+                                return Err(format!("Variable {name} already declared"));
+                            }
+                            if let Ok(length) = size.parse::<i32>() {
+                              if length <= 0 {
+                                return Err(format!("line {}: array {name} needs a positive size, not {length}", token_line(start)));
+                              }
+                            }
+                            let ir = if arr_table.iter().any(|arr| &arr.name == name) { format!("_scope{}_{name}", create_num()) } else { name.clone() };
+                            code += &format!("%int[] {ir}, {size} \n");
+                            arr_table.push(Array { name: name.to_string(), ir: ir.clone(), dims, size: size.clone(), in_scope: true });
+                            if matches!(peek_result(tokens, *index)?, Token::Assign) {
+                                *index += 1;
+                                parse_initialiser(tokens, index, name, &size, symbol_table, func_table, arr_table, globals, consts, &mut code)?;
+                            }
                         },
                         _ => {return Err(String::from("expected identifier for array name"));}
                     }
@@ -1285,12 +2492,22 @@ fn parse_declaration(tokens: &Vec<Token>, index: &mut usize, symbol_table: &mut
                 },
                 Token::Ident(name) => {  // Normal int variable declaration
                     if in_table(symbol_table, name){
-                        return Err(String::from("Variable already declared"));
+                        return Err(format!("Variable {name} already declared"));
+                    }
+                    if array(arr_table, name).is_some() {
+                        return Err(format!("{name} is already declared as an array"));
+                    }
+                    if in_table(globals, name) {
+                        return Err(format!("{name} is already declared as a global"));
+                    }
+                    if constant(consts, name).is_some() {
+                        return Err(format!("{name} is already declared as a constant"));
                     }
                     code += &format!("%int {name}\n");
+                    symbol_table.push(name.clone());
                     match next_result(tokens, index)? {
                       Token::Assign => {
-                        let exp = parse_expression(tokens, index, symbol_table, func_table, arr_table, &mut code)?;
+                        let exp = parse_expression(tokens, index, symbol_table, func_table, arr_table, globals, consts, &mut code)?;
                         code += &format!("%mov {name}, {exp}\n");
                         match next_result(tokens, index)?{
                           Token::Semicolon => {}
@@ -1314,35 +2531,69 @@ fn parse_declaration(tokens: &Vec<Token>, index: &mut usize, symbol_table: &mut
     }
 }
 
+// `= {e0, e1, ...}` after an array declaration, stored from index 0 up.
+// elements that aren't listed keep the zero the array starts with.
+fn parse_initialiser(tokens: &Vec<Token>, index: &mut usize, name: &String, size: &String, symbol_table: &mut Vec<String>, func_table: &mut Vec<(String, Vec<Option<Vec<i32>>>)>, arr_table: &mut Vec<Array>, globals: &Vec<String>, consts: &mut Vec<(String, i32)>, code: &mut String) -> Result<(), String> {
+  let size: i32 = match size.parse() {
+    Ok(size) => size,
+    Err(_) => return Err(format!("array {name} needs a constant size to have an initialiser")),
+  };
+  if !matches!(next_result(tokens, index)?, Token::LeftCurly) {
+    return Err(String::from("expected '{' to start the array initialiser"));
+  }
+  let mut count = 0;
+  loop {
+    if matches!(peek_result(tokens, *index)?, Token::RightCurly) {
+      *index += 1;
+      break;
+    }
+    if count == size {
+      return Err(format!("too many initialisers for {name}, it has {size} elements"));
+    }
+    let exp = parse_expression(tokens, index, symbol_table, func_table, arr_table, globals, consts, code)?;
+    *code += &format!("%mov [{} + {count}], {exp}\n", array(arr_table, name).unwrap().ir);
+    count += 1;
+    match next_result(tokens, index)? {
+      Token::Comma => {}
+      Token::RightCurly => break,
+      _ => return Err(String::from("expected ',' or '}' in array initialiser")),
+    }
+  }
+  return Ok(());
+}
 
 // a term is either a Number or an Identifier.
-fn parse_term(tokens: &Vec<Token>, index: &mut usize, symbol_table: &mut Vec<String>, func_table: &mut Vec<String>, arr_table: &mut Vec<String>, code: &mut String) -> Result<String, String> {
+fn parse_term(tokens: &Vec<Token>, index: &mut usize, symbol_table: &mut Vec<String>, func_table: &mut Vec<(String, Vec<Option<Vec<i32>>>)>, arr_table: &mut Vec<Array>, globals: &Vec<String>, consts: &mut Vec<(String, i32)>, code: &mut String) -> Result<String, String> {
   match next_result(tokens, index)? {
+    Token::Ident(ident) if constant(consts, ident).is_some() => {
+        if let Token::Increment | Token::Decrement = peek_result(tokens, *index)? {
+            return Err(format!("cannot assign to constant {ident}"));
+        }
+        return Ok(constant(consts, ident).unwrap().to_string());
+    }
     Token::Ident(ident) => {
         match peek_result(tokens, *index)? {
             Token::LeftBracket => {
-                *index += 1;
-                if !in_table(arr_table, ident) {
-                    return Err(format!("Array {ident} not initialized"));
+                let place = parse_index(tokens, index, ident, symbol_table, func_table, arr_table, globals, consts, code)?;
+                let dest = load_place(&place, code);
+                if let Token::Increment | Token::Decrement = peek_result(tokens, *index)? {
+                    let step = next_result(tokens, index)?;
+                    step_place(&place, &dest, step, code);
                 }
-                let size = parse_term(tokens, index, symbol_table, func_table, arr_table, code)?;  // Parses the size of the array
-                if !matches!(next_result(tokens, index)?, Token::RightBracket) {
-                    return Err(String::from("expect ']' closing array size declaration"));
-                }
-                let dest = create_temp();
-                *code += &format!("%int {dest}\n%mov {dest}, [{ident} + {size}]\n");
-                return Ok(format!("{}", dest));
+                return Ok(dest);
             }
 
             Token::LeftParen => {
                 *index += 1;
-                if !in_table(func_table, ident) {
-                    return Err(format!("Function {ident} not initialized"));
-                }
+                let arrays = match signature(func_table, ident) {
+                    Some(arrays) => arrays.clone(),
+                    None => return Err(format!("Function {ident} not initialized")),
+                };
                 let dest = create_temp();
                 *code += &format!("%int {dest}\n");
  
                 let mut args_code = String::new();
+                let mut count = 0;
                 loop {
                     match peek_result(tokens, *index)? {
                         Token::RightParen => {
@@ -1350,7 +2601,26 @@ fn parse_term(tokens: &Vec<Token>, index: &mut usize, symbol_table: &mut Vec<Str
                             break;
                         }
                         _ => {
-                            let exp = parse_expression(tokens, index, symbol_table, func_table, arr_table, code)?;
+                            // arrays are passed by reference, so the argument must name one
+                            let bare_array = match (peek_result(tokens, *index)?, peek(tokens, *index + 1)) {
+                                (Token::Ident(arr), Some(Token::Comma | Token::RightParen)) if array(arr_table, arr).is_some() => Some(arr.clone()),
+                                _ => None,
+                            };
+                            let exp = match (arrays.get(count), bare_array) {
+                                (Some(Some(dims)), Some(arr)) if array(arr_table, &arr).map(|a| &a.dims) == Some(dims) => {
+                                    *index += 1;
+                                    let arr = array(arr_table, &arr).unwrap();
+                                    format!("[{}], {}", arr.ir, arr.size)
+                                }
+                                (Some(Some(dims)), _) => {
+                                    return Err(format!("argument {} of {ident} must be an {} array", count + 1, array_type(dims)));
+                                }
+                                (_, Some(arr)) => {
+                                    return Err(format!("argument {} of {ident} must be an int, {arr} is an array", count + 1));
+                                }
+                                _ => parse_expression(tokens, index, symbol_table, func_table, arr_table, globals, consts, code)?,
+                            };
+                            count += 1;
                             args_code += &exp;
                             match peek_result(tokens, *index)? {
                                 Token::Comma => {
@@ -1365,19 +2635,37 @@ fn parse_term(tokens: &Vec<Token>, index: &mut usize, symbol_table: &mut Vec<Str
                         }
                     }
                 }
+                if count != arrays.len() {
+                    return Err(format!("{ident} takes {} arguments but {count} were given", arrays.len()));
+                }
                 *code += &format!("%call {}, {}({})\n", dest, ident, args_code);
                 return Ok(dest);
             }
+            Token::Increment | Token::Decrement => {
+                let step = next_result(tokens, index)?;
+                let place = scalar_place(ident, symbol_table, arr_table, globals)?;
+                let old = load_place(&place, code);
+                step_place(&place, &old, step, code);
+                return Ok(old);
+            }
+            _ if in_table(globals, ident) => {
+              return Ok(load_place(&scalar_place(ident, symbol_table, arr_table, globals)?, code));
+            }
             _ => {
-              return Ok(format!("{}", &ident));
+              return scalar_place(ident, symbol_table, arr_table, globals);
             }
         }
     }
     Token::Num(num) => {
         return Ok(format!("{}", num));
     }
+    step @ (Token::Increment | Token::Decrement) => {
+        let place = parse_place(tokens, index, symbol_table, func_table, arr_table, globals, consts, code)?;
+        let old = load_place(&place, code);
+        return Ok(step_place(&place, &old, step, code));
+    }
     Token::LeftParen => {
-        let exp = parse_expression(tokens, index, symbol_table, func_table, arr_table, code)?;
+        let exp = parse_expression(tokens, index, symbol_table, func_table, arr_table, globals, consts, code)?;
         if !matches!(next_result(tokens, index)?, Token::RightParen) {
             return Err(String::from("expected ')'"));
         }
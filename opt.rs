@@ -0,0 +1,186 @@
+// cleanup passes run after the structural optimisations (inlining, loop
+// transforms). they fold constants and copies inside basic blocks and throw
+// away code whose result is never read.
+use std::collections::{HashMap, HashSet};
+use crate::ir::{self, Function, Instr, Overflow};
+
+pub fn cleanup(function: &mut Function, overflow: Overflow) {
+  loop {
+    let mut changed = propagate(function, overflow);
+    changed |= remove_unreachable(function);
+    changed |= remove_dead_code(function, overflow);
+    changed |= remove_jumps(function);
+    if !changed {
+      break;
+    }
+  }
+}
+
+// forget `var` and every copy that refers to it.
+fn kill(known: &mut HashMap<String, String>, var: &String) {
+  known.remove(var);
+  known.retain(|_, value| value != var);
+}
+
+fn lookup(known: &HashMap<String, String>, operand: &String) -> Option<String> {
+  return known.get(operand).cloned();
+}
+
+// block-local constant and copy propagation with constant folding.
+fn propagate(function: &mut Function, overflow: Overflow) -> bool {
+  let mut changed = false;
+  let mut known: HashMap<String, String> = HashMap::new();
+  let mut body: Vec<Instr> = vec![];
+
+  for mut instr in function.body.drain(..) {
+    if let Instr::Label(_) = instr {
+      known.clear();
+      body.push(instr);
+      continue;
+    }
+
+    // %out, %input, branch conditions and jump table indices must stay variables, only copies go there.
+    let constants_allowed = !matches!(instr, Instr::Out(_) | Instr::BranchIf(_, _) | Instr::BranchIfn(_, _) | Instr::JumpTable(_, _, _));
+    for operand in ir::uses_mut(&mut instr) {
+      if let Some(value) = lookup(&known, operand) {
+        if constants_allowed || !ir::is_constant(&value) {
+          *operand = value;
+          changed = true;
+        }
+      }
+    }
+
+    // Some(None) means the instruction folds away entirely.
+    let folded: Option<Option<Instr>> = match &instr {
+      Instr::Binary(op, x, y, z) if ir::is_constant(y) && ir::is_constant(z) => {
+        let value = ir::eval_binary(op, y.parse().unwrap(), z.parse().unwrap(), overflow);
+        value.map(|value| Some(Instr::Mov(x.clone(), value.to_string())))
+      }
+      Instr::BranchIf(x, label) | Instr::BranchIfn(x, label) => {
+        lookup(&known, x).filter(|value| ir::is_constant(value)).map(|value| {
+          let taken = (value != "0") == matches!(instr, Instr::BranchIf(_, _));
+          if taken { Some(Instr::Jmp(label.clone())) } else { None }
+        })
+      }
+      Instr::Check(i, size, _, _) if ir::is_constant(i) && ir::is_constant(size) => {
+        let (i, size): (i32, i32) = (i.parse().unwrap(), size.parse().unwrap());
+        if i >= 0 && i < size { Some(None) } else { None }
+      }
+      Instr::JumpTable(x, default, table) => {
+        lookup(&known, x).and_then(|value| value.parse::<i32>().ok()).map(|value| {
+          let label = usize::try_from(value).ok().and_then(|k| table.get(k)).unwrap_or(default);
+          Some(Instr::Jmp(label.clone()))
+        })
+      }
+      _ => None,
+    };
+    if let Some(folded) = folded {
+      changed = true;
+      match folded {
+        Some(folded) => instr = folded,
+        None => continue,
+      }
+    }
+
+    match &instr {
+      Instr::Int(x) => kill(&mut known, x),
+      Instr::Mov(x, y) => {
+        kill(&mut known, x);
+        if x != y {
+          known.insert(x.clone(), y.clone());
+        }
+      }
+      _ => {
+        if let Some(x) = ir::def(&instr) {
+          let x = x.clone();
+          kill(&mut known, &x);
+        }
+      }
+    }
+    body.push(instr);
+  }
+
+  function.body = body;
+  return changed;
+}
+
+// code after an unconditional jump or return is dead until the next label.
+fn remove_unreachable(function: &mut Function) -> bool {
+  let before = function.body.len();
+  let mut reachable = true;
+  function.body.retain(|instr| {
+    match instr {
+      Instr::Label(_) => reachable = true,
+      _ if !reachable => return false,
+      Instr::Jmp(_) | Instr::JumpTable(_, _, _) | Instr::Ret(_) => reachable = false,
+      _ => {}
+    }
+    true
+  });
+  return function.body.len() != before;
+}
+
+// drop movs and arithmetic whose destination is never read, then any
+// declarations and labels that nothing refers to anymore.
+fn remove_dead_code(function: &mut Function, overflow: Overflow) -> bool {
+  let before = function.body.len();
+  let mut read: HashSet<String> = HashSet::new();
+  let mut labels: HashSet<String> = HashSet::new();
+  for instr in &function.body {
+    for operand in ir::uses(instr) {
+      read.insert(operand.clone());
+    }
+    match instr {
+      Instr::Store(arr, _, _) | Instr::Load(_, arr, _) => { read.insert(arr.clone()); }
+      _ => {}
+    }
+    for label in ir::targets(instr) {
+      labels.insert(label.clone());
+    }
+  }
+
+  function.body.retain(|instr| {
+    match instr {
+      Instr::Mov(x, _) | Instr::Binary(_, x, _, _) => read.contains(x) || ir::can_trap(instr, overflow),
+      Instr::Label(label) => labels.contains(label),
+      _ => true,
+    }
+  });
+
+  let mut mentioned: HashSet<String> = function.params.iter().cloned().collect();
+  for instr in &function.body {
+    if let Some(x) = ir::def(instr) {
+      mentioned.insert(x.clone());
+    }
+  }
+  mentioned.extend(read);
+  function.body.retain(|instr| {
+    match instr {
+      Instr::Int(x) => mentioned.contains(x),
+      _ => true,
+    }
+  });
+
+  return function.body.len() != before;
+}
+
+// a jump to the label that immediately follows it does nothing.
+fn remove_jumps(function: &mut Function) -> bool {
+  let mut remove: Vec<usize> = vec![];
+  for (i, instr) in function.body.iter().enumerate() {
+    if let Instr::Jmp(label) = instr {
+      let mut j = i + 1;
+      while let Some(Instr::Label(next)) = function.body.get(j) {
+        if next == label {
+          remove.push(i);
+          break;
+        }
+        j += 1;
+      }
+    }
+  }
+  for i in remove.iter().rev() {
+    function.body.remove(*i);
+  }
+  return !remove.is_empty();
+}
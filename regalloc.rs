@@ -0,0 +1,171 @@
+// register allocation for the machine backends. liveness is solved
+// backwards over the CFG, each scalar gets one live interval over the
+// instruction numbering (from its first to its last live point), and linear
+// scan hands out `count` registers, spilling the interval that ends last when
+// they run out. backends pass callee-saved registers, so allocated values
+// survive calls without extra saving around them.
+use std::collections::{HashMap, HashSet};
+use crate::cfg;
+use crate::ir::{self, Function, Instr};
+
+#[derive(Debug, Clone)]
+pub struct Interval {
+  pub var: String,
+  pub start: usize, // instruction indices in the function body, inclusive
+  pub end: usize,
+}
+
+pub struct Allocation {
+  pub intervals: Vec<Interval>,
+  pub registers: HashMap<String, usize>, // variable -> register number
+  pub spilled: Vec<String>,
+}
+
+fn arrays(function: &Function) -> HashSet<&String> {
+  let mut arrays: HashSet<&String> = function.array_params.iter().collect();
+  for instr in &function.body {
+    if let Instr::IntArray(x, _) = instr {
+      arrays.insert(x);
+    }
+  }
+  return arrays;
+}
+
+// variables live on entry to and on exit from each block.
+pub fn liveness(function: &Function) -> (Vec<HashSet<String>>, Vec<HashSet<String>>) {
+  let cfg = cfg::build(function);
+  let arrays = arrays(function);
+  let scalar = |name: &String| !ir::is_constant(name) && !arrays.contains(name);
+
+  // per block: variables read before any write in it, and variables written in it.
+  let mut gen: Vec<HashSet<String>> = vec![HashSet::new(); cfg.blocks.len()];
+  let mut kill: Vec<HashSet<String>> = vec![HashSet::new(); cfg.blocks.len()];
+  for (b, block) in cfg.blocks.iter().enumerate() {
+    for instr in &function.body[block.start..block.end] {
+      for operand in ir::uses(instr) {
+        if scalar(operand) && !kill[b].contains(operand) {
+          gen[b].insert(operand.clone());
+        }
+      }
+      if let Some(x) = ir::def(instr) {
+        kill[b].insert(x.clone());
+      }
+    }
+  }
+
+  let mut live_in: Vec<HashSet<String>> = vec![HashSet::new(); cfg.blocks.len()];
+  let mut live_out: Vec<HashSet<String>> = vec![HashSet::new(); cfg.blocks.len()];
+  let mut changed = true;
+  while changed {
+    changed = false;
+    for b in (0..cfg.blocks.len()).rev() {
+      let mut out: HashSet<String> = HashSet::new();
+      for s in &cfg.blocks[b].succs {
+        out.extend(live_in[*s].iter().cloned());
+      }
+      let mut inside: HashSet<String> = out.iter().filter(|x| !kill[b].contains(*x)).cloned().collect();
+      inside.extend(gen[b].iter().cloned());
+      if inside != live_in[b] || out != live_out[b] {
+        live_in[b] = inside;
+        live_out[b] = out;
+        changed = true;
+      }
+    }
+  }
+  return (live_in, live_out);
+}
+
+pub fn intervals(function: &Function) -> Vec<Interval> {
+  let cfg = cfg::build(function);
+  let arrays = arrays(function);
+  let (live_in, live_out) = liveness(function);
+  let mut ranges: HashMap<String, (usize, usize)> = HashMap::new();
+  let mut extend = |var: &String, position: usize| {
+    let range = ranges.entry(var.clone()).or_insert((position, position));
+    range.0 = range.0.min(position);
+    range.1 = range.1.max(position);
+  };
+
+  // parameters arrive at the entry.
+  for param in function.params.iter().filter(|param| !arrays.contains(param)) {
+    extend(param, 0);
+  }
+  for (b, block) in cfg.blocks.iter().enumerate() {
+    if block.start == block.end {
+      continue;
+    }
+    for var in &live_in[b] {
+      extend(var, block.start);
+    }
+    for var in &live_out[b] {
+      extend(var, block.end - 1);
+    }
+    for position in block.start..block.end {
+      let instr = &function.body[position];
+      let mut names: Vec<&String> = ir::uses(instr);
+      names.extend(ir::def(instr));
+      for name in names {
+        if !ir::is_constant(name) && !arrays.contains(name) {
+          extend(name, position);
+        }
+      }
+    }
+  }
+
+  let mut intervals: Vec<Interval> = ranges.into_iter().map(|(var, (start, end))| Interval { var, start, end }).collect();
+  intervals.sort_by(|a, b| (a.start, a.end, &a.var).cmp(&(b.start, b.end, &b.var)));
+  return intervals;
+}
+
+pub fn allocate(function: &Function, count: usize) -> Allocation {
+  let intervals = intervals(function);
+  let mut registers: HashMap<String, usize> = HashMap::new();
+  let mut spilled: Vec<String> = vec![];
+  let mut free: Vec<usize> = (0..count).rev().collect();
+  let mut active: Vec<&Interval> = vec![]; // sorted by end
+
+  for interval in &intervals {
+    // intervals that ended before this one starts give their registers back.
+    while let Some(first) = active.first() {
+      if first.end >= interval.start {
+        break;
+      }
+      free.push(registers[&first.var]);
+      active.remove(0);
+    }
+
+    if let Some(register) = free.pop() {
+      registers.insert(interval.var.clone(), register);
+    } else {
+      match active.last() {
+        Some(last) if last.end > interval.end => {
+          let register = registers.remove(&last.var).unwrap();
+          spilled.push(last.var.clone());
+          active.pop();
+          registers.insert(interval.var.clone(), register);
+        }
+        _ => {
+          spilled.push(interval.var.clone());
+          continue;
+        }
+      }
+    }
+    let position = active.iter().position(|a| a.end > interval.end).unwrap_or(active.len());
+    active.insert(position, interval);
+  }
+  return Allocation { intervals, registers, spilled };
+}
+
+// one line per interval: where it lives and which register it got.
+pub fn dump(function: &Function, allocation: &Allocation, names: &[&str]) -> String {
+  let mut text = format!("{}:\n", function.name);
+  for interval in &allocation.intervals {
+    let place = match allocation.registers.get(&interval.var) {
+      Some(register) => names[*register].to_string(),
+      None => String::from("spilled"),
+    };
+    text += &format!("  {:<16} [{:>4}, {:>4}] {place}\n", interval.var, interval.start, interval.end);
+  }
+  text += &format!("  {} in registers, {} spilled\n", allocation.registers.len(), allocation.spilled.len());
+  return text;
+}
@@ -0,0 +1,548 @@
+// RISC-V backend. emits RV32IM assembly using the standard calling
+// convention: arguments in a0-a7 and then on the stack, result in a0, ra and
+// s0 saved by the callee, s0 as the frame pointer. variables live in the
+// s1-s11 registers regalloc.rs assigns them, or in the frame when spilled;
+// arrays always live in the frame, and globals in .data. array parameters are
+// addresses kept in the frame. %out and %input use the RARS/Venus
+// environment calls (1 print int, 11 print char, 5 read int, 4 print string,
+// 93 exit), so the output runs in those simulators as well as in rvsim.rs.
+use std::collections::{HashMap, HashSet};
+use crate::ir::{self, Function, Instr, Overflow};
+use crate::regalloc::{self, Allocation};
+
+const ARG_REGS: [&str; 8] = ["a0", "a1", "a2", "a3", "a4", "a5", "a6", "a7"];
+
+// callee-saved, so values in them survive calls.
+pub const ALLOCATABLE: [&str; 11] = ["s1", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11"];
+
+struct Frame {
+  slots: HashMap<String, i32>,  // variable -> offset from s0
+  arrays: HashMap<String, i32>, // array -> offset of element 0 from s0
+  registers: HashMap<String, &'static str>,
+  saves: Vec<(&'static str, i32)>, // callee-saved register -> where it is kept
+  size: i32,
+}
+
+// ra and the caller's s0 take the top 8 bytes of the frame, then the saved
+// registers.
+fn build_frame(function: &Function, allocation: &Allocation) -> Result<Frame, String> {
+  let mut frame = Frame { slots: HashMap::new(), arrays: HashMap::new(), registers: HashMap::new(), saves: vec![], size: 8 };
+  for (var, register) in &allocation.registers {
+    frame.registers.insert(var.clone(), ALLOCATABLE[*register]);
+  }
+  for (k, register) in ALLOCATABLE.iter().enumerate() {
+    if allocation.registers.values().any(|r| *r == k) {
+      frame.size += 4;
+      frame.saves.push((register, -frame.size));
+    }
+  }
+  let mut names: Vec<&String> = function.params.iter().collect();
+  for instr in &function.body {
+    match instr {
+      Instr::Int(x) => names.push(x),
+      Instr::IntArray(x, size) => {
+        let length: i32 = match size.parse() {
+          Ok(length) if length > 0 => length,
+          _ => return Err(format!("array {x} in {} needs a constant size for RISC-V", function.name)),
+        };
+        if !frame.arrays.contains_key(x) {
+          frame.size += 4 * length;
+          frame.arrays.insert(x.clone(), -frame.size);
+        }
+      }
+      _ => names.extend(ir::def(instr)),
+    }
+  }
+  for name in names {
+    if !frame.slots.contains_key(name) && !frame.arrays.contains_key(name) && !frame.registers.contains_key(name) {
+      frame.size += 4;
+      frame.slots.insert(name.clone(), -frame.size);
+    }
+  }
+  frame.size = (frame.size + 15) / 16 * 16;
+  return Ok(frame);
+}
+
+fn label(function: &Function, name: &String) -> String {
+  format!(".L{}_{}", function.name, &name[1..])
+}
+
+fn symbol(name: &String) -> String {
+  format!("tt_{name}")
+}
+
+fn global_symbol(name: &String) -> String {
+  format!("tt_global_{name}")
+}
+
+fn name_symbol(arr: &String) -> String {
+  format!("tt_name_{arr}")
+}
+
+fn fits_immediate(value: i32) -> bool {
+  (-2048..=2047).contains(&value)
+}
+
+struct Emitter<'a> {
+  function: &'a Function,
+  globals: &'a HashSet<String>,
+  overflow: Overflow,
+  frame: Frame,
+  code: String,
+  count: usize,
+}
+
+impl<'a> Emitter<'a> {
+  fn line(&mut self, text: &str) {
+    self.code += "    ";
+    self.code += text;
+    self.code += "\n";
+  }
+
+  fn fresh(&mut self) -> String {
+    self.count += 1;
+    format!(".L{}__{}", self.function.name, self.count)
+  }
+
+  // memory operand for s0 + offset; big frames go through t6.
+  fn frame_address(&mut self, offset: i32) -> String {
+    if fits_immediate(offset) {
+      return format!("{offset}(s0)");
+    }
+    self.line(&format!("li t6, {offset}"));
+    self.line("add t6, s0, t6");
+    return String::from("0(t6)");
+  }
+
+  fn slot(&mut self, name: &String) -> Result<String, String> {
+    match self.frame.slots.get(name) {
+      Some(offset) => {
+        let offset = *offset;
+        Ok(self.frame_address(offset))
+      }
+      None => Err(format!("unknown variable {name} in {}", self.function.name)),
+    }
+  }
+
+  fn load(&mut self, operand: &String, reg: &str) -> Result<(), String> {
+    if ir::is_constant(operand) {
+      self.line(&format!("li {reg}, {operand}"));
+    } else if let Some(register) = self.frame.registers.get(operand) {
+      let register = *register;
+      self.line(&format!("mv {reg}, {register}"));
+    } else {
+      let slot = self.slot(operand)?;
+      self.line(&format!("lw {reg}, {slot}"));
+    }
+    Ok(())
+  }
+
+  fn store(&mut self, reg: &str, dest: &String) -> Result<(), String> {
+    if let Some(register) = self.frame.registers.get(dest) {
+      let register = *register;
+      self.line(&format!("mv {register}, {reg}"));
+      return Ok(());
+    }
+    let slot = self.slot(dest)?;
+    self.line(&format!("sw {reg}, {slot}"));
+    Ok(())
+  }
+
+  // address of element 0 of arr in `reg`.
+  fn address(&mut self, arr: &String, reg: &str) -> Result<(), String> {
+    if let Some(base) = self.frame.arrays.get(arr) {
+      let base = *base;
+      if fits_immediate(base) {
+        self.line(&format!("addi {reg}, s0, {base}"));
+      } else {
+        self.line(&format!("li {reg}, {base}"));
+        self.line(&format!("add {reg}, s0, {reg}"));
+      }
+    } else if self.function.array_params.contains(arr) {
+      let slot = self.slot(arr)?;
+      self.line(&format!("lw {reg}, {slot}"));
+    } else if self.globals.contains(arr) {
+      self.line(&format!("la {reg}, {}", global_symbol(arr)));
+    } else {
+      return Err(format!("unknown array {arr} in {}", self.function.name));
+    }
+    Ok(())
+  }
+
+  // address of arr[index] in t2.
+  fn element(&mut self, arr: &String, index: &String) -> Result<String, String> {
+    self.load(index, "t2")?;
+    self.line("slli t2, t2, 2");
+    self.address(arr, "t3")?;
+    self.line("add t2, t3, t2");
+    Ok(String::from("0(t2)"))
+  }
+
+  // loads a call argument, which is the address of element 0 for an array.
+  fn argument(&mut self, arg: &String, reg: &str) -> Result<(), String> {
+    match ir::array_arg(arg) {
+      Some(arr) => self.address(&arr.to_string(), reg),
+      None => self.load(arg, reg),
+    }
+  }
+
+  fn binary(&mut self, op: &String, x: &String, y: &String, z: &String) -> Result<(), String> {
+    self.load(y, "t0")?;
+    self.load(z, "t1")?;
+    match op.as_str() {
+      "add" if self.overflow == Overflow::Wrap => self.line("add t0, t0, t1"),
+      "sub" if self.overflow == Overflow::Wrap => self.line("sub t0, t0, t1"),
+      "mult" if self.overflow == Overflow::Wrap => self.line("mul t0, t0, t1"),
+      "add" | "sub" | "mult" => {
+        // there are no flags, so compare the result with what the operands allow:
+        // a sum is below y exactly when z is negative, a difference when z is
+        // positive, and a product fits when its high word is the sign of the low one.
+        let fits = self.fresh();
+        match op.as_str() {
+          "add" => {
+            self.line("add t2, t0, t1");
+            self.line("slt t3, t2, t0");
+            self.line("slt t4, t1, zero");
+          }
+          "sub" => {
+            self.line("sub t2, t0, t1");
+            self.line("slt t3, t2, t0");
+            self.line("slt t4, zero, t1");
+          }
+          _ => {
+            self.line("mul t2, t0, t1");
+            self.line("mulh t3, t0, t1");
+            self.line("srai t4, t2, 31");
+          }
+        }
+        self.line(&format!("beq t3, t4, {fits}"));
+        if self.overflow == Overflow::Trap {
+          self.line("call tt_overflow");
+        } else {
+          // INT_MAX, plus one for a negative result: y's sign, or y * z's for mult.
+          if op == "mult" {
+            self.line("xor t3, t0, t1");
+            self.line("srli t3, t3, 31");
+          } else {
+            self.line("srli t3, t0, 31");
+          }
+          self.line("li t2, 2147483647");
+          self.line("add t2, t2, t3");
+        }
+        self.code += &format!("{fits}:\n");
+        self.line("mv t0, t2");
+      }
+      "div" | "mod" => {
+        // x / 0 does not trap on RISC-V, and INT_MIN / -1 wraps, which only suits --overflow=wrap.
+        let nonzero = self.fresh();
+        self.line(&format!("bnez t1, {nonzero}"));
+        self.line("call tt_division_by_zero");
+        self.code += &format!("{nonzero}:\n");
+        if op == "div" && self.overflow != Overflow::Wrap {
+          let plain = self.fresh();
+          let done = self.fresh();
+          self.line("li t2, -1");
+          self.line(&format!("bne t1, t2, {plain}"));
+          self.line("li t2, -2147483648");
+          self.line(&format!("bne t0, t2, {plain}"));
+          if self.overflow == Overflow::Trap {
+            self.line("call tt_overflow");
+          } else {
+            self.line("li t0, 2147483647");
+            self.line(&format!("j {done}"));
+          }
+          self.code += &format!("{plain}:\n");
+          self.line("div t0, t0, t1");
+          self.code += &format!("{done}:\n");
+        } else {
+          self.line(if op == "div" { "div t0, t0, t1" } else { "rem t0, t0, t1" });
+        }
+      }
+      "lt" => self.line("slt t0, t0, t1"),
+      "gt" => self.line("slt t0, t1, t0"),
+      "le" => {
+        self.line("slt t0, t1, t0");
+        self.line("xori t0, t0, 1");
+      }
+      "ge" => {
+        self.line("slt t0, t0, t1");
+        self.line("xori t0, t0, 1");
+      }
+      "eq" => {
+        self.line("sub t0, t0, t1");
+        self.line("seqz t0, t0");
+      }
+      _ => {
+        self.line("sub t0, t0, t1");
+        self.line("snez t0, t0");
+      }
+    }
+    self.store("t0", x)
+  }
+
+  fn call(&mut self, dest: &String, callee: &String, args: &Vec<String>) -> Result<(), String> {
+    let stacked = if args.len() > 8 { args.len() - 8 } else { 0 };
+    let space = ((4 * stacked).div_ceil(16) * 16) as i32;
+    if space > 0 {
+      self.line(&format!("addi sp, sp, -{space}"));
+      for (k, arg) in args.iter().skip(8).enumerate() {
+        self.argument(arg, "t0")?;
+        self.line(&format!("sw t0, {}(sp)", 4 * k));
+      }
+    }
+    for (arg, reg) in args.iter().zip(ARG_REGS) {
+      self.argument(arg, reg)?;
+    }
+    self.line(&format!("call {}", symbol(callee)));
+    if space > 0 {
+      self.line(&format!("addi sp, sp, {space}"));
+    }
+    self.store("a0", dest)
+  }
+
+  fn epilogue(&mut self) {
+    for (register, offset) in self.frame.saves.clone() {
+      self.line(&format!("lw {register}, {offset}(s0)"));
+    }
+    self.line("lw ra, -4(s0)");
+    self.line("mv sp, s0");
+    self.line("lw s0, -8(sp)");
+    self.line("ret");
+  }
+
+  fn instr(&mut self, instr: &Instr) -> Result<(), String> {
+    match instr {
+      Instr::Int(_) | Instr::IntArray(_, _) => {}
+      Instr::Mov(x, y) => {
+        self.load(y, "t0")?;
+        self.store("t0", x)?;
+      }
+      Instr::Store(arr, i, x) => {
+        let address = self.element(arr, i)?;
+        self.load(x, "t0")?;
+        self.line(&format!("sw t0, {address}"));
+      }
+      Instr::Load(x, arr, i) => {
+        let address = self.element(arr, i)?;
+        self.line(&format!("lw t0, {address}"));
+        self.store("t0", x)?;
+      }
+      Instr::Binary(op, x, y, z) => self.binary(op, x, y, z)?,
+      Instr::Out(x) => {
+        self.load(x, "a0")?;
+        self.line("li a7, 1");
+        self.line("ecall");
+        self.line("li a0, 10");
+        self.line("li a7, 11");
+        self.line("ecall");
+      }
+      Instr::Input(x) => {
+        self.line("li a7, 5");
+        self.line("ecall");
+        self.store("a0", x)?;
+      }
+      Instr::Call(x, f, args) => self.call(x, f, args)?,
+      Instr::Check(i, size, arr, line) => {
+        // the unsigned compare also catches negative indices.
+        let ok = self.fresh();
+        self.load(i, "a0")?;
+        self.load(size, "a1")?;
+        self.line("sltu t0, a0, a1");
+        self.line(&format!("bnez t0, {ok}"));
+        self.line(&format!("la a2, {}", name_symbol(arr)));
+        self.line(&format!("li a3, {line}"));
+        self.line("call tt_out_of_bounds");
+        self.code += &format!("{ok}:\n");
+      }
+      Instr::Ret(x) => {
+        self.load(x, "a0")?;
+        self.epilogue();
+      }
+      Instr::Jmp(l) => {
+        let target = label(self.function, l);
+        self.line(&format!("j {target}"));
+      }
+      Instr::BranchIf(x, l) | Instr::BranchIfn(x, l) => {
+        // conditional branches only reach +-4KiB, so they skip over a j instead.
+        let skip = if matches!(instr, Instr::BranchIf(_, _)) { "beqz" } else { "bnez" };
+        let target = label(self.function, l);
+        let over = self.fresh();
+        self.load(x, "t0")?;
+        self.line(&format!("{skip} t0, {over}"));
+        self.line(&format!("j {target}"));
+        self.code += &format!("{over}:\n");
+      }
+      Instr::JumpTable(x, default, table) => {
+        // an unsigned compare also sends negative indices to the default, and
+        // the table itself is a row of j instructions to jump into.
+        let inside = self.fresh();
+        let table_label = self.fresh();
+        self.load(x, "t0")?;
+        self.line(&format!("li t1, {}", table.len()));
+        self.line("sltu t1, t0, t1");
+        self.line(&format!("bnez t1, {inside}"));
+        self.line(&format!("j {}", label(self.function, default)));
+        self.code += &format!("{inside}:\n");
+        self.line(&format!("la t1, {table_label}"));
+        self.line("slli t0, t0, 2");
+        self.line("add t1, t1, t0");
+        self.line("jr t1");
+        self.code += &format!("{table_label}:\n");
+        for l in table {
+          self.line(&format!("j {}", label(self.function, l)));
+        }
+      }
+      Instr::Label(l) => {
+        self.code += &format!("{}:\n", label(self.function, l));
+      }
+      Instr::Phi(_, _) => return Err(String::from("phi instructions must be removed before code generation")),
+    }
+    Ok(())
+  }
+}
+
+fn generate_function(function: &Function, globals: &HashSet<String>, overflow: Overflow) -> Result<String, String> {
+  let allocation = regalloc::allocate(function, ALLOCATABLE.len());
+  let frame = build_frame(function, &allocation)?;
+  let size = frame.size;
+  let mut emitter = Emitter { function, globals, overflow, frame, code: String::new(), count: 0 };
+  let name = symbol(&function.name);
+  emitter.code += &format!("    .globl {name}\n{name}:\n");
+  emitter.line("mv t0, sp");
+  if fits_immediate(-size) {
+    emitter.line(&format!("addi sp, sp, -{size}"));
+  } else {
+    emitter.line(&format!("li t1, {size}"));
+    emitter.line("sub sp, sp, t1");
+  }
+  emitter.line("sw ra, -4(t0)");
+  emitter.line("sw s0, -8(t0)");
+  emitter.line("mv s0, t0");
+  // variables start out as 0, like in the VM.
+  let zero = emitter.fresh();
+  emitter.line("mv t0, sp");
+  emitter.line("addi t1, s0, -8");
+  emitter.code += &format!("{zero}:\n");
+  emitter.line("sw zero, 0(t0)");
+  emitter.line("addi t0, t0, 4");
+  emitter.line(&format!("bne t0, t1, {zero}"));
+  for (register, offset) in emitter.frame.saves.clone() {
+    emitter.line(&format!("sw {register}, {offset}(s0)"));
+  }
+  for register in ALLOCATABLE {
+    if emitter.frame.registers.iter().any(|(var, r)| *r == register && !function.params.contains(var)) {
+      emitter.line(&format!("li {register}, 0"));
+    }
+  }
+  for (k, param) in function.params.iter().enumerate() {
+    if k < 8 {
+      emitter.store(ARG_REGS[k], param)?;
+    } else {
+      emitter.line(&format!("lw t0, {}(s0)", 4 * (k - 8)));
+      emitter.store("t0", param)?;
+    }
+  }
+  for instr in &function.body {
+    emitter.instr(instr)?;
+  }
+  emitter.line("li a0, 0");
+  emitter.epilogue();
+  return Ok(emitter.code);
+}
+
+pub fn generate(program: &ir::Program) -> Result<String, String> {
+  let globals = ir::global_names(program);
+  let mut code = String::from("    .text
+    .globl _start
+_start:
+    call tt_main
+    li a0, 0
+    li a7, 93
+    ecall
+
+tt_division_by_zero:
+    la a0, tt_division_message
+    li a7, 4
+    ecall
+    li a0, 1
+    li a7, 93
+    ecall
+
+tt_overflow:
+    la a0, tt_overflow_message
+    li a7, 4
+    ecall
+    li a0, 1
+    li a7, 93
+    ecall
+
+tt_out_of_bounds:
+    mv t0, a0
+    mv t1, a1
+    mv t2, a2
+    la a0, tt_bounds_line
+    li a7, 4
+    ecall
+    mv a0, a3
+    li a7, 1
+    ecall
+    la a0, tt_bounds_index
+    li a7, 4
+    ecall
+    mv a0, t0
+    li a7, 1
+    ecall
+    la a0, tt_bounds_array
+    li a7, 4
+    ecall
+    mv a0, t2
+    ecall
+    la a0, tt_bounds_size
+    ecall
+    mv a0, t1
+    li a7, 1
+    ecall
+    li a0, 10
+    li a7, 11
+    ecall
+    li a0, 1
+    li a7, 93
+    ecall
+
+");
+  for function in &program.functions {
+    code += &generate_function(function, &globals, program.overflow)?;
+    code += "\n";
+  }
+  // globals go first so that they stay word aligned.
+  code += "    .data\n";
+  for global in &program.globals {
+    code += &format!("{}:\n    .space {}\n", global_symbol(&global.name), 4 * global.size);
+  }
+  code += "tt_division_message:
+    .string \"runtime error: division by zero\\n\"
+tt_overflow_message:
+    .string \"runtime error: integer overflow\\n\"
+tt_bounds_line:
+    .string \"runtime error: line \"
+tt_bounds_index:
+    .string \": index \"
+tt_bounds_array:
+    .string \" out of bounds for array \"
+tt_bounds_size:
+    .string \" of size \"
+";
+  // the array names failed checks report.
+  let mut names: Vec<&String> = vec![];
+  for function in &program.functions {
+    for instr in &function.body {
+      if let Instr::Check(_, _, arr, _) = instr {
+        if !names.contains(&arr) {
+          names.push(arr);
+          code += &format!("{}:\n    .string \"{arr}\"\n", name_symbol(arr));
+        }
+      }
+    }
+  }
+  return Ok(code);
+}
@@ -0,0 +1,427 @@
+// small RV32IM simulator for checking the RISC-V backend without a
+// toolchain. it assembles the subset of GNU assembler syntax that riscv.rs
+// writes, expanding pseudo-instructions (li, la, mv, j, call, ret, beqz,
+// bnez, seqz, snez) into base instructions, and runs the result with the
+// RARS environment calls for I/O.
+use std::collections::HashMap;
+
+const TEXT_BASE: u32 = 0x0040_0000;
+const DATA_BASE: u32 = 0x1001_0000;
+const STACK_TOP: u32 = 0x7fff_f000;
+const STACK_SIZE: u32 = 8 << 20;
+
+#[derive(Debug, Clone, Copy)]
+enum Alu {
+  Add,
+  Sub,
+  Mul,
+  Mulh,
+  Div,
+  Rem,
+  Slt,
+  Sltu,
+  Xor,
+}
+
+#[derive(Debug, Clone)]
+enum Inst {
+  Lui(usize, i32),
+  Addi(usize, usize, i32),
+  Xori(usize, usize, i32),
+  Slli(usize, usize, u32),
+  Srli(usize, usize, u32),
+  Srai(usize, usize, u32),
+  Sltiu(usize, usize, i32),
+  Op(Alu, usize, usize, usize),
+  Lw(usize, usize, i32),
+  Sw(usize, usize, i32), // value register, base register, offset
+  Beq(usize, usize, String),
+  Bne(usize, usize, String),
+  Jal(usize, String),
+  Jalr(usize, usize, i32),
+  Ecall,
+}
+
+fn register(name: &str) -> Result<usize, String> {
+  let abi = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4", "a5",
+    "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4", "t5", "t6",
+  ];
+  if name == "fp" {
+    return Ok(8);
+  }
+  if let Some(index) = abi.iter().position(|r| *r == name) {
+    return Ok(index);
+  }
+  match name.strip_prefix('x').and_then(|n| n.parse::<usize>().ok()) {
+    Some(index) if index < 32 => Ok(index),
+    _ => Err(format!("unknown register {name}")),
+  }
+}
+
+fn immediate(text: &str) -> Result<i32, String> {
+  let parsed = match text.strip_prefix("0x") {
+    Some(hex) => i64::from_str_radix(hex, 16),
+    None => text.parse::<i64>(),
+  };
+  match parsed {
+    Ok(value) if value >= i32::MIN as i64 && value <= u32::MAX as i64 => Ok(value as i32),
+    _ => Err(format!("bad immediate {text}")),
+  }
+}
+
+// "12(sp)" -> (12, sp)
+fn memory_operand(text: &str) -> Result<(i32, usize), String> {
+  let open = match text.find('(') {
+    Some(open) if text.ends_with(')') => open,
+    _ => return Err(format!("bad memory operand {text}")),
+  };
+  let offset = if open == 0 { 0 } else { immediate(&text[..open])? };
+  return Ok((offset, register(&text[open + 1..text.len() - 1])?));
+}
+
+// lui + addi pair for a 32-bit constant; addi sign-extends, so round the upper part.
+fn load_constant(rd: usize, value: i32) -> Vec<Inst> {
+  if (-2048..=2047).contains(&value) {
+    return vec![Inst::Addi(rd, 0, value)];
+  }
+  let upper = value.wrapping_add(0x800) >> 12;
+  let lower = value.wrapping_sub(upper << 12);
+  return vec![Inst::Lui(rd, upper), Inst::Addi(rd, rd, lower)];
+}
+
+fn unescape(text: &str) -> Result<Vec<u8>, String> {
+  let inner = match text.strip_prefix('"').and_then(|t| t.strip_suffix('"')) {
+    Some(inner) => inner,
+    None => return Err(format!("bad string {text}")),
+  };
+  let mut bytes: Vec<u8> = vec![];
+  let mut chars = inner.chars();
+  while let Some(c) = chars.next() {
+    if c != '\\' {
+      let mut buffer = [0; 4];
+      bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+      continue;
+    }
+    match chars.next() {
+      Some('n') => bytes.push(b'\n'),
+      Some('t') => bytes.push(b'\t'),
+      Some('0') => bytes.push(0),
+      Some('\\') => bytes.push(b'\\'),
+      Some('"') => bytes.push(b'"'),
+      other => return Err(format!("bad escape \\{}", other.unwrap_or(' '))),
+    }
+  }
+  bytes.push(0);
+  return Ok(bytes);
+}
+
+struct Program {
+  text: Vec<Inst>,
+  data: Vec<u8>,
+  symbols: HashMap<String, u32>,
+}
+
+fn assemble(source: &str) -> Result<Program, String> {
+  let mut program = Program { text: vec![], data: vec![], symbols: HashMap::new() };
+  let mut in_data = false;
+  // la needs addresses of data labels that may come later, so it is patched afterwards.
+  let mut addresses: Vec<(usize, usize, String)> = vec![];
+
+  for (number, line) in source.lines().enumerate() {
+    let line = match line.find('#') {
+      Some(comment) => &line[..comment],
+      None => line,
+    };
+    let mut line = line.trim();
+    while let Some(colon) = line.find(':') {
+      let name = &line[..colon];
+      if name.contains(' ') || name.contains('"') {
+        break;
+      }
+      let address = if in_data { DATA_BASE + program.data.len() as u32 } else { TEXT_BASE + 4 * program.text.len() as u32 };
+      program.symbols.insert(name.to_string(), address);
+      line = line[colon + 1..].trim();
+    }
+    if line.is_empty() {
+      continue;
+    }
+    let (mnemonic, rest) = match line.find(char::is_whitespace) {
+      Some(space) => (&line[..space], line[space..].trim()),
+      None => (line, ""),
+    };
+    let error = |message: String| format!("line {}: {message}", number + 1);
+    match mnemonic {
+      ".text" => in_data = false,
+      ".data" => in_data = true,
+      ".globl" | ".global" | ".align" | ".p2align" | ".type" | ".size" | ".section" => {}
+      ".string" | ".asciz" => program.data.extend(unescape(rest).map_err(error)?),
+      ".word" => {
+        for word in rest.split(',') {
+          program.data.extend_from_slice(&immediate(word.trim()).map_err(error)?.to_le_bytes());
+        }
+      }
+      ".space" | ".zero" => {
+        let length = immediate(rest).map_err(error)?;
+        if length < 0 {
+          return Err(error(format!("negative {mnemonic} length")));
+        }
+        program.data.resize(program.data.len() + length as usize, 0);
+      }
+      _ => {
+        let operands: Vec<&str> = if rest.is_empty() { vec![] } else { rest.split(',').map(|o| o.trim()).collect() };
+        let start = program.text.len();
+        let instrs = instruction(mnemonic, &operands).map_err(error)?;
+        if mnemonic == "la" {
+          addresses.push((start, register(operands[0]).map_err(error)?, operands[1].to_string()));
+        }
+        program.text.extend(instrs);
+      }
+    }
+  }
+
+  for (index, rd, name) in addresses {
+    let address = match program.symbols.get(&name) {
+      Some(address) => *address as i32,
+      None => return Err(format!("undefined symbol {name}")),
+    };
+    let upper = address.wrapping_add(0x800) >> 12;
+    program.text[index] = Inst::Lui(rd, upper);
+    program.text[index + 1] = Inst::Addi(rd, rd, address.wrapping_sub(upper << 12));
+  }
+  for inst in &program.text {
+    if let Inst::Beq(_, _, target) | Inst::Bne(_, _, target) | Inst::Jal(_, target) = inst {
+      if !program.symbols.contains_key(target) {
+        return Err(format!("undefined symbol {target}"));
+      }
+    }
+  }
+  return Ok(program);
+}
+
+fn instruction(mnemonic: &str, operands: &Vec<&str>) -> Result<Vec<Inst>, String> {
+  let expected = match mnemonic {
+    "ret" | "ecall" => 0,
+    "j" | "jr" | "call" => 1,
+    "li" | "la" | "mv" | "beqz" | "bnez" | "seqz" | "snez" | "lw" | "sw" | "lui" => 2,
+    _ => 3,
+  };
+  if operands.len() != expected {
+    return Err(format!("{mnemonic} takes {expected} operands"));
+  }
+  let reg = |k: usize| register(operands[k]);
+  let target = |k: usize| operands[k].to_string();
+  let instrs = match mnemonic {
+    "li" => load_constant(reg(0)?, immediate(operands[1])?),
+    "la" => vec![Inst::Lui(reg(0)?, 0), Inst::Addi(reg(0)?, reg(0)?, 0)],
+    "mv" => vec![Inst::Addi(reg(0)?, reg(1)?, 0)],
+    "j" => vec![Inst::Jal(0, target(0))],
+    "call" => vec![Inst::Jal(1, target(0))],
+    "jr" => vec![Inst::Jalr(0, reg(0)?, 0)],
+    "ret" => vec![Inst::Jalr(0, 1, 0)],
+    "beqz" => vec![Inst::Beq(reg(0)?, 0, target(1))],
+    "bnez" => vec![Inst::Bne(reg(0)?, 0, target(1))],
+    "seqz" => vec![Inst::Sltiu(reg(0)?, reg(1)?, 1)],
+    "snez" => vec![Inst::Op(Alu::Sltu, reg(0)?, 0, reg(1)?)],
+    "ecall" => vec![Inst::Ecall],
+    "lui" => vec![Inst::Lui(reg(0)?, immediate(operands[1])?)],
+    "lw" => {
+      let (offset, base) = memory_operand(operands[1])?;
+      vec![Inst::Lw(reg(0)?, base, offset)]
+    }
+    "sw" => {
+      let (offset, base) = memory_operand(operands[1])?;
+      vec![Inst::Sw(reg(0)?, base, offset)]
+    }
+    "addi" => vec![Inst::Addi(reg(0)?, reg(1)?, immediate(operands[2])?)],
+    "xori" => vec![Inst::Xori(reg(0)?, reg(1)?, immediate(operands[2])?)],
+    "slli" => vec![Inst::Slli(reg(0)?, reg(1)?, immediate(operands[2])? as u32 & 31)],
+    "srli" => vec![Inst::Srli(reg(0)?, reg(1)?, immediate(operands[2])? as u32 & 31)],
+    "srai" => vec![Inst::Srai(reg(0)?, reg(1)?, immediate(operands[2])? as u32 & 31)],
+    "sltiu" => vec![Inst::Sltiu(reg(0)?, reg(1)?, immediate(operands[2])?)],
+    "beq" => vec![Inst::Beq(reg(0)?, reg(1)?, target(2))],
+    "bne" => vec![Inst::Bne(reg(0)?, reg(1)?, target(2))],
+    "jalr" => vec![Inst::Jalr(reg(0)?, reg(1)?, immediate(operands[2])?)],
+    _ => {
+      let alu = match mnemonic {
+        "add" => Alu::Add,
+        "sub" => Alu::Sub,
+        "mul" => Alu::Mul,
+        "mulh" => Alu::Mulh,
+        "div" => Alu::Div,
+        "rem" => Alu::Rem,
+        "slt" => Alu::Slt,
+        "sltu" => Alu::Sltu,
+        "xor" => Alu::Xor,
+        _ => return Err(format!("unsupported instruction {mnemonic}")),
+      };
+      vec![Inst::Op(alu, reg(0)?, reg(1)?, reg(2)?)]
+    }
+  };
+  return Ok(instrs);
+}
+
+fn alu(op: Alu, a: i32, b: i32) -> i32 {
+  match op {
+    Alu::Add => a.wrapping_add(b),
+    Alu::Sub => a.wrapping_sub(b),
+    Alu::Mul => a.wrapping_mul(b),
+    Alu::Mulh => ((a as i64 * b as i64) >> 32) as i32,
+    // RV32M defines both corner cases instead of trapping.
+    Alu::Div => if b == 0 { -1 } else { a.wrapping_div(b) },
+    Alu::Rem => if b == 0 { a } else { a.wrapping_rem(b) },
+    Alu::Slt => (a < b) as i32,
+    Alu::Sltu => ((a as u32) < (b as u32)) as i32,
+    Alu::Xor => a ^ b,
+  }
+}
+
+struct Machine<'a> {
+  regs: [i32; 32],
+  data: Vec<u8>,
+  stack: Vec<u8>,
+  input: std::str::SplitWhitespace<'a>,
+  output: String,
+}
+
+impl<'a> Machine<'a> {
+  fn bytes(&mut self, address: u32, length: u32) -> Result<&mut [u8], String> {
+    let stack_base = STACK_TOP - STACK_SIZE;
+    if address >= DATA_BASE && address - DATA_BASE + length <= self.data.len() as u32 {
+      let start = (address - DATA_BASE) as usize;
+      return Ok(&mut self.data[start..start + length as usize]);
+    }
+    if address >= stack_base && address.checked_add(length).is_some_and(|end| end <= STACK_TOP) {
+      let start = (address - stack_base) as usize;
+      return Ok(&mut self.stack[start..start + length as usize]);
+    }
+    return Err(format!("memory access at 0x{address:08x} is out of range"));
+  }
+
+  fn load(&mut self, address: u32) -> Result<i32, String> {
+    if !address.is_multiple_of(4) {
+      return Err(format!("misaligned load at 0x{address:08x}"));
+    }
+    let bytes = self.bytes(address, 4)?;
+    return Ok(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+  }
+
+  fn store(&mut self, address: u32, value: i32) -> Result<(), String> {
+    if !address.is_multiple_of(4) {
+      return Err(format!("misaligned store at 0x{address:08x}"));
+    }
+    self.bytes(address, 4)?.copy_from_slice(&value.to_le_bytes());
+    Ok(())
+  }
+
+  fn set(&mut self, rd: usize, value: i32) {
+    if rd != 0 {
+      self.regs[rd] = value;
+    }
+  }
+
+  // returns the exit code once the program exits.
+  fn ecall(&mut self) -> Result<Option<i32>, String> {
+    match self.regs[17] {
+      1 => self.output += &self.regs[10].to_string(),
+      4 => {
+        let mut address = self.regs[10] as u32;
+        loop {
+          let byte = self.bytes(address, 1)?[0];
+          if byte == 0 {
+            break;
+          }
+          self.output.push(byte as char);
+          address += 1;
+        }
+      }
+      // 0 at the end of input; a word that is not an int stops the program
+      // with the same runtime error the other backends print.
+      5 => {
+        let value = match self.input.next() {
+          Some(word) => match word.parse::<i32>() {
+            Ok(value) => value,
+            Err(_) => {
+              self.output += &format!("runtime error: invalid input '{word}'\n");
+              return Ok(Some(1));
+            }
+          },
+          None => 0,
+        };
+        self.regs[10] = value;
+      }
+      10 => return Ok(Some(0)),
+      11 => self.output.push(self.regs[10] as u8 as char),
+      93 => return Ok(Some(self.regs[10])),
+      call => return Err(format!("unsupported environment call {call}")),
+    }
+    Ok(None)
+  }
+}
+
+// assemble and run a program, returning what it printed.
+pub fn run(source: &str, input: &str) -> Result<String, String> {
+  let program = assemble(source)?;
+  let entry = match program.symbols.get("_start").or(program.symbols.get("main")) {
+    Some(entry) => *entry,
+    None => TEXT_BASE,
+  };
+  let mut machine = Machine {
+    regs: [0; 32],
+    data: program.data.clone(),
+    stack: vec![0; STACK_SIZE as usize],
+    input: input.split_whitespace(),
+    output: String::new(),
+  };
+  machine.regs[2] = STACK_TOP as i32;
+
+  let mut pc = entry;
+  loop {
+    let index = pc.wrapping_sub(TEXT_BASE) / 4;
+    let inst = match program.text.get(index as usize) {
+      Some(inst) if pc % 4 == 0 => inst,
+      _ => return Err(format!("jump to 0x{pc:08x} outside the program")),
+    };
+    let mut next = pc + 4;
+    let regs = machine.regs;
+    match inst {
+      Inst::Lui(rd, upper) => machine.set(*rd, upper << 12),
+      Inst::Addi(rd, rs, imm) => machine.set(*rd, regs[*rs].wrapping_add(*imm)),
+      Inst::Xori(rd, rs, imm) => machine.set(*rd, regs[*rs] ^ imm),
+      Inst::Slli(rd, rs, shift) => machine.set(*rd, regs[*rs] << shift),
+      Inst::Srli(rd, rs, shift) => machine.set(*rd, ((regs[*rs] as u32) >> shift) as i32),
+      Inst::Srai(rd, rs, shift) => machine.set(*rd, regs[*rs] >> shift),
+      Inst::Sltiu(rd, rs, imm) => machine.set(*rd, ((regs[*rs] as u32) < (*imm as u32)) as i32),
+      Inst::Op(op, rd, rs1, rs2) => machine.set(*rd, alu(*op, regs[*rs1], regs[*rs2])),
+      Inst::Lw(rd, base, offset) => {
+        let value = machine.load(regs[*base].wrapping_add(*offset) as u32)?;
+        machine.set(*rd, value);
+      }
+      Inst::Sw(rs, base, offset) => machine.store(regs[*base].wrapping_add(*offset) as u32, regs[*rs])?,
+      Inst::Beq(rs1, rs2, target) => {
+        if regs[*rs1] == regs[*rs2] {
+          next = program.symbols[target];
+        }
+      }
+      Inst::Bne(rs1, rs2, target) => {
+        if regs[*rs1] != regs[*rs2] {
+          next = program.symbols[target];
+        }
+      }
+      Inst::Jal(rd, target) => {
+        machine.set(*rd, next as i32);
+        next = program.symbols[target];
+      }
+      Inst::Jalr(rd, rs, offset) => {
+        machine.set(*rd, next as i32);
+        next = (regs[*rs].wrapping_add(*offset) as u32) & !1;
+      }
+      Inst::Ecall => {
+        if machine.ecall()?.is_some() {
+          return Ok(machine.output);
+        }
+      }
+    }
+    pc = next;
+  }
+}
@@ -0,0 +1,270 @@
+// static single assignment form. construction places phis on the iterated
+// dominance frontier of each variable's definitions (semi-pruned: only names
+// that are live across blocks) and renames along the dominator tree, giving
+// versions written x.1, x.2, ... destruction turns each phi back into plain
+// %mov copies in the predecessors so the VM and backends can run it.
+use std::collections::{HashMap, HashSet};
+use crate::cfg::{self, Cfg};
+use crate::ir::{self, Function, Instr};
+
+// every block gets a label so phis can name their predecessors, and the entry
+// gets a fresh one so that nothing jumps back to it.
+fn label_blocks(function: &mut Function) {
+  function.body.insert(0, Instr::Label(format!(":entry{}", crate::create_num())));
+  let cfg = cfg::build(function);
+  for block in cfg.blocks.iter().rev() {
+    let labelled = matches!(function.body.get(block.start), Some(Instr::Label(_)));
+    if !labelled && block.start < function.body.len() {
+      function.body.insert(block.start, Instr::Label(format!(":block{}", crate::create_num())));
+    }
+  }
+}
+
+fn split_blocks(function: &Function, cfg: &Cfg) -> Vec<Vec<Instr>> {
+  cfg.blocks.iter().map(|block| function.body[block.start..block.end].to_vec()).collect()
+}
+
+fn block_label(block: &Vec<Instr>) -> String {
+  match block.first() {
+    Some(Instr::Label(label)) => label.clone(),
+    _ => String::new(),
+  }
+}
+
+struct Renamer<'a> {
+  cfg: &'a Cfg,
+  children: Vec<Vec<usize>>,
+  blocks: Vec<Vec<Instr>>,
+  labels: Vec<String>,
+  phis: Vec<Vec<String>>, // variable of each phi at the head of a block, in order
+  scalars: HashSet<String>,
+  counters: HashMap<String, usize>,
+  stacks: HashMap<String, Vec<String>>,
+}
+
+impl<'a> Renamer<'a> {
+  fn current(&self, var: &String) -> String {
+    match self.stacks.get(var).and_then(|stack| stack.last()) {
+      Some(version) => version.clone(),
+      None => var.clone(),
+    }
+  }
+
+  fn fresh(&mut self, var: &String) -> String {
+    let counter = self.counters.entry(var.clone()).or_insert(0);
+    *counter += 1;
+    let version = format!("{var}.{counter}");
+    self.stacks.entry(var.clone()).or_default().push(version.clone());
+    return version;
+  }
+
+  fn rename(&mut self, b: usize) {
+    let mut pushed: Vec<String> = vec![];
+    let mut block = std::mem::take(&mut self.blocks[b]);
+    let mut phi = 0;
+    for instr in block.iter_mut() {
+      if let Instr::Phi(dest, _) = instr {
+        let var = self.phis[b][phi].clone();
+        phi += 1;
+        *dest = self.fresh(&var);
+        pushed.push(var);
+        continue;
+      }
+
+      for operand in ir::uses_mut(instr) {
+        if self.scalars.contains(operand) {
+          *operand = self.current(operand);
+        }
+      }
+      let defined = match instr {
+        Instr::Mov(x, _) | Instr::Load(x, _, _) | Instr::Binary(_, x, _, _) | Instr::Input(x) | Instr::Call(x, _, _) => Some(x),
+        _ => None,
+      };
+      if let Some(x) = defined {
+        if self.scalars.contains(x) {
+          let var = x.clone();
+          *x = self.fresh(&var);
+          pushed.push(var);
+        }
+      }
+    }
+    self.blocks[b] = block;
+
+    for s in self.cfg.blocks[b].succs.clone() {
+      let mut phi = 0;
+      for k in 0..self.blocks[s].len() {
+        let value = match &self.blocks[s][k] {
+          Instr::Phi(_, _) => self.current(&self.phis[s][phi]),
+          _ => continue,
+        };
+        phi += 1;
+        if let Instr::Phi(_, incoming) = &mut self.blocks[s][k] {
+          for (arg, label) in incoming.iter_mut() {
+            if label == &self.labels[b] {
+              *arg = value.clone();
+            }
+          }
+        }
+      }
+    }
+
+    for c in self.children[b].clone() {
+      self.rename(c);
+    }
+    for var in pushed {
+      self.stacks.get_mut(&var).unwrap().pop();
+    }
+  }
+}
+
+pub fn construct(function: &mut Function) {
+  label_blocks(function);
+  let cfg = cfg::build(function);
+  let mut blocks = split_blocks(function, &cfg);
+  let labels: Vec<String> = blocks.iter().map(block_label).collect();
+
+  let mut arrays: HashSet<String> = function.array_params.iter().cloned().collect();
+  let mut scalars: HashSet<String> = function.params.iter().cloned().collect();
+  for instr in &function.body {
+    match instr {
+      Instr::Int(x) => { scalars.insert(x.clone()); }
+      Instr::IntArray(x, _) => { arrays.insert(x.clone()); }
+      _ => {}
+    }
+  }
+  scalars.retain(|x| !arrays.contains(x));
+
+  // names read before being written in some block, and where each one is written.
+  // every variable counts as written at the entry, where it starts out as its parameter or 0.
+  let mut live_across: HashSet<String> = HashSet::new();
+  let mut defsites: HashMap<String, Vec<usize>> = HashMap::new();
+  for (b, block) in blocks.iter().enumerate() {
+    let mut written: HashSet<&String> = HashSet::new();
+    for instr in block {
+      for operand in ir::uses(instr) {
+        if scalars.contains(operand) && !written.contains(operand) {
+          live_across.insert(operand.clone());
+        }
+      }
+      if let Some(x) = ir::def(instr) {
+        if scalars.contains(x) {
+          written.insert(x);
+          let sites = defsites.entry(x.clone()).or_default();
+          if !sites.contains(&b) {
+            sites.push(b);
+          }
+        }
+      }
+    }
+  }
+
+  let frontiers = cfg.dominance_frontiers();
+  let mut phis: Vec<Vec<String>> = vec![vec![]; blocks.len()];
+  let mut names: Vec<&String> = live_across.iter().collect();
+  names.sort();
+  for var in names {
+    let mut worklist: Vec<usize> = defsites.get(var).cloned().unwrap_or_default();
+    worklist.push(0);
+    let mut seen: HashSet<usize> = worklist.iter().cloned().collect();
+    while let Some(b) = worklist.pop() {
+      for &d in &frontiers[b] {
+        if phis[d].contains(var) {
+          continue;
+        }
+        phis[d].push(var.clone());
+        if seen.insert(d) {
+          worklist.push(d);
+        }
+      }
+    }
+  }
+
+  for (b, block) in blocks.iter_mut().enumerate() {
+    block.retain(|instr| !matches!(instr, Instr::Int(x) if scalars.contains(x)));
+    let incoming: Vec<String> = cfg.blocks[b].preds.iter().map(|p| labels[*p].clone()).collect();
+    for (k, var) in phis[b].iter().enumerate() {
+      let args = incoming.iter().map(|label| (var.clone(), label.clone())).collect();
+      block.insert(1 + k, Instr::Phi(var.clone(), args));
+    }
+  }
+
+  let mut renamer = Renamer {
+    cfg: &cfg,
+    children: cfg.dominator_tree(),
+    blocks,
+    labels,
+    phis,
+    scalars,
+    counters: HashMap::new(),
+    stacks: HashMap::new(),
+  };
+  renamer.rename(0);
+  function.body = renamer.blocks.concat();
+}
+
+// x.3 -> _x_ssa3, a name the parser can never produce.
+fn plain_name(name: &String) -> String {
+  match name.rsplit_once('.') {
+    Some((var, version)) if version.parse::<usize>().is_ok() => format!("_{var}_ssa{version}"),
+    _ => name.clone(),
+  }
+}
+
+pub fn destruct(function: &mut Function) {
+  let cfg = cfg::build(function);
+  let mut blocks = split_blocks(function, &cfg);
+  let mut by_label: HashMap<String, usize> = HashMap::new();
+  for (b, block) in blocks.iter().enumerate() {
+    by_label.insert(block_label(block), b);
+  }
+
+  // d = phi(...) becomes copies into a fresh temp at the end of every
+  // predecessor and d = temp in its place. going through the temp keeps
+  // phis that read each other's results correct.
+  let mut copies: Vec<(usize, Instr)> = vec![];
+  for block in blocks.iter_mut() {
+    for instr in block.iter_mut() {
+      if let Instr::Phi(dest, incoming) = instr {
+        let temp = crate::create_temp();
+        for (value, label) in incoming.iter() {
+          if let Some(&p) = by_label.get(label) {
+            copies.push((p, Instr::Mov(temp.clone(), value.clone())));
+          }
+        }
+        *instr = Instr::Mov(dest.clone(), temp);
+      }
+    }
+  }
+  for (p, copy) in copies {
+    let block = &mut blocks[p];
+    let terminated = matches!(block.last(), Some(Instr::Jmp(_) | Instr::BranchIf(_, _) | Instr::BranchIfn(_, _) | Instr::JumpTable(_, _, _) | Instr::Ret(_)));
+    if terminated {
+      let last = block.len() - 1;
+      block.insert(last, copy);
+    } else {
+      block.push(copy);
+    }
+  }
+
+  let mut body: Vec<Instr> = blocks.concat().iter().map(|instr| ir::rename(instr, &plain_name, &|l| l.clone())).collect();
+
+  let mut arrays: HashSet<String> = HashSet::new();
+  for instr in &body {
+    if let Instr::IntArray(x, _) = instr {
+      arrays.insert(x.clone());
+    }
+  }
+  let mut declared: Vec<String> = vec![];
+  for instr in &body {
+    let mut names: Vec<&String> = ir::uses(instr);
+    names.extend(ir::def(instr));
+    for name in names {
+      if !ir::is_constant(name) && !arrays.contains(name) && !function.params.contains(name) && !declared.contains(name) {
+        declared.push(name.clone());
+      }
+    }
+  }
+  let position = if matches!(body.first(), Some(Instr::Label(_))) { 1 } else { 0 };
+  body.splice(position..position, declared.into_iter().map(Instr::Int));
+  function.body = body;
+}
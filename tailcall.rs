@@ -0,0 +1,77 @@
+// tail-call elimination for self-recursive functions. `return f(...)` inside
+// f is lowered to reassigning the parameters and jumping back to the top of
+// the body, so deep recursion no longer grows the call stack.
+use crate::ir::{self, Function, Instr};
+
+// index of the %ret that returns the result of the call at `index`, if the call is in tail position.
+fn tail_return(body: &Vec<Instr>, index: usize) -> Option<usize> {
+  let dest = match &body[index] {
+    Instr::Call(dest, _, _) => dest,
+    _ => return None,
+  };
+  let mut i = index + 1;
+  while let Some(Instr::Int(x)) = body.get(i) {
+    if x == dest {
+      return None;
+    }
+    i += 1;
+  }
+  match body.get(i) {
+    Some(Instr::Ret(x)) if x == dest => Some(i),
+    _ => None,
+  }
+}
+
+// an array parameter can't be reassigned, so the call has to pass each one on unchanged.
+fn same_arrays(function: &Function, args: &Vec<String>) -> bool {
+  function.params.iter().zip(args).all(|(param, arg)| {
+    !function.array_params.contains(param) || ir::array_arg(arg) == Some(param.as_str())
+  })
+}
+
+pub fn eliminate_tail_calls(function: &mut Function) -> bool {
+  let start = format!(":tailcall{}", crate::create_num());
+  let mut body: Vec<Instr> = vec![];
+  let mut changed = false;
+  let mut i = 0;
+  while i < function.body.len() {
+    let instr = &function.body[i];
+    let args = match instr {
+      Instr::Call(_, callee, args) if callee == &function.name && args.len() == function.params.len() && same_arrays(function, args) => args,
+      _ => {
+        body.push(instr.clone());
+        i += 1;
+        continue;
+      }
+    };
+    let ret = match tail_return(&function.body, i) {
+      Some(ret) => ret,
+      None => {
+        body.push(instr.clone());
+        i += 1;
+        continue;
+      }
+    };
+
+    // evaluate every argument before any parameter is overwritten.
+    let mut temps: Vec<String> = vec![];
+    for arg in args.iter().filter(|arg| ir::array_arg(arg).is_none()) {
+      let temp = crate::create_temp();
+      body.push(Instr::Int(temp.clone()));
+      body.push(Instr::Mov(temp.clone(), arg.clone()));
+      temps.push(temp);
+    }
+    for (param, temp) in function.params.iter().filter(|param| !function.array_params.contains(param)).zip(temps) {
+      body.push(Instr::Mov(param.clone(), temp));
+    }
+    body.push(Instr::Jmp(start.clone()));
+    changed = true;
+    i = ret + 1;
+  }
+
+  if changed {
+    body.insert(0, Instr::Label(start));
+    function.body = body;
+  }
+  return changed;
+}
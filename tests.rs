@@ -0,0 +1,700 @@
+// programs under tests/ compiled the way main does it and run in process,
+// plus emitted code compared with golden files next to them. TT_BLESS=1
+// rewrites the golden files instead of comparing.
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::{bytecode, cbackend, check, ir, llvm, loops, opt, riscv, rvsim, vm, wat, x86};
+use crate::ir::Overflow;
+
+// programs with the options and input they run with, covering arithmetic in
+// each overflow mode, calls, arrays and each kind of runtime error.
+const PROGRAMS: [(&str, &[&str], &str); 12] = [
+  ("arith", &[], "17 5"),
+  ("arith", &[], "-17 5"),
+  ("arith", &["--no-opt"], "-17 5"),
+  ("arith", &["--overflow=trap"], "100000 3"),
+  ("arith", &["--overflow=saturate"], "100000 3"),
+  ("calls", &[], "10"),
+  ("calls", &["--no-opt"], "10"),
+  ("arrays", &[], ""),
+  ("divide_by_zero", &[], "0"),
+  ("out_of_bounds", &[], "3"),
+  ("out_of_bounds", &[], "-1"),
+  ("switch_extremes", &["--overflow=trap"], "-2147483648 2147483647 2 -5"),
+];
+
+// temps, labels and token lines come from globals, so one compile at a time,
+// each starting the numbering from scratch to keep golden files stable.
+static COMPILER: Mutex<()> = Mutex::new(());
+
+fn start_numbering() -> MutexGuard<'static, ()> {
+  let guard = COMPILER.lock().unwrap_or_else(|error| error.into_inner());
+  unsafe {
+    crate::VAR_NUM = 0;
+    crate::IF_NUM = 0;
+  }
+  return guard;
+}
+
+fn path(name: &str) -> PathBuf {
+  return PathBuf::from(file!()).with_file_name("tests").join(name);
+}
+
+fn source(name: &str) -> String {
+  let path = path(&format!("{name}.tt"));
+  return fs::read_to_string(&path).unwrap_or_else(|error| panic!("{}: {error}", path.display()));
+}
+
+fn compile(name: &str, options: &[&str]) -> String {
+  return build(&source(name), options).unwrap_or_else(|message| panic!("{name}: {message}"));
+}
+
+// parses and optimises source given inline, for programs that only the
+// checks made while optimising should reject.
+fn build(code: &str, options: &[&str]) -> Result<String, String> {
+  let _guard = start_numbering();
+  let options: Vec<String> = options.iter().map(|option| option.to_string()).collect();
+  return crate::lex(code)
+    .and_then(|tokens| crate::parse_program(&tokens, &mut 0))
+    .and_then(|code| crate::optimize(&code, &options));
+}
+
+// compiles source given inline, for programs the parser should reject.
+fn parse(code: &str) -> Result<String, String> {
+  let _guard = start_numbering();
+  return crate::lex(code).and_then(|tokens| crate::parse_program(&tokens, &mut 0));
+}
+
+// compares emitted code with the file of that name under tests/, or writes
+// it there when TT_BLESS is set.
+fn golden(name: &str, actual: &str) {
+  let path = path(name);
+  if std::env::var_os("TT_BLESS").is_some() {
+    fs::write(&path, actual).unwrap();
+    return;
+  }
+  let expected = fs::read_to_string(&path).unwrap_or_else(|error| panic!("{}: {error}", path.display()));
+  assert!(actual == expected, "{} is out of date (TT_BLESS=1 rewrites it)", path.display());
+}
+
+// the loop passes on the first function of hand-written IR.
+fn optimize_loops(code: &str, overflow: Overflow) -> String {
+  let _guard = start_numbering();
+  let mut program = ir::parse_program(code).unwrap();
+  loops::optimize_loops(&mut program.functions[0], overflow);
+  return ir::print_function(&program.functions[0]);
+}
+
+fn vm_output(code: &String, input: &str) -> String {
+  let program = ir::parse_program(code).and_then(|program| bytecode::compile(&program)).unwrap();
+  return vm::output(&program, input);
+}
+
+fn riscv_output(code: &String, input: &str) -> String {
+  let assembly = ir::parse_program(code).and_then(|program| riscv::generate(&program)).unwrap();
+  return rvsim::run(&assembly, input).unwrap();
+}
+
+// builds `code` with a backend that needs outside tools (exe, c or llvm) in a
+// directory of its own and runs the result like --check does.
+fn native_output(kind: &str, code: &String, input: &str) -> String {
+  static BUILDS: AtomicUsize = AtomicUsize::new(0);
+  let program = ir::parse_program(code).unwrap();
+  let dir = std::env::temp_dir().join(format!("tt-test-{}-{}", std::process::id(), BUILDS.fetch_add(1, Ordering::Relaxed)));
+  fs::create_dir_all(&dir).unwrap();
+  let mut executable = dir.join("program");
+  let mut args: Vec<String> = vec![];
+  match kind {
+    "exe" => {
+      let assembly = dir.join("program.s");
+      fs::write(&assembly, x86::generate(&program).unwrap()).unwrap();
+      x86::link(&assembly, &executable).unwrap();
+    }
+    "c" => {
+      let source = dir.join("program.c");
+      fs::write(&source, cbackend::generate(&program).unwrap()).unwrap();
+      cbackend::compile(&source, &executable).unwrap();
+    }
+    "llvm" => {
+      let module = dir.join("program.ll");
+      fs::write(&module, llvm::generate(&program).unwrap()).unwrap();
+      executable = PathBuf::from("lli");
+      args = llvm::lli_args(&module);
+    }
+    _ => panic!("unknown backend {kind}"),
+  }
+  let output = check::run(&executable, &args, &input.to_string()).unwrap();
+  let _ = fs::remove_dir_all(&dir);
+  return output;
+}
+
+// what --check does: the backend has to print what the VM prints.
+fn check(backend: &str) {
+  for (name, options, input) in PROGRAMS {
+    let code = compile(name, options);
+    let actual = match backend {
+      "riscv" => riscv_output(&code, input),
+      _ => native_output(backend, &code, input),
+    };
+    assert_eq!(actual, vm_output(&code, input), "{backend} output for {name} {options:?} with input {input:?}");
+  }
+}
+
+// inlined copies are laid out in the caller's frame, which is only zeroed
+// once, yet every expansion has to see its locals at zero like a real call.
+#[test]
+fn inlined_locals_start_at_zero() {
+  let optimised = compile("inline_locals", &[]);
+  assert!(!optimised.contains("%call"));
+  let expected = "1\n1\n1\n2\n".repeat(3);
+  assert_eq!(riscv_output(&optimised, ""), expected);
+  assert_eq!(riscv_output(&compile("inline_locals", &["--no-opt"]), ""), expected);
+}
+
+// an array whose size the callee computes can't be cleared before the copied
+// body runs, so such callees stay calls while fixed-size ones are inlined.
+#[test]
+fn callees_with_variable_arrays_are_not_inlined() {
+  let optimised = compile("inline_variable_array", &[]);
+  assert!(optimised.contains(", last(3)"), "{optimised}");
+  assert!(!optimised.contains("fixed("), "{optimised}");
+  let expected = "3\n5\n3\n3\n";
+  assert_eq!(vm_output(&optimised, ""), expected);
+  assert_eq!(vm_output(&compile("inline_variable_array", &["--no-opt"]), ""), expected);
+}
+
+// locals of every kind are zeroed once per call, not each time their
+// declaration runs, so an array declared in a loop body keeps its values.
+#[test]
+fn arrays_declared_in_loops_keep_their_values() {
+  let expected = "10\n1\n2\n20\n1\n2\n";
+  for options in [&[][..], &["--no-opt"]] {
+    let code = compile("array_in_loop", options);
+    assert_eq!(vm_output(&code, ""), expected);
+    assert_eq!(riscv_output(&code, ""), expected);
+  }
+}
+
+// a name is in scope until the end of the block that declares it, and a for
+// loop's init clause belongs to the loop, so names can be reused afterwards
+// but not redeclared while the outer one is still visible, nor used once it
+// is gone. arrays of the same name in sibling blocks may differ in size.
+#[test]
+fn variables_are_scoped_to_their_block() {
+  let expected = "5\n28\n7\n8\n1\n";
+  for options in [&[][..], &["--no-opt"]] {
+    let code = compile("scopes", options);
+    assert_eq!(vm_output(&code, ""), expected);
+    assert_eq!(riscv_output(&code, ""), expected);
+  }
+  let shadowing = [
+    "func main() { int i = 5; for (int i = 0; i < 3; i++) {} print(i); }",
+    "func main() { int i = 5; while i > 0 { int i = 1; } }",
+    "func main(int i) { if i > 0 { int i = 1; } }",
+    "func main() { int i = 5; int i = 6; }",
+    "func main() { int i; int[3] i; }",
+  ];
+  for code in shadowing {
+    assert_eq!(parse(code), Err(String::from("Variable i already declared")), "{code}");
+  }
+  assert_eq!(parse("func main() { int[3] i; int i; }"), Err(String::from("i is already declared as an array")));
+  let out_of_scope = [
+    ("func main() { for (int i = 0; i < 3; i++) {} print(i); }", "Variable i not declared"),
+    ("func main() { for (int i = 0; i < 3; i++) { int y = 2; } print(y); }", "Variable y not declared"),
+    ("func main() { for (int i = 0; i < 3; i++) {} i = 42; }", "Variable i not declared"),
+    ("func main() { int k = 0; if k == 0 { int[3] a; } print(a[1]); }", "Array a not initialized"),
+    ("func main() { int[3] a; print(a); }", "a is an array and needs an index"),
+    ("func main() { x++; }", "Variable x not declared"),
+  ];
+  for (code, error) in out_of_scope {
+    assert_eq!(parse(code), Err(String::from(error)), "{code}");
+  }
+}
+
+// constants follow the same scoping, and can't take the name of anything
+// already visible.
+#[test]
+fn constants_are_scoped_to_their_block() {
+  assert_eq!(vm_output(&compile("const_scopes", &[]), ""), "1124\n");
+  let clashes = [
+    "func main() { int x; const int x = 1; }",
+    "func main() { int[3] x; const int x = 1; }",
+    "int x;\nfunc main() { const int x = 1; }",
+    "int[2] x;\nfunc main() { const int x = 1; }",
+    "func main(int x) { if x > 0 { const int x = 1; } }",
+    "func main() { for (int x = 0; x < 2; x++) { const int x = 1; } }",
+    "const int x = 1;\nfunc main() { const int x = 2; }",
+  ];
+  for code in clashes {
+    assert_eq!(parse(code), Err(String::from("x is already declared")), "{code}");
+  }
+}
+
+// a jump table subtracts the lowest case from the value, which must not be
+// reached with values it would overflow for when overflow traps.
+#[test]
+fn switch_tables_take_any_value() {
+  let input = "-2147483648 2147483647 2 -5";
+  let expected = "-2147483648\n2147483647\n20\n-5\n";
+  for options in [&["--overflow=trap"][..], &["--overflow=trap", "--no-opt"]] {
+    let code = compile("switch_extremes", options);
+    assert!(code.contains("%jump_table"));
+    assert_eq!(vm_output(&code, input), expected);
+    assert_eq!(riscv_output(&code, input), expected);
+  }
+}
+
+// break and continue leave or restart the loop named by their label, or
+// the innermost one without it.
+#[test]
+fn labelled_break_and_continue_pick_their_loop() {
+  let expected = "32\n1\n3\n5\n";
+  for options in [&[][..], &["--no-opt"]] {
+    let code = compile("break_continue", options);
+    assert_eq!(vm_output(&code, ""), expected);
+    assert_eq!(riscv_output(&code, ""), expected);
+  }
+  let errors = [
+    ("func main() { a: while 1 < 2 {} while 1 < 2 { break a; } }", "break to a, which is not the label of an enclosing loop"),
+    ("func main() { a: while 1 < 2 {} while 1 < 2 { continue a; } }", "continue to a, which is not the label of an enclosing loop"),
+    ("func main() { a: while 1 < 2 { a: while 1 < 2 {} } }", "label a is already used by an enclosing loop"),
+    ("func main() { a: print(1); }", "label a must be followed by a loop"),
+    ("func main() { break; }", "Tried to break but not in loop"),
+    ("func main() { continue; }", "Tried to continue but not in loop"),
+  ];
+  for (code, error) in errors {
+    assert_eq!(parse(code), Err(String::from(error)), "{code}");
+  }
+}
+
+// globals are shared by every function, recursive calls included, and no
+// local or parameter may take a global's name.
+#[test]
+fn globals_are_shared_across_functions() {
+  let expected = "7\n7\n2\n8\n12\n15\n";
+  for options in [&[][..], &["--no-opt"]] {
+    let code = compile("globals", options);
+    assert_eq!(vm_output(&code, "7"), expected);
+    assert_eq!(riscv_output(&code, "7"), expected);
+  }
+  let clashes = [
+    ("int g;\nint[2] g;", "global g already declared"),
+    ("int g = 1;", "global g cannot have an initialiser, globals start out as 0"),
+    ("int g;\nfunc f(int g) { return g; }", "parameter g has the name of a global"),
+    ("int[3] g;\nfunc f(int g) { return g; }", "parameter g has the name of a global"),
+    ("int g;\nfunc f(int[] g) { return 1; }", "parameter g has the name of a global"),
+    ("int g;\nfunc main() { int[2] g; }", "g is already declared as a global"),
+    ("int[3] g;\nfunc main() { int g = 1; }", "g is already declared as an array"),
+  ];
+  for (code, error) in clashes {
+    assert_eq!(parse(code), Err(String::from(error)), "{code}");
+  }
+}
+
+// listed elements are stored from index 0 and the rest stay 0, and a list
+// needs an array of constant size with room for it.
+#[test]
+fn initialisers_fill_arrays_from_the_start() {
+  let expected = "25\n0\n40\n0\n20\n11\n4\n";
+  for options in [&[][..], &["--no-opt"]] {
+    let code = compile("initialisers", options);
+    assert_eq!(vm_output(&code, "5"), expected);
+    assert_eq!(riscv_output(&code, "5"), expected);
+  }
+  let errors = [
+    ("func main() { int n = 3; int[n] a = {1}; }", "array a needs a constant size to have an initialiser"),
+    ("func main() { int[2] a = {1, 2, 3}; }", "too many initialisers for a, it has 2 elements"),
+    ("func main() { int[2] a = 1; }", "expected '{' to start the array initialiser"),
+    ("func main() { int[2] a = {1 2}; }", "expected ',' or '}' in array initialiser"),
+  ];
+  for (code, error) in errors {
+    assert_eq!(parse(code), Err(String::from(error)), "{code}");
+  }
+}
+
+// arrays are passed by reference along with their size, which bounds checks
+// in the callee use, and only to parameters of the same shape.
+#[test]
+fn array_parameters_share_the_callers_array() {
+  let expected = "60\n16\n28\n9\n6\n1\nruntime error: line 30: index 5 out of bounds for array xs of size 5\n";
+  for options in [&[][..], &["--no-opt"]] {
+    assert_eq!(vm_output(&compile("array_params", options), "5"), expected);
+    // the other backends need arrays of constant size.
+    let fixed = build(&source("array_params").replace("int[n] v;", "int[5] v;"), options).unwrap();
+    assert_eq!(riscv_output(&fixed, "5"), expected);
+  }
+  let errors = [
+    ("func f(int[] xs) { return 0; }\nfunc main() { int x; print(f(x)); }", "argument 1 of f must be an int[] array"),
+    ("func f(int[] xs) { return 0; }\nfunc main() { int[2][3] m; print(f(m)); }", "argument 1 of f must be an int[] array"),
+    ("func f(int[][3] m) { return 0; }\nfunc main() { int[2][4] m; print(f(m)); }", "argument 1 of f must be an int[][3] array"),
+    ("func f(int x) { return 0; }\nfunc main() { int[2] a; print(f(a)); }", "argument 1 of f must be an int, a is an array"),
+    ("func f(int[] xs, int n) { return 0; }\nfunc main() { int[2] a; print(f(a)); }", "f takes 2 arguments but 1 were given"),
+  ];
+  for (code, error) in errors {
+    assert_eq!(parse(code), Err(String::from(error)), "{code}");
+  }
+}
+
+// an element takes one index per dimension, wherever the array comes from
+// and however it is used.
+#[test]
+fn elements_take_one_index_per_dimension() {
+  let errors = [
+    ("func main() { int[2][3] m; m[1] = 4; }", "m needs 2 indices, one per dimension"),
+    ("func main() { int[2][3] m; print(m[1][2][0]); }", "m needs 2 indices, one per dimension"),
+    ("func main() { int[2][3][4] c; c[1][2] += 1; }", "c needs 3 indices, one per dimension"),
+    ("func main() { int[2][3][4] c; c[1][2]++; }", "c needs 3 indices, one per dimension"),
+    ("func main() { int[2] a; a[0][1] = 1; }", "a takes a single index"),
+    ("func f(int[][3] m) { return m[1]; }", "m needs 2 indices, one per dimension"),
+    ("int[2][3] g;\nfunc main() { print(g[1]); }", "g needs 2 indices, one per dimension"),
+    ("func main() { int[2][3] m; print(m); }", "m is an array and needs an index"),
+  ];
+  for (code, error) in errors {
+    assert_eq!(parse(code), Err(String::from(error)), "{code}");
+  }
+}
+
+// op= and ++/-- on an array element read and write the same element, so the
+// index, here a call that counts itself, is evaluated once.
+#[test]
+fn compound_assignment_evaluates_the_index_once() {
+  let expected = "4\n3\n-2\n1\n-1\n8\n-1\n6\n-11\n1\n";
+  for options in [&[][..], &["--no-opt"]] {
+    let code = compile("compound", options);
+    assert_eq!(vm_output(&code, ""), expected);
+    assert_eq!(riscv_output(&code, ""), expected);
+  }
+}
+
+// a switch jumps to its matching case or default and runs on into the next
+// case until a break, through a table for dense cases or compares otherwise.
+#[test]
+fn switch_runs_from_the_matching_case() {
+  let expected = "99\n10\n50\n30\n99\n50\n99\n1\n2\n";
+  for options in [&[][..], &["--no-opt"]] {
+    let code = compile("switch", options);
+    assert!(code.contains("%jump_table"));
+    assert_eq!(vm_output(&code, ""), expected);
+    assert_eq!(riscv_output(&code, ""), expected);
+  }
+  let errors = [
+    ("func main() { switch (1) { case 1: case 1: } }", "duplicate case value 1 in switch"),
+    ("func main() { switch (1) { default: default: } }", "more than one 'default' in switch"),
+    ("func main() { switch (1) { print(1); } }", "expected 'case' or 'default' in switch"),
+  ];
+  for (code, error) in errors {
+    assert_eq!(parse(code), Err(String::from(error)), "{code}");
+  }
+}
+
+// a condition without a comparison holds when its value isn't 0, in every
+// kind of loop and in if.
+#[test]
+fn conditions_without_a_comparison_test_for_non_zero() {
+  for (input, expected) in [("10", "0\n0\n4\n1\n4\n5\n"), ("1", "0\n0\n1\n1\n4\n")] {
+    for options in [&[][..], &["--no-opt"]] {
+      let code = compile("conditions", options);
+      assert_eq!(vm_output(&code, input), expected, "{options:?} {input}");
+      assert_eq!(riscv_output(&code, input), expected, "{options:?} {input}");
+    }
+  }
+}
+
+// constant expressions are folded while parsing and others are computed at
+// runtime, and both group the same way: left to right, products first.
+#[test]
+fn folded_and_computed_expressions_agree() {
+  let expected = "7\n7\n-4\n-4\n33\n77\n33\n6\n";
+  for options in [&[][..], &["--no-opt"]] {
+    assert_eq!(vm_output(&compile("expressions", options), ""), expected);
+  }
+}
+
+// indexes and sizes known while compiling are checked then, as are indexes
+// a simple loop is certain to reach, with or without --no-opt.
+#[test]
+fn out_of_range_arrays_are_rejected() {
+  let constant = [
+    ("func main() {\n  int[4] a;\n  a[4] = 1;\n}", "line 3: index 4 out of bounds for array a of size 4"),
+    ("const int N = 4;\nfunc main() {\n  int[N] a;\n  print(a[N - 5]);\n}", "line 4: index -1 out of bounds for array a of size 4"),
+    ("func main() {\n  int[2][3] m;\n  m[1][3] = 1;\n}", "line 3: index 3 out of bounds for dimension 2 of array m of size 3"),
+    ("func main() {\n  int[0] a;\n}", "line 2: array a needs a positive size, not 0"),
+    ("const int N = 0 - 2;\nfunc main() {\n  int[N] a;\n}", "line 3: array a needs a positive size, not -2"),
+    ("int[0] g;\nfunc main() {}", "global arrays need a positive constant size"),
+  ];
+  for (code, error) in constant {
+    assert_eq!(parse(code), Err(String::from(error)), "{code}");
+  }
+  let overrun = "func main() {\n  int[4] a;\n  int i = 0;\n  while i < 5 {\n    a[i] = i;\n    i++;\n  }\n}";
+  let error = String::from("line 5: index 4 out of bounds for array a of size 4 in the loop over i");
+  assert_eq!(build(overrun, &[]), Err(error.clone()));
+  assert_eq!(build(overrun, &["--no-opt"]), Err(error));
+  assert!(build(&overrun.replace("i < 5", "i < 4"), &[]).is_ok());
+}
+
+// a literal has to fit in an int; the smallest one is spelled as a
+// subtraction, which must not overflow even when overflow traps.
+#[test]
+fn literals_must_fit_in_an_int() {
+  let expected = "-2147483648\n-2147483648\n2147483647\n";
+  for options in [&[][..], &["--overflow=trap"], &["--overflow=trap", "--no-opt"]] {
+    assert_eq!(vm_output(&compile("literals", options), ""), expected);
+  }
+  assert_eq!(parse("func main() {\n  int x = 2147483648;\n}"), Err(String::from("line 2: integer literal 2147483648 is too large (the largest int is 2147483647)")));
+  assert_eq!(parse("const int BIG = 99999999999;"), Err(String::from("line 1: integer literal 99999999999 is too large (the largest int is 2147483647)")));
+}
+
+// puts a fresh checksum on a damaged .ttbc body so that decode gets past it.
+fn reseal(body: &[u8]) -> Vec<u8> {
+  let mut bytes = body.to_vec();
+  bytes.extend_from_slice(&bytecode::checksum(body).to_le_bytes());
+  return bytes;
+}
+
+// a .ttbc file decodes to a program that runs and encodes the same way, and
+// anything else is refused before the VM allocates or runs any of it.
+#[test]
+fn bytecode_files_round_trip_and_reject_damage() {
+  let program = bytecode::compile(&ir::parse_program(&compile("calls", &[])).unwrap()).unwrap();
+  let bytes = bytecode::encode(&program);
+  let decoded = bytecode::decode(&bytes).unwrap();
+  assert_eq!(vm::output(&decoded, "10"), vm::output(&program, "10"));
+  assert_eq!(bytecode::encode(&decoded), bytes);
+
+  let mut older = bytes.clone();
+  older[4] = 4;
+  assert_eq!(bytecode::decode(&older).err(), Some(String::from("unsupported bytecode version 4 (expected 5)")));
+  let mut flipped = bytes.clone();
+  flipped[bytes.len() / 2] ^= 1;
+  assert_eq!(bytecode::decode(&flipped).err(), Some(String::from("bytecode checksum mismatch")));
+  assert_eq!(bytecode::decode(&bytes[..bytes.len() - 10]).err(), Some(String::from("bytecode checksum mismatch")));
+  assert_eq!(bytecode::decode(&bytes[..8]).err(), Some(String::from("not a .ttbc file")));
+  let truncated = reseal(&bytes[..bytes.len() - 14]);
+  assert_eq!(bytecode::decode(&truncated).err(), Some(String::from("bytecode ends unexpectedly")));
+
+  let huge = bytecode::Program { overflow: Overflow::Wrap, functions: program.functions.clone(), main: program.main, globals: vec![1 << 20, u32::MAX] };
+  assert_eq!(bytecode::decode(&bytecode::encode(&huge)).err(), Some(String::from("globals hold more than 67108864 ints")));
+  let mut functions = program.functions.clone();
+  functions[0].slots = u32::MAX;
+  let name = functions[0].name.clone();
+  let deep = bytecode::Program { overflow: Overflow::Wrap, functions, main: program.main, globals: vec![] };
+  assert_eq!(bytecode::decode(&bytecode::encode(&deep)).err(), Some(format!("frame of {name} is too large")));
+}
+
+// reading past the end of input gives 0, and a word that is not an int is a
+// runtime error, whichever backend runs the program.
+#[test]
+fn input_is_read_the_same_everywhere() {
+  let code = compile("input", &[]);
+  let cases = [
+    ("5", "5\n0\n"),
+    ("", "0\n0\n"),
+    ("-3\n+4\n", "-3\n4\n"),
+    ("5 oops", "runtime error: invalid input 'oops'\n"),
+    ("2147483648 1", "runtime error: invalid input '2147483648'\n"),
+  ];
+  for (input, expected) in cases {
+    assert_eq!(vm_output(&code, input), expected);
+    assert_eq!(riscv_output(&code, input), expected);
+    assert_eq!(native_output("exe", &code, input), expected);
+    assert_eq!(native_output("c", &code, input), expected);
+    assert_eq!(native_output("llvm", &code, input), expected);
+  }
+}
+
+#[test]
+fn c_matches_the_vm() {
+  check("c");
+}
+
+#[test]
+fn c_code_matches_the_golden_file() {
+  let code = compile("arith", &[]);
+  golden("arith.c", &cbackend::generate(&ir::parse_program(&code).unwrap()).unwrap());
+}
+
+#[test]
+fn llvm_matches_the_vm() {
+  check("llvm");
+}
+
+#[test]
+fn llvm_code_matches_the_golden_file() {
+  let code = compile("arith", &[]);
+  golden("arith.ll", &llvm::generate(&ir::parse_program(&code).unwrap()).unwrap());
+}
+
+#[test]
+fn riscv_matches_the_vm() {
+  check("riscv");
+}
+
+#[test]
+fn riscv_code_matches_the_golden_files() {
+  for name in ["arith", "calls", "arrays", "out_of_bounds"] {
+    let code = compile(name, &[]);
+    golden(&format!("{name}.rv.s"), &riscv::generate(&ir::parse_program(&code).unwrap()).unwrap());
+  }
+}
+
+// the simulator on its own, with assembly that did not come from riscv.rs.
+#[test]
+fn rvsim_runs_hand_written_assembly() {
+  let countdown = "    .text
+_start:
+    li t0, 3
+.Lloop:
+    mv a0, t0
+    li a7, 1
+    ecall
+    li a0, 10
+    li a7, 11
+    ecall
+    addi t0, t0, -1
+    bnez t0, .Lloop
+    li a7, 10
+    ecall
+";
+  assert_eq!(rvsim::run(countdown, "").unwrap(), "3\n2\n1\n");
+  assert!(rvsim::run("    .text\n_start:\n    li a7, 99\n    ecall\n", "").is_err());
+  assert!(rvsim::run("    .text\n_start:\n    lw a0, 0(zero)\n", "").is_err());
+}
+
+// the structured control flow wat.rs rebuilds from the dominator tree, for
+// loops of each kind, jump tables, labelled break and continue, and recursion.
+#[test]
+fn wat_code_matches_the_golden_files() {
+  for (name, construct) in [("loops", "loop"), ("switch", "br_table"), ("break_continue", "br $"), ("calls", "call $f_fib")] {
+    let code = compile(name, &[]);
+    let module = wat::generate(&ir::parse_program(&code).unwrap()).unwrap();
+    assert!(module.contains(construct), "{name}.wat has no {construct}");
+    golden(&format!("{name}.wat"), &module);
+  }
+}
+
+const COUNTED_LOOP: &str = "%func main ()
+%int n
+%int a
+%int b
+%int i
+%int t
+%int k
+%int j
+%int[] xs, 8
+%input n
+%input a
+%input b
+%mov i, 0
+:loop
+%lt t, i, 8
+%branch_ifn t, :done
+%mult k, a, b
+%mult j, i, 4
+%check i, 8, xs, 3
+%mov [xs + i], j
+%out k
+%add i, i, 1
+%jmp :loop
+:done
+%ret 0
+%endfunc
+";
+
+// a loop over an array of 8: a * b is invariant, i * 4 follows i, and the
+// check is covered by i < 8.
+#[test]
+fn loop_passes_hoist_reduce_and_drop_checks() {
+  let expected = "%func main ()
+%int n
+%int a
+%int b
+%int i
+%int t
+%int k
+%int j
+%int[] xs, 8
+%input n
+%input a
+%input b
+%mov i, 0
+:preheader1
+%mult k, a, b
+%int _temp1
+%mult _temp1, i, 4
+:loop
+%lt t, i, 8
+%branch_ifn t, :done
+%mov j, _temp1
+%mov [xs + i], j
+%out k
+%add i, i, 1
+%add _temp1, _temp1, 4
+%jmp :loop
+:done
+%ret 0
+%endfunc
+";
+  assert_eq!(optimize_loops(COUNTED_LOOP, Overflow::Wrap), expected);
+}
+
+// when overflow traps, a * b could stop a loop that never runs, so it stays,
+// and i * 4 is not turned into a running sum that could overflow first.
+#[test]
+fn loop_passes_leave_trapping_arithmetic_in_place() {
+  let optimised = optimize_loops(COUNTED_LOOP, Overflow::Trap);
+  let body = &optimised[optimised.find(":loop").unwrap()..];
+  assert!(body.contains("%mult k, a, b"));
+  assert!(body.contains("%mult j, i, 4"));
+  assert!(!optimised.contains("%check"));
+}
+
+#[test]
+fn loop_passes_keep_checks_the_condition_does_not_cover() {
+  let code = COUNTED_LOOP.replace("%lt t, i, 8", "%lt t, i, n");
+  let optimised = optimize_loops(&code, Overflow::Wrap);
+  assert_eq!(optimised.matches("%check i, 8, xs, 3").count(), 1);
+}
+
+#[test]
+fn loop_passes_keep_the_output() {
+  let cases = [
+    (&[][..], "3 2 5", "42\n"),
+    (&["--overflow=trap"], "3 2 5", "42\n"),
+    (&["--overflow=trap"], "0 100000 100000", "0\n"),
+    (&["--overflow=trap"], "1 100000 100000", "runtime error: integer overflow\n"),
+    (&["--overflow=saturate"], "2 100000 100000", "2147483647\n"),
+  ];
+  for (options, input, expected) in cases {
+    let no_opt = [options, &["--no-opt"]].concat();
+    assert_eq!(vm_output(&compile("loop_opt", options), input), expected, "{options:?} {input}");
+    assert_eq!(vm_output(&compile("loop_opt", &no_opt), input), expected, "{options:?} --no-opt {input}");
+  }
+}
+
+// the same cleaned-up program with and without the loop passes: hoisting
+// a * b and dropping the checks leaves fewer ops to run in every iteration.
+#[test]
+fn loop_passes_cut_the_ops_executed() {
+  // one run of numbering throughout, so the passes' temps are fresh.
+  let _guard = start_numbering();
+  let code = crate::lex(&source("loop_opt")).and_then(|tokens| crate::parse_program(&tokens, &mut 0)).unwrap();
+  let mut plain = ir::parse_program(&code).unwrap();
+  for function in plain.functions.iter_mut() {
+    opt::cleanup(function, plain.overflow);
+  }
+  let mut optimised = plain.clone();
+  for function in optimised.functions.iter_mut() {
+    loops::optimize_loops(function, optimised.overflow);
+    opt::cleanup(function, optimised.overflow);
+  }
+  let plain = bytecode::compile(&plain).unwrap();
+  let optimised = bytecode::compile(&optimised).unwrap();
+  for n in [10, 100, 1000] {
+    let input = format!("{n} 3 5");
+    assert_eq!(vm::output(&optimised, &input), vm::output(&plain, &input));
+    let (before, after) = (vm::steps(&plain, &input), vm::steps(&optimised, &input));
+    assert!(after + n < before, "{after} ops with the loop passes, {before} without, for n = {n}");
+  }
+}
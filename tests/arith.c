@@ -0,0 +1,114 @@
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+static void tt_division_by_zero(void) {
+  printf("runtime error: division by zero\n");
+  exit(1);
+}
+
+static void tt_out_of_bounds(int32_t index, int32_t size, const char *arr, int line) {
+  printf("runtime error: line %d: index %d out of bounds for array %s of size %d\n", line, (int)index, arr, (int)size);
+  exit(1);
+}
+
+static void tt_overflow(void) {
+  printf("runtime error: integer overflow\n");
+  exit(1);
+}
+
+static void tt_out(int32_t x) { printf("%d\n", (int)x); }
+
+// 0 at the end of input, and a runtime error unless the whole word is an int.
+static int32_t tt_input(void) {
+  char word[64], *end;
+  if (scanf("%63s", word) != 1) return 0;
+  long x = strtol(word, &end, 10);
+  if (end == word || *end != '\0' || x < INT32_MIN || x > INT32_MAX) {
+    printf("runtime error: invalid input '%s'\n", word);
+    exit(1);
+  }
+  return (int32_t)x;
+}
+
+static int32_t tt_fit(int64_t x) {
+  return (int32_t)(uint32_t)x;
+}
+
+static int32_t tt_add(int32_t a, int32_t b) { return tt_fit((int64_t)a + b); }
+static int32_t tt_sub(int32_t a, int32_t b) { return tt_fit((int64_t)a - b); }
+static int32_t tt_mult(int32_t a, int32_t b) { return tt_fit((int64_t)a * b); }
+
+static int32_t tt_div(int32_t a, int32_t b) {
+  if (b == 0) tt_division_by_zero();
+  return tt_fit((int64_t)a / b);
+}
+
+static int32_t tt_mod(int32_t a, int32_t b) {
+  if (b == 0) tt_division_by_zero();
+  if (b == -1) return 0;
+  return a % b;
+}
+
+static int32_t f_main(void);
+
+static int32_t f_main(void) {
+  int32_t v_a = 0;
+  int32_t v_b = 0;
+  int32_t v__temp1 = 0;
+  int32_t v__temp3 = 0;
+  int32_t v__temp5 = 0;
+  int32_t v__temp7 = 0;
+  int32_t v__temp9 = 0;
+  int32_t v__temp11 = 0;
+  int32_t v__temp12 = 0;
+  int32_t v__temp14 = 0;
+  int32_t v__temp15 = 0;
+  int32_t v__temp17 = 0;
+  int32_t v__temp19 = 0;
+  int32_t v__temp20 = 0;
+  int32_t v__temp22 = 0;
+  int32_t v__temp23 = 0;
+  int32_t v__temp24 = 0;
+  int32_t v__temp25 = 0;
+  v_a = tt_input();
+  v_b = tt_input();
+  v__temp1 = tt_add(v_a, v_b);
+  tt_out(v__temp1);
+  v__temp3 = tt_sub(v_a, v_b);
+  tt_out(v__temp3);
+  v__temp5 = tt_mult(v_a, v_b);
+  tt_out(v__temp5);
+  v__temp7 = tt_div(v_a, v_b);
+  tt_out(v__temp7);
+  v__temp9 = tt_mod(v_a, v_b);
+  tt_out(v__temp9);
+  v__temp11 = tt_div(v_a, v_b);
+  v__temp12 = tt_sub(0, v__temp11);
+  tt_out(v__temp12);
+  v__temp14 = v_a < v_b;
+  if (v__temp14) goto L_iftrue1;
+  goto L_else1;
+L_iftrue1:;
+  v__temp15 = tt_sub(v_b, v_a);
+  tt_out(v__temp15);
+  goto L_endif1;
+L_else1:;
+  v__temp17 = tt_sub(v_a, v_b);
+  tt_out(v__temp17);
+L_endif1:;
+  v__temp19 = tt_mult(v_a, 100000);
+  v__temp20 = tt_mult(v__temp19, 100000);
+  tt_out(v__temp20);
+  v__temp22 = tt_mult(v_a, v_b);
+  v__temp23 = tt_mult(v__temp22, v_b);
+  v__temp24 = tt_mult(v__temp23, v_b);
+  v__temp25 = tt_mult(v__temp24, v_b);
+  tt_out(v__temp25);
+  return 0;
+}
+
+int main(void) {
+  f_main();
+  return 0;
+}
@@ -0,0 +1,268 @@
+@.out = private unnamed_addr constant [4 x i8] c"%d\0A\00"
+@.in = private unnamed_addr constant [5 x i8] c"%63s\00"
+@.invalid = private unnamed_addr constant [35 x i8] c"runtime error: invalid input '%s'\0A\00"
+@.division = private unnamed_addr constant [32 x i8] c"runtime error: division by zero\00"
+@.bounds = private unnamed_addr constant [72 x i8] c"runtime error: line %d: index %d out of bounds for array %s of size %d\0A\00"
+@.overflow = private unnamed_addr constant [32 x i8] c"runtime error: integer overflow\00"
+
+declare i32 @printf(ptr, ...)
+declare i32 @scanf(ptr, ...)
+declare i32 @puts(ptr)
+declare i64 @strtol(ptr, ptr, i32)
+declare void @exit(i32)
+
+; the next whitespace-separated word as an int, like the VM reads it: 0 at
+; the end of input, and a runtime error unless the whole word is an int.
+define internal i32 @tt_input() {
+  %word = alloca [64 x i8]
+  %end = alloca ptr
+  %read = call i32 (ptr, ...) @scanf(ptr @.in, ptr %word)
+  %found = icmp eq i32 %read, 1
+  br i1 %found, label %convert, label %none
+none:
+  ret i32 0
+convert:
+  %x = call i64 @strtol(ptr %word, ptr %end, i32 10)
+  %stop = load ptr, ptr %end
+  %empty = icmp eq ptr %stop, %word
+  %last = load i8, ptr %stop
+  %rest = icmp ne i8 %last, 0
+  %v = trunc i64 %x to i32
+  %back = sext i32 %v to i64
+  %wide = icmp ne i64 %back, %x
+  %bad = or i1 %empty, %rest
+  %invalid = or i1 %bad, %wide
+  br i1 %invalid, label %fail, label %done
+fail:
+  call i32 (ptr, ...) @printf(ptr @.invalid, ptr %word)
+  call void @exit(i32 1)
+  unreachable
+done:
+  ret i32 %v
+}
+
+define internal void @tt_division_by_zero() {
+  call i32 @puts(ptr @.division)
+  call void @exit(i32 1)
+  unreachable
+}
+
+define internal void @tt_out_of_bounds(i32 %index, i32 %size, ptr %arr, i32 %line) {
+  call i32 (ptr, ...) @printf(ptr @.bounds, i32 %line, i32 %index, ptr %arr, i32 %size)
+  call void @exit(i32 1)
+  unreachable
+}
+
+define internal void @tt_overflow() {
+  call i32 @puts(ptr @.overflow)
+  call void @exit(i32 1)
+  unreachable
+}
+
+define internal i32 @tt_add(i32 %a, i32 %b) {
+  %x = sext i32 %a to i64
+  %y = sext i32 %b to i64
+  %r = add i64 %x, %y
+  %v = call i32 @tt_fit(i64 %r)
+  ret i32 %v
+}
+
+define internal i32 @tt_sub(i32 %a, i32 %b) {
+  %x = sext i32 %a to i64
+  %y = sext i32 %b to i64
+  %r = sub i64 %x, %y
+  %v = call i32 @tt_fit(i64 %r)
+  ret i32 %v
+}
+
+define internal i32 @tt_mult(i32 %a, i32 %b) {
+  %x = sext i32 %a to i64
+  %y = sext i32 %b to i64
+  %r = mul i64 %x, %y
+  %v = call i32 @tt_fit(i64 %r)
+  ret i32 %v
+}
+
+define internal i32 @tt_div(i32 %a, i32 %b) {
+  %zero = icmp eq i32 %b, 0
+  br i1 %zero, label %fail, label %nonzero
+fail:
+  call void @tt_division_by_zero()
+  unreachable
+nonzero:
+  %minus = icmp eq i32 %b, -1
+  br i1 %minus, label %negate, label %divide
+negate:
+  %n = call i32 @tt_sub(i32 0, i32 %a)
+  ret i32 %n
+divide:
+  %q = sdiv i32 %a, %b
+  ret i32 %q
+}
+
+define internal i32 @tt_mod(i32 %a, i32 %b) {
+  %zero = icmp eq i32 %b, 0
+  br i1 %zero, label %fail, label %nonzero
+fail:
+  call void @tt_division_by_zero()
+  unreachable
+nonzero:
+  %minus = icmp eq i32 %b, -1
+  br i1 %minus, label %none, label %divide
+none:
+  ret i32 0
+divide:
+  %r = srem i32 %a, %b
+  ret i32 %r
+}
+
+define internal i32 @tt_fit(i64 %x) {
+  %v = trunc i64 %x to i32
+  ret i32 %v
+}
+
+define i32 @f_main() {
+entry:
+  %v.a = alloca i32
+  store i32 0, ptr %v.a
+  %v.b = alloca i32
+  store i32 0, ptr %v.b
+  %v._temp1 = alloca i32
+  store i32 0, ptr %v._temp1
+  %v._temp3 = alloca i32
+  store i32 0, ptr %v._temp3
+  %v._temp5 = alloca i32
+  store i32 0, ptr %v._temp5
+  %v._temp7 = alloca i32
+  store i32 0, ptr %v._temp7
+  %v._temp9 = alloca i32
+  store i32 0, ptr %v._temp9
+  %v._temp11 = alloca i32
+  store i32 0, ptr %v._temp11
+  %v._temp12 = alloca i32
+  store i32 0, ptr %v._temp12
+  %v._temp14 = alloca i32
+  store i32 0, ptr %v._temp14
+  %v._temp15 = alloca i32
+  store i32 0, ptr %v._temp15
+  %v._temp17 = alloca i32
+  store i32 0, ptr %v._temp17
+  %v._temp19 = alloca i32
+  store i32 0, ptr %v._temp19
+  %v._temp20 = alloca i32
+  store i32 0, ptr %v._temp20
+  %v._temp22 = alloca i32
+  store i32 0, ptr %v._temp22
+  %v._temp23 = alloca i32
+  store i32 0, ptr %v._temp23
+  %v._temp24 = alloca i32
+  store i32 0, ptr %v._temp24
+  %v._temp25 = alloca i32
+  store i32 0, ptr %v._temp25
+  %t1 = call i32 @tt_input()
+  store i32 %t1, ptr %v.a
+  %t2 = call i32 @tt_input()
+  store i32 %t2, ptr %v.b
+  %t3 = load i32, ptr %v.a
+  %t4 = load i32, ptr %v.b
+  %t5 = add i32 %t3, %t4
+  store i32 %t5, ptr %v._temp1
+  %t6 = load i32, ptr %v._temp1
+  call i32 (ptr, ...) @printf(ptr @.out, i32 %t6)
+  %t7 = load i32, ptr %v.a
+  %t8 = load i32, ptr %v.b
+  %t9 = sub i32 %t7, %t8
+  store i32 %t9, ptr %v._temp3
+  %t10 = load i32, ptr %v._temp3
+  call i32 (ptr, ...) @printf(ptr @.out, i32 %t10)
+  %t11 = load i32, ptr %v.a
+  %t12 = load i32, ptr %v.b
+  %t13 = mul i32 %t11, %t12
+  store i32 %t13, ptr %v._temp5
+  %t14 = load i32, ptr %v._temp5
+  call i32 (ptr, ...) @printf(ptr @.out, i32 %t14)
+  %t15 = load i32, ptr %v.a
+  %t16 = load i32, ptr %v.b
+  %t17 = call i32 @tt_div(i32 %t15, i32 %t16)
+  store i32 %t17, ptr %v._temp7
+  %t18 = load i32, ptr %v._temp7
+  call i32 (ptr, ...) @printf(ptr @.out, i32 %t18)
+  %t19 = load i32, ptr %v.a
+  %t20 = load i32, ptr %v.b
+  %t21 = call i32 @tt_mod(i32 %t19, i32 %t20)
+  store i32 %t21, ptr %v._temp9
+  %t22 = load i32, ptr %v._temp9
+  call i32 (ptr, ...) @printf(ptr @.out, i32 %t22)
+  %t23 = load i32, ptr %v.a
+  %t24 = load i32, ptr %v.b
+  %t25 = call i32 @tt_div(i32 %t23, i32 %t24)
+  store i32 %t25, ptr %v._temp11
+  %t26 = load i32, ptr %v._temp11
+  %t27 = sub i32 0, %t26
+  store i32 %t27, ptr %v._temp12
+  %t28 = load i32, ptr %v._temp12
+  call i32 (ptr, ...) @printf(ptr @.out, i32 %t28)
+  %t29 = load i32, ptr %v.a
+  %t30 = load i32, ptr %v.b
+  %t32 = icmp slt i32 %t29, %t30
+  %t31 = zext i1 %t32 to i32
+  store i32 %t31, ptr %v._temp14
+  %t33 = load i32, ptr %v._temp14
+  %t34 = icmp ne i32 %t33, 0
+  br i1 %t34, label %L.iftrue1, label %fall35
+fall35:
+  br label %L.else1
+dead36:
+  br label %L.iftrue1
+L.iftrue1:
+  %t37 = load i32, ptr %v.b
+  %t38 = load i32, ptr %v.a
+  %t39 = sub i32 %t37, %t38
+  store i32 %t39, ptr %v._temp15
+  %t40 = load i32, ptr %v._temp15
+  call i32 (ptr, ...) @printf(ptr @.out, i32 %t40)
+  br label %L.endif1
+dead41:
+  br label %L.else1
+L.else1:
+  %t42 = load i32, ptr %v.a
+  %t43 = load i32, ptr %v.b
+  %t44 = sub i32 %t42, %t43
+  store i32 %t44, ptr %v._temp17
+  %t45 = load i32, ptr %v._temp17
+  call i32 (ptr, ...) @printf(ptr @.out, i32 %t45)
+  br label %L.endif1
+L.endif1:
+  %t46 = load i32, ptr %v.a
+  %t47 = mul i32 %t46, 100000
+  store i32 %t47, ptr %v._temp19
+  %t48 = load i32, ptr %v._temp19
+  %t49 = mul i32 %t48, 100000
+  store i32 %t49, ptr %v._temp20
+  %t50 = load i32, ptr %v._temp20
+  call i32 (ptr, ...) @printf(ptr @.out, i32 %t50)
+  %t51 = load i32, ptr %v.a
+  %t52 = load i32, ptr %v.b
+  %t53 = mul i32 %t51, %t52
+  store i32 %t53, ptr %v._temp22
+  %t54 = load i32, ptr %v._temp22
+  %t55 = load i32, ptr %v.b
+  %t56 = mul i32 %t54, %t55
+  store i32 %t56, ptr %v._temp23
+  %t57 = load i32, ptr %v._temp23
+  %t58 = load i32, ptr %v.b
+  %t59 = mul i32 %t57, %t58
+  store i32 %t59, ptr %v._temp24
+  %t60 = load i32, ptr %v._temp24
+  %t61 = load i32, ptr %v.b
+  %t62 = mul i32 %t60, %t61
+  store i32 %t62, ptr %v._temp25
+  %t63 = load i32, ptr %v._temp25
+  call i32 (ptr, ...) @printf(ptr @.out, i32 %t63)
+  ret i32 0
+}
+
+define i32 @main() {
+  call i32 @f_main()
+  ret i32 0
+}
@@ -0,0 +1,249 @@
+    .text
+    .globl _start
+_start:
+    call tt_main
+    li a0, 0
+    li a7, 93
+    ecall
+
+tt_division_by_zero:
+    la a0, tt_division_message
+    li a7, 4
+    ecall
+    li a0, 1
+    li a7, 93
+    ecall
+
+tt_overflow:
+    la a0, tt_overflow_message
+    li a7, 4
+    ecall
+    li a0, 1
+    li a7, 93
+    ecall
+
+tt_out_of_bounds:
+    mv t0, a0
+    mv t1, a1
+    mv t2, a2
+    la a0, tt_bounds_line
+    li a7, 4
+    ecall
+    mv a0, a3
+    li a7, 1
+    ecall
+    la a0, tt_bounds_index
+    li a7, 4
+    ecall
+    mv a0, t0
+    li a7, 1
+    ecall
+    la a0, tt_bounds_array
+    li a7, 4
+    ecall
+    mv a0, t2
+    ecall
+    la a0, tt_bounds_size
+    ecall
+    mv a0, t1
+    li a7, 1
+    ecall
+    li a0, 10
+    li a7, 11
+    ecall
+    li a0, 1
+    li a7, 93
+    ecall
+
+    .globl tt_main
+tt_main:
+    mv t0, sp
+    addi sp, sp, -32
+    sw ra, -4(t0)
+    sw s0, -8(t0)
+    mv s0, t0
+    mv t0, sp
+    addi t1, s0, -8
+.Lmain__1:
+    sw zero, 0(t0)
+    addi t0, t0, 4
+    bne t0, t1, .Lmain__1
+    sw s1, -12(s0)
+    sw s2, -16(s0)
+    sw s3, -20(s0)
+    sw s4, -24(s0)
+    li s1, 0
+    li s2, 0
+    li s3, 0
+    li s4, 0
+    li a7, 5
+    ecall
+    mv s1, a0
+    li a7, 5
+    ecall
+    mv s2, a0
+    mv t0, s1
+    mv t1, s2
+    add t0, t0, t1
+    mv s3, t0
+    mv a0, s3
+    li a7, 1
+    ecall
+    li a0, 10
+    li a7, 11
+    ecall
+    mv t0, s1
+    mv t1, s2
+    sub t0, t0, t1
+    mv s3, t0
+    mv a0, s3
+    li a7, 1
+    ecall
+    li a0, 10
+    li a7, 11
+    ecall
+    mv t0, s1
+    mv t1, s2
+    mul t0, t0, t1
+    mv s3, t0
+    mv a0, s3
+    li a7, 1
+    ecall
+    li a0, 10
+    li a7, 11
+    ecall
+    mv t0, s1
+    mv t1, s2
+    bnez t1, .Lmain__2
+    call tt_division_by_zero
+.Lmain__2:
+    div t0, t0, t1
+    mv s3, t0
+    mv a0, s3
+    li a7, 1
+    ecall
+    li a0, 10
+    li a7, 11
+    ecall
+    mv t0, s1
+    mv t1, s2
+    bnez t1, .Lmain__3
+    call tt_division_by_zero
+.Lmain__3:
+    rem t0, t0, t1
+    mv s3, t0
+    mv a0, s3
+    li a7, 1
+    ecall
+    li a0, 10
+    li a7, 11
+    ecall
+    mv t0, s1
+    mv t1, s2
+    bnez t1, .Lmain__4
+    call tt_division_by_zero
+.Lmain__4:
+    div t0, t0, t1
+    mv s3, t0
+    li t0, 0
+    mv t1, s3
+    sub t0, t0, t1
+    mv s4, t0
+    mv a0, s4
+    li a7, 1
+    ecall
+    li a0, 10
+    li a7, 11
+    ecall
+    mv t0, s1
+    mv t1, s2
+    slt t0, t0, t1
+    mv s4, t0
+    mv t0, s4
+    beqz t0, .Lmain__5
+    j .Lmain_iftrue1
+.Lmain__5:
+    j .Lmain_else1
+.Lmain_iftrue1:
+    mv t0, s2
+    mv t1, s1
+    sub t0, t0, t1
+    mv s4, t0
+    mv a0, s4
+    li a7, 1
+    ecall
+    li a0, 10
+    li a7, 11
+    ecall
+    j .Lmain_endif1
+.Lmain_else1:
+    mv t0, s1
+    mv t1, s2
+    sub t0, t0, t1
+    mv s4, t0
+    mv a0, s4
+    li a7, 1
+    ecall
+    li a0, 10
+    li a7, 11
+    ecall
+.Lmain_endif1:
+    mv t0, s1
+    li t1, 100000
+    mul t0, t0, t1
+    mv s4, t0
+    mv t0, s4
+    li t1, 100000
+    mul t0, t0, t1
+    mv s3, t0
+    mv a0, s3
+    li a7, 1
+    ecall
+    li a0, 10
+    li a7, 11
+    ecall
+    mv t0, s1
+    mv t1, s2
+    mul t0, t0, t1
+    mv s3, t0
+    mv t0, s3
+    mv t1, s2
+    mul t0, t0, t1
+    mv s1, t0
+    mv t0, s1
+    mv t1, s2
+    mul t0, t0, t1
+    mv s3, t0
+    mv t0, s3
+    mv t1, s2
+    mul t0, t0, t1
+    mv s1, t0
+    mv a0, s1
+    li a7, 1
+    ecall
+    li a0, 10
+    li a7, 11
+    ecall
+    li a0, 0
+    lw s1, -12(s0)
+    lw s2, -16(s0)
+    lw s3, -20(s0)
+    lw s4, -24(s0)
+    lw ra, -4(s0)
+    mv sp, s0
+    lw s0, -8(sp)
+    ret
+
+    .data
+tt_division_message:
+    .string "runtime error: division by zero\n"
+tt_overflow_message:
+    .string "runtime error: integer overflow\n"
+tt_bounds_line:
+    .string "runtime error: line "
+tt_bounds_index:
+    .string ": index "
+tt_bounds_array:
+    .string " out of bounds for array "
+tt_bounds_size:
+    .string " of size "
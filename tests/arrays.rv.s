@@ -0,0 +1,342 @@
+    .text
+    .globl _start
+_start:
+    call tt_main
+    li a0, 0
+    li a7, 93
+    ecall
+
+tt_division_by_zero:
+    la a0, tt_division_message
+    li a7, 4
+    ecall
+    li a0, 1
+    li a7, 93
+    ecall
+
+tt_overflow:
+    la a0, tt_overflow_message
+    li a7, 4
+    ecall
+    li a0, 1
+    li a7, 93
+    ecall
+
+tt_out_of_bounds:
+    mv t0, a0
+    mv t1, a1
+    mv t2, a2
+    la a0, tt_bounds_line
+    li a7, 4
+    ecall
+    mv a0, a3
+    li a7, 1
+    ecall
+    la a0, tt_bounds_index
+    li a7, 4
+    ecall
+    mv a0, t0
+    li a7, 1
+    ecall
+    la a0, tt_bounds_array
+    li a7, 4
+    ecall
+    mv a0, t2
+    ecall
+    la a0, tt_bounds_size
+    ecall
+    mv a0, t1
+    li a7, 1
+    ecall
+    li a0, 10
+    li a7, 11
+    ecall
+    li a0, 1
+    li a7, 93
+    ecall
+
+    .globl tt_main
+tt_main:
+    mv t0, sp
+    addi sp, sp, -96
+    sw ra, -4(t0)
+    sw s0, -8(t0)
+    mv s0, t0
+    mv t0, sp
+    addi t1, s0, -8
+.Lmain__1:
+    sw zero, 0(t0)
+    addi t0, t0, 4
+    bne t0, t1, .Lmain__1
+    sw s1, -12(s0)
+    sw s2, -16(s0)
+    sw s3, -20(s0)
+    sw s4, -24(s0)
+    sw s5, -28(s0)
+    sw s6, -32(s0)
+    sw s7, -36(s0)
+    sw s8, -40(s0)
+    sw s9, -44(s0)
+    li s1, 0
+    li s2, 0
+    li s3, 0
+    li s4, 0
+    li s5, 0
+    li s6, 0
+    li s7, 0
+    li s8, 0
+    li s9, 0
+    li t2, 0
+    slli t2, t2, 2
+    addi t3, s0, -84
+    add t2, t3, t2
+    li t0, 3
+    sw t0, 0(t2)
+    li t2, 1
+    slli t2, t2, 2
+    addi t3, s0, -84
+    add t2, t3, t2
+    li t0, 1
+    sw t0, 0(t2)
+    li t2, 2
+    slli t2, t2, 2
+    addi t3, s0, -84
+    add t2, t3, t2
+    li t0, 4
+    sw t0, 0(t2)
+    li t0, 0
+    mv s1, t0
+    j .Lmain_loopcond1
+.Lmain_loopbegin1:
+    mv t0, s1
+    li t1, 1
+    add t0, t0, t1
+    mv s2, t0
+    mv t0, s2
+    mv s1, t0
+.Lmain_loopcond1:
+    mv t0, s1
+    li t1, 5
+    slt t0, t0, t1
+    mv s2, t0
+    mv t0, s2
+    bnez t0, .Lmain__2
+    j .Lmain_endloop1
+.Lmain__2:
+    mv t0, s1
+    mv t1, s1
+    mul t0, t0, t1
+    mv s2, t0
+    mv t2, s1
+    slli t2, t2, 2
+    la t3, tt_global_squares
+    add t2, t3, t2
+    mv t0, s2
+    sw t0, 0(t2)
+    j .Lmain_loopbegin1
+.Lmain_endloop1:
+    li t0, 0
+    mv s2, t0
+    li t0, 0
+    mv s3, t0
+    li t0, 0
+    mv s4, t0
+    j .Lmain_loopcond2
+.Lmain_loopbegin2:
+    mv t0, s2
+    li t1, 1
+    add t0, t0, t1
+    mv s5, t0
+    mv t0, s5
+    mv s2, t0
+    mv t0, s3
+    li t1, 3
+    add t0, t0, t1
+    mv s3, t0
+    mv t0, s4
+    li t1, 10
+    add t0, t0, t1
+    mv s4, t0
+.Lmain_loopcond2:
+    mv t0, s2
+    li t1, 2
+    slt t0, t0, t1
+    mv s5, t0
+    mv t0, s5
+    bnez t0, .Lmain__3
+    j .Lmain_endloop2
+.Lmain__3:
+    li t0, 0
+    mv s5, t0
+    mv t0, s3
+    mv s6, t0
+    mv t0, s4
+    mv s7, t0
+    j .Lmain_loopcond3
+.Lmain_loopbegin3:
+    mv t0, s5
+    li t1, 1
+    add t0, t0, t1
+    mv s8, t0
+    mv t0, s8
+    mv s5, t0
+.Lmain_loopcond3:
+    mv t0, s5
+    li t1, 3
+    slt t0, t0, t1
+    mv s8, t0
+    mv t0, s8
+    bnez t0, .Lmain__4
+    j .Lmain_endloop3
+.Lmain__4:
+    mv t0, s6
+    mv t1, s5
+    add t0, t0, t1
+    mv s8, t0
+    mv a0, s8
+    li a1, 6
+    sltu t0, a0, a1
+    bnez t0, .Lmain__5
+    la a2, tt_name_grid
+    li a3, 13
+    call tt_out_of_bounds
+.Lmain__5:
+    mv t0, s7
+    mv t1, s5
+    add t0, t0, t1
+    mv s9, t0
+    mv t2, s8
+    slli t2, t2, 2
+    addi t3, s0, -68
+    add t2, t3, t2
+    mv t0, s9
+    sw t0, 0(t2)
+    j .Lmain_loopbegin3
+.Lmain_endloop3:
+    j .Lmain_loopbegin2
+.Lmain_endloop2:
+    li t0, 0
+    mv s1, t0
+    j .Lmain_loopcond4
+.Lmain_loopbegin4:
+    mv t0, s1
+    li t1, 1
+    add t0, t0, t1
+    mv s4, t0
+    mv t0, s4
+    mv s1, t0
+.Lmain_loopcond4:
+    mv t0, s1
+    li t1, 4
+    slt t0, t0, t1
+    mv s4, t0
+    mv t0, s4
+    bnez t0, .Lmain__6
+    j .Lmain_endloop4
+.Lmain__6:
+    mv t2, s1
+    slli t2, t2, 2
+    addi t3, s0, -84
+    add t2, t3, t2
+    lw t0, 0(t2)
+    mv s4, t0
+    li t2, 0
+    slli t2, t2, 2
+    la t3, tt_global_total
+    add t2, t3, t2
+    lw t0, 0(t2)
+    mv s3, t0
+    mv t0, s3
+    mv t1, s4
+    add t0, t0, t1
+    mv s2, t0
+    li t2, 0
+    slli t2, t2, 2
+    la t3, tt_global_total
+    add t2, t3, t2
+    mv t0, s2
+    sw t0, 0(t2)
+    j .Lmain_loopbegin4
+.Lmain_endloop4:
+    li t2, 4
+    slli t2, t2, 2
+    la t3, tt_global_squares
+    add t2, t3, t2
+    lw t0, 0(t2)
+    mv s1, t0
+    mv a0, s1
+    li a7, 1
+    ecall
+    li a0, 10
+    li a7, 11
+    ecall
+    li t2, 5
+    slli t2, t2, 2
+    addi t3, s0, -68
+    add t2, t3, t2
+    lw t0, 0(t2)
+    mv s1, t0
+    mv a0, s1
+    li a7, 1
+    ecall
+    li a0, 10
+    li a7, 11
+    ecall
+    li t2, 0
+    slli t2, t2, 2
+    la t3, tt_global_total
+    add t2, t3, t2
+    lw t0, 0(t2)
+    mv s1, t0
+    mv a0, s1
+    li a7, 1
+    ecall
+    li a0, 10
+    li a7, 11
+    ecall
+    li t2, 3
+    slli t2, t2, 2
+    addi t3, s0, -84
+    add t2, t3, t2
+    lw t0, 0(t2)
+    mv s1, t0
+    mv a0, s1
+    li a7, 1
+    ecall
+    li a0, 10
+    li a7, 11
+    ecall
+    li a0, 0
+    lw s1, -12(s0)
+    lw s2, -16(s0)
+    lw s3, -20(s0)
+    lw s4, -24(s0)
+    lw s5, -28(s0)
+    lw s6, -32(s0)
+    lw s7, -36(s0)
+    lw s8, -40(s0)
+    lw s9, -44(s0)
+    lw ra, -4(s0)
+    mv sp, s0
+    lw s0, -8(sp)
+    ret
+
+    .data
+tt_global_squares:
+    .space 20
+tt_global_total:
+    .space 4
+tt_division_message:
+    .string "runtime error: division by zero\n"
+tt_overflow_message:
+    .string "runtime error: integer overflow\n"
+tt_bounds_line:
+    .string "runtime error: line "
+tt_bounds_index:
+    .string ": index "
+tt_bounds_array:
+    .string " out of bounds for array "
+tt_bounds_size:
+    .string " of size "
+tt_name_grid:
+    .string "grid"
@@ -0,0 +1,230 @@
+(module
+  (import "env" "out" (func $out (param i32)))
+  (import "env" "input" (func $input (result i32)))
+  (import "env" "division_by_zero" (func $division_by_zero))
+  (import "env" "out_of_bounds" (func $out_of_bounds (param i32 i32 i32 i32 i32)))
+  (memory (export "memory") 16)
+  (global $sp (mut i32) (i32.const 1048576))
+
+  (func $tt_div (param $a i32) (param $b i32) (result i32)
+    local.get $b
+    i32.eqz
+    if
+      call $division_by_zero
+      unreachable
+    end
+    local.get $b
+    i32.const -1
+    i32.eq
+    if
+      i32.const 0
+      local.get $a
+      call $tt_sub
+      return
+    end
+    local.get $a
+    local.get $b
+    i32.div_s)
+
+  (func $tt_mod (param $a i32) (param $b i32) (result i32)
+    local.get $b
+    i32.eqz
+    if
+      call $division_by_zero
+      unreachable
+    end
+    local.get $a
+    local.get $b
+    i32.rem_s)
+
+  (func $tt_add (param $a i32) (param $b i32) (result i32)
+    local.get $a
+    i64.extend_i32_s
+    local.get $b
+    i64.extend_i32_s
+    i64.add
+    call $tt_fit)
+
+  (func $tt_sub (param $a i32) (param $b i32) (result i32)
+    local.get $a
+    i64.extend_i32_s
+    local.get $b
+    i64.extend_i32_s
+    i64.sub
+    call $tt_fit)
+
+  (func $tt_mult (param $a i32) (param $b i32) (result i32)
+    local.get $a
+    i64.extend_i32_s
+    local.get $b
+    i64.extend_i32_s
+    i64.mul
+    call $tt_fit)
+
+  (func $tt_fit (param $x i64) (result i32)
+    local.get $x
+    i32.wrap_i64)
+
+  (func $f_main (result i32)
+    (local $v_found i32)
+    (local $v_i i32)
+    (local $v__temp3 i32)
+    (local $v__temp1 i32)
+    (local $v_j i32)
+    (local $v__temp6 i32)
+    (local $v__temp4 i32)
+    (local $v__temp7 i32)
+    (local $v__temp8 i32)
+    (local $v__temp9 i32)
+    (local $v__temp10 i32)
+    (local $v__temp11 i32)
+    (local $v__temp12 i32)
+    (local $v_k i32)
+    (local $v__temp14 i32)
+    (local $v__temp16 i32)
+    (local $v__temp17 i32)
+    (local $v__temp18 i32)
+    (local $v__temp19 i32)
+    i32.const 0
+    local.set $v_found
+    i32.const 0
+    local.set $v_i
+    loop $loopcond1
+      block $to_endloop1
+        local.get $v_i
+        i32.const 5
+        i32.lt_s
+        local.set $v__temp1
+        local.get $v__temp1
+        if
+          i32.const 0
+          local.set $v_j
+          loop $loopcond2
+            block $to_loopbegin1
+              block $to_endloop2
+                local.get $v_j
+                i32.const 5
+                i32.lt_s
+                local.set $v__temp4
+                local.get $v__temp4
+                if
+                  local.get $v_j
+                  local.get $v_i
+                  i32.eq
+                  local.set $v__temp7
+                  local.get $v__temp7
+                  if
+                    br $to_loopbegin1
+                  else
+                    local.get $v_i
+                    local.get $v_j
+                    i32.mul
+                    local.set $v__temp8
+                    local.get $v__temp8
+                    i32.const 6
+                    i32.eq
+                    local.set $v__temp9
+                    local.get $v__temp9
+                    if
+                      local.get $v_i
+                      i32.const 10
+                      i32.mul
+                      local.set $v__temp10
+                      local.get $v__temp10
+                      local.get $v_j
+                      i32.add
+                      local.set $v__temp11
+                      local.get $v__temp11
+                      local.set $v_found
+                      br $to_endloop1
+                    else
+                      local.get $v_j
+                      i32.const 3
+                      i32.gt_s
+                      local.set $v__temp12
+                      local.get $v__temp12
+                      if
+                        br $to_endloop2
+                      else
+                        local.get $v_j
+                        i32.const 1
+                        i32.add
+                        local.set $v__temp6
+                        local.get $v__temp6
+                        local.set $v_j
+                        br $loopcond2
+                      end
+                    end
+                  end
+                else
+                  br $to_endloop2
+                end
+              end
+              br $to_loopbegin1
+            end
+            local.get $v_i
+            i32.const 1
+            i32.add
+            local.set $v__temp3
+            local.get $v__temp3
+            local.set $v_i
+            br $loopcond1
+          end
+        else
+          br $to_endloop1
+        end
+      end
+      local.get $v_found
+      call $out
+      i32.const 0
+      local.set $v_k
+      loop $loopbegin6
+        block $to_endloop6
+          local.get $v_k
+          i32.const 10
+          i32.lt_s
+          local.set $v__temp14
+          local.get $v__temp14
+          if
+            local.get $v_k
+            i32.const 1
+            i32.add
+            local.set $v__temp16
+            local.get $v__temp16
+            local.set $v_k
+            local.get $v__temp16
+            i32.const 2
+            call $tt_mod
+            local.set $v__temp17
+            local.get $v__temp17
+            i32.const 0
+            i32.eq
+            local.set $v__temp18
+            local.get $v__temp18
+            if
+              br $loopbegin6
+            else
+              local.get $v_k
+              i32.const 6
+              i32.gt_s
+              local.set $v__temp19
+              local.get $v__temp19
+              if
+                br $to_endloop6
+              else
+                local.get $v_k
+                call $out
+                br $loopbegin6
+              end
+            end
+          else
+            br $to_endloop6
+          end
+        end
+        i32.const 0
+        return
+      end
+    end
+    unreachable)
+
+  (export "main" (func $f_main)))
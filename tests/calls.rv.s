@@ -0,0 +1,504 @@
+    .text
+    .globl _start
+_start:
+    call tt_main
+    li a0, 0
+    li a7, 93
+    ecall
+
+tt_division_by_zero:
+    la a0, tt_division_message
+    li a7, 4
+    ecall
+    li a0, 1
+    li a7, 93
+    ecall
+
+tt_overflow:
+    la a0, tt_overflow_message
+    li a7, 4
+    ecall
+    li a0, 1
+    li a7, 93
+    ecall
+
+tt_out_of_bounds:
+    mv t0, a0
+    mv t1, a1
+    mv t2, a2
+    la a0, tt_bounds_line
+    li a7, 4
+    ecall
+    mv a0, a3
+    li a7, 1
+    ecall
+    la a0, tt_bounds_index
+    li a7, 4
+    ecall
+    mv a0, t0
+    li a7, 1
+    ecall
+    la a0, tt_bounds_array
+    li a7, 4
+    ecall
+    mv a0, t2
+    ecall
+    la a0, tt_bounds_size
+    ecall
+    mv a0, t1
+    li a7, 1
+    ecall
+    li a0, 10
+    li a7, 11
+    ecall
+    li a0, 1
+    li a7, 93
+    ecall
+
+    .globl tt_fib
+tt_fib:
+    mv t0, sp
+    addi sp, sp, -32
+    sw ra, -4(t0)
+    sw s0, -8(t0)
+    mv s0, t0
+    mv t0, sp
+    addi t1, s0, -8
+.Lfib__1:
+    sw zero, 0(t0)
+    addi t0, t0, 4
+    bne t0, t1, .Lfib__1
+    sw s1, -12(s0)
+    sw s2, -16(s0)
+    sw s3, -20(s0)
+    li s1, 0
+    li s2, 0
+    li s3, 0
+    mv s1, a0
+    mv t0, s1
+    li t1, 2
+    slt t0, t0, t1
+    mv s2, t0
+    mv t0, s2
+    beqz t0, .Lfib__2
+    j .Lfib_iftrue1
+.Lfib__2:
+    j .Lfib_else1
+.Lfib_iftrue1:
+    mv a0, s1
+    lw s1, -12(s0)
+    lw s2, -16(s0)
+    lw s3, -20(s0)
+    lw ra, -4(s0)
+    mv sp, s0
+    lw s0, -8(sp)
+    ret
+.Lfib_else1:
+    mv t0, s1
+    li t1, 1
+    sub t0, t0, t1
+    mv s2, t0
+    mv a0, s2
+    call tt_fib
+    mv s3, a0
+    mv t0, s1
+    li t1, 2
+    sub t0, t0, t1
+    mv s2, t0
+    mv a0, s2
+    call tt_fib
+    mv s1, a0
+    mv t0, s3
+    mv t1, s1
+    add t0, t0, t1
+    mv s2, t0
+    mv a0, s2
+    lw s1, -12(s0)
+    lw s2, -16(s0)
+    lw s3, -20(s0)
+    lw ra, -4(s0)
+    mv sp, s0
+    lw s0, -8(sp)
+    ret
+    li a0, 0
+    lw s1, -12(s0)
+    lw s2, -16(s0)
+    lw s3, -20(s0)
+    lw ra, -4(s0)
+    mv sp, s0
+    lw s0, -8(sp)
+    ret
+
+    .globl tt_weigh
+tt_weigh:
+    mv t0, sp
+    addi sp, sp, -48
+    sw ra, -4(t0)
+    sw s0, -8(t0)
+    mv s0, t0
+    mv t0, sp
+    addi t1, s0, -8
+.Lweigh__1:
+    sw zero, 0(t0)
+    addi t0, t0, 4
+    bne t0, t1, .Lweigh__1
+    sw s1, -12(s0)
+    sw s2, -16(s0)
+    sw s3, -20(s0)
+    sw s4, -24(s0)
+    sw s5, -28(s0)
+    sw s6, -32(s0)
+    sw s7, -36(s0)
+    sw s8, -40(s0)
+    sw s9, -44(s0)
+    sw s10, -48(s0)
+    li s1, 0
+    li s3, 0
+    li s4, 0
+    li s5, 0
+    li s6, 0
+    li s7, 0
+    li s8, 0
+    li s9, 0
+    li s10, 0
+    mv s2, a0
+    mv s1, a1
+    mv s3, a2
+    mv s4, a3
+    mv s5, a4
+    mv s6, a5
+    mv s7, a6
+    mv s8, a7
+    lw t0, 0(s0)
+    mv s9, t0
+    li t0, 2
+    mv t1, s1
+    mul t0, t0, t1
+    mv s10, t0
+    mv t0, s2
+    mv t1, s10
+    add t0, t0, t1
+    mv s1, t0
+    li t0, 3
+    mv t1, s3
+    mul t0, t0, t1
+    mv s10, t0
+    mv t0, s1
+    mv t1, s10
+    add t0, t0, t1
+    mv s3, t0
+    li t0, 4
+    mv t1, s4
+    mul t0, t0, t1
+    mv s10, t0
+    mv t0, s3
+    mv t1, s10
+    add t0, t0, t1
+    mv s4, t0
+    li t0, 5
+    mv t1, s5
+    mul t0, t0, t1
+    mv s10, t0
+    mv t0, s4
+    mv t1, s10
+    add t0, t0, t1
+    mv s5, t0
+    li t0, 6
+    mv t1, s6
+    mul t0, t0, t1
+    mv s10, t0
+    mv t0, s5
+    mv t1, s10
+    add t0, t0, t1
+    mv s6, t0
+    li t0, 7
+    mv t1, s7
+    mul t0, t0, t1
+    mv s10, t0
+    mv t0, s6
+    mv t1, s10
+    add t0, t0, t1
+    mv s7, t0
+    li t0, 8
+    mv t1, s8
+    mul t0, t0, t1
+    mv s10, t0
+    mv t0, s7
+    mv t1, s10
+    add t0, t0, t1
+    mv s8, t0
+    li t0, 9
+    mv t1, s9
+    mul t0, t0, t1
+    mv s10, t0
+    mv t0, s8
+    mv t1, s10
+    add t0, t0, t1
+    mv s9, t0
+    mv a0, s9
+    lw s1, -12(s0)
+    lw s2, -16(s0)
+    lw s3, -20(s0)
+    lw s4, -24(s0)
+    lw s5, -28(s0)
+    lw s6, -32(s0)
+    lw s7, -36(s0)
+    lw s8, -40(s0)
+    lw s9, -44(s0)
+    lw s10, -48(s0)
+    lw ra, -4(s0)
+    mv sp, s0
+    lw s0, -8(sp)
+    ret
+    li a0, 0
+    lw s1, -12(s0)
+    lw s2, -16(s0)
+    lw s3, -20(s0)
+    lw s4, -24(s0)
+    lw s5, -28(s0)
+    lw s6, -32(s0)
+    lw s7, -36(s0)
+    lw s8, -40(s0)
+    lw s9, -44(s0)
+    lw s10, -48(s0)
+    lw ra, -4(s0)
+    mv sp, s0
+    lw s0, -8(sp)
+    ret
+
+    .globl tt_fill
+tt_fill:
+    mv t0, sp
+    addi sp, sp, -32
+    sw ra, -4(t0)
+    sw s0, -8(t0)
+    mv s0, t0
+    mv t0, sp
+    addi t1, s0, -8
+.Lfill__1:
+    sw zero, 0(t0)
+    addi t0, t0, 4
+    bne t0, t1, .Lfill__1
+    sw s1, -12(s0)
+    sw s2, -16(s0)
+    sw s3, -20(s0)
+    sw s4, -24(s0)
+    sw s5, -28(s0)
+    li s4, 0
+    li s5, 0
+    sw a0, -32(s0)
+    mv s1, a1
+    mv s3, a2
+    mv s2, a3
+    li t0, 0
+    mv s4, t0
+    j .Lfill_loopcond2
+.Lfill_loopbegin2:
+    mv t0, s4
+    li t1, 1
+    add t0, t0, t1
+    mv s5, t0
+    mv t0, s5
+    mv s4, t0
+.Lfill_loopcond2:
+    mv t0, s4
+    mv t1, s3
+    slt t0, t0, t1
+    mv s5, t0
+    mv t0, s5
+    bnez t0, .Lfill__2
+    j .Lfill_endloop2
+.Lfill__2:
+    mv a0, s4
+    mv a1, s1
+    sltu t0, a0, a1
+    bnez t0, .Lfill__3
+    la a2, tt_name_xs
+    li a3, 14
+    call tt_out_of_bounds
+.Lfill__3:
+    mv t0, s2
+    mv t1, s4
+    add t0, t0, t1
+    mv s5, t0
+    mv t2, s4
+    slli t2, t2, 2
+    lw t3, -32(s0)
+    add t2, t3, t2
+    mv t0, s5
+    sw t0, 0(t2)
+    j .Lfill_loopbegin2
+.Lfill_endloop2:
+    mv a0, s3
+    lw s1, -12(s0)
+    lw s2, -16(s0)
+    lw s3, -20(s0)
+    lw s4, -24(s0)
+    lw s5, -28(s0)
+    lw ra, -4(s0)
+    mv sp, s0
+    lw s0, -8(sp)
+    ret
+    li a0, 0
+    lw s1, -12(s0)
+    lw s2, -16(s0)
+    lw s3, -20(s0)
+    lw s4, -24(s0)
+    lw s5, -28(s0)
+    lw ra, -4(s0)
+    mv sp, s0
+    lw s0, -8(sp)
+    ret
+
+    .globl tt_main
+tt_main:
+    mv t0, sp
+    addi sp, sp, -64
+    sw ra, -4(t0)
+    sw s0, -8(t0)
+    mv s0, t0
+    mv t0, sp
+    addi t1, s0, -8
+.Lmain__1:
+    sw zero, 0(t0)
+    addi t0, t0, 4
+    bne t0, t1, .Lmain__1
+    sw s1, -12(s0)
+    sw s2, -16(s0)
+    sw s3, -20(s0)
+    sw s4, -24(s0)
+    sw s5, -28(s0)
+    sw s6, -32(s0)
+    sw s7, -36(s0)
+    li s1, 0
+    li s2, 0
+    li s3, 0
+    li s4, 0
+    li s5, 0
+    li s6, 0
+    li s7, 0
+    li a7, 5
+    ecall
+    mv s1, a0
+    mv a0, s1
+    call tt_fib
+    mv s2, a0
+    mv a0, s2
+    li a7, 1
+    ecall
+    li a0, 10
+    li a7, 11
+    ecall
+    li t0, 0
+    mv s2, t0
+    li t0, 0
+    mv s3, t0
+    li t0, 9
+    mv t1, s1
+    mul t0, t0, t1
+    mv s2, t0
+    li t0, 204
+    mv t1, s2
+    add t0, t0, t1
+    mv s3, t0
+    mv a0, s3
+    li a7, 1
+    ecall
+    li a0, 10
+    li a7, 11
+    ecall
+    li t0, 4
+    mv s3, t0
+    mv t0, s1
+    mv s2, t0
+    li t0, 0
+    mv s4, t0
+    li t0, 0
+    mv s5, t0
+    li t0, 0
+    mv s6, t0
+    li t0, 0
+    mv s7, t0
+    li t0, 0
+    mv s4, t0
+    j .Lmain_inline8_loopcond2
+.Lmain_inline8_loopbegin2:
+    mv t0, s4
+    li t1, 1
+    add t0, t0, t1
+    mv s5, t0
+    mv t0, s5
+    mv s4, t0
+.Lmain_inline8_loopcond2:
+    mv t0, s4
+    mv t1, s3
+    slt t0, t0, t1
+    mv s6, t0
+    mv t0, s6
+    bnez t0, .Lmain__2
+    j .Lmain_inline8_endloop2
+.Lmain__2:
+    mv t0, s2
+    mv t1, s4
+    add t0, t0, t1
+    mv s7, t0
+    mv t2, s4
+    slli t2, t2, 2
+    addi t3, s0, -52
+    add t2, t3, t2
+    mv t0, s7
+    sw t0, 0(t2)
+    j .Lmain_inline8_loopbegin2
+.Lmain_inline8_endloop2:
+    mv t0, s3
+    mv s1, t0
+    li t2, 0
+    slli t2, t2, 2
+    addi t3, s0, -52
+    add t2, t3, t2
+    lw t0, 0(t2)
+    mv s3, t0
+    li t2, 3
+    slli t2, t2, 2
+    addi t3, s0, -52
+    add t2, t3, t2
+    lw t0, 0(t2)
+    mv s1, t0
+    mv t0, s3
+    mv t1, s1
+    add t0, t0, t1
+    mv s4, t0
+    mv a0, s4
+    li a7, 1
+    ecall
+    li a0, 10
+    li a7, 11
+    ecall
+    li a0, 0
+    lw s1, -12(s0)
+    lw s2, -16(s0)
+    lw s3, -20(s0)
+    lw s4, -24(s0)
+    lw s5, -28(s0)
+    lw s6, -32(s0)
+    lw s7, -36(s0)
+    lw ra, -4(s0)
+    mv sp, s0
+    lw s0, -8(sp)
+    ret
+
+    .data
+tt_division_message:
+    .string "runtime error: division by zero\n"
+tt_overflow_message:
+    .string "runtime error: integer overflow\n"
+tt_bounds_line:
+    .string "runtime error: line "
+tt_bounds_index:
+    .string ": index "
+tt_bounds_array:
+    .string " out of bounds for array "
+tt_bounds_size:
+    .string " of size "
+tt_name_xs:
+    .string "xs"
@@ -0,0 +1,361 @@
+(module
+  (import "env" "out" (func $out (param i32)))
+  (import "env" "input" (func $input (result i32)))
+  (import "env" "division_by_zero" (func $division_by_zero))
+  (import "env" "out_of_bounds" (func $out_of_bounds (param i32 i32 i32 i32 i32)))
+  (memory (export "memory") 16)
+  (global $sp (mut i32) (i32.const 1048576))
+
+  (func $tt_div (param $a i32) (param $b i32) (result i32)
+    local.get $b
+    i32.eqz
+    if
+      call $division_by_zero
+      unreachable
+    end
+    local.get $b
+    i32.const -1
+    i32.eq
+    if
+      i32.const 0
+      local.get $a
+      call $tt_sub
+      return
+    end
+    local.get $a
+    local.get $b
+    i32.div_s)
+
+  (func $tt_mod (param $a i32) (param $b i32) (result i32)
+    local.get $b
+    i32.eqz
+    if
+      call $division_by_zero
+      unreachable
+    end
+    local.get $a
+    local.get $b
+    i32.rem_s)
+
+  (func $tt_add (param $a i32) (param $b i32) (result i32)
+    local.get $a
+    i64.extend_i32_s
+    local.get $b
+    i64.extend_i32_s
+    i64.add
+    call $tt_fit)
+
+  (func $tt_sub (param $a i32) (param $b i32) (result i32)
+    local.get $a
+    i64.extend_i32_s
+    local.get $b
+    i64.extend_i32_s
+    i64.sub
+    call $tt_fit)
+
+  (func $tt_mult (param $a i32) (param $b i32) (result i32)
+    local.get $a
+    i64.extend_i32_s
+    local.get $b
+    i64.extend_i32_s
+    i64.mul
+    call $tt_fit)
+
+  (func $tt_fit (param $x i64) (result i32)
+    local.get $x
+    i32.wrap_i64)
+  (data (i32.const 0) "xs")
+
+  (func $f_fib (param $v_n i32) (result i32)
+    (local $v__temp1 i32)
+    (local $v__temp2 i32)
+    (local $v__temp3 i32)
+    (local $v__temp4 i32)
+    (local $v__temp5 i32)
+    (local $v__temp6 i32)
+    local.get $v_n
+    i32.const 2
+    i32.lt_s
+    local.set $v__temp1
+    local.get $v__temp1
+    if
+      local.get $v_n
+      return
+    else
+      local.get $v_n
+      i32.const 1
+      i32.sub
+      local.set $v__temp3
+      local.get $v__temp3
+      call $f_fib
+      local.set $v__temp2
+      local.get $v_n
+      i32.const 2
+      i32.sub
+      local.set $v__temp5
+      local.get $v__temp5
+      call $f_fib
+      local.set $v__temp4
+      local.get $v__temp2
+      local.get $v__temp4
+      i32.add
+      local.set $v__temp6
+      local.get $v__temp6
+      return
+    end
+    unreachable)
+
+  (func $f_weigh (param $v_a i32) (param $v_b i32) (param $v_c i32) (param $v_d i32) (param $v_e i32) (param $v_f i32) (param $v_g i32) (param $v_h i32) (param $v_i i32) (result i32)
+    (local $v__temp7 i32)
+    (local $v__temp8 i32)
+    (local $v__temp9 i32)
+    (local $v__temp10 i32)
+    (local $v__temp11 i32)
+    (local $v__temp12 i32)
+    (local $v__temp13 i32)
+    (local $v__temp14 i32)
+    (local $v__temp15 i32)
+    (local $v__temp16 i32)
+    (local $v__temp17 i32)
+    (local $v__temp18 i32)
+    (local $v__temp19 i32)
+    (local $v__temp20 i32)
+    (local $v__temp21 i32)
+    (local $v__temp22 i32)
+    i32.const 2
+    local.get $v_b
+    i32.mul
+    local.set $v__temp7
+    local.get $v_a
+    local.get $v__temp7
+    i32.add
+    local.set $v__temp8
+    i32.const 3
+    local.get $v_c
+    i32.mul
+    local.set $v__temp9
+    local.get $v__temp8
+    local.get $v__temp9
+    i32.add
+    local.set $v__temp10
+    i32.const 4
+    local.get $v_d
+    i32.mul
+    local.set $v__temp11
+    local.get $v__temp10
+    local.get $v__temp11
+    i32.add
+    local.set $v__temp12
+    i32.const 5
+    local.get $v_e
+    i32.mul
+    local.set $v__temp13
+    local.get $v__temp12
+    local.get $v__temp13
+    i32.add
+    local.set $v__temp14
+    i32.const 6
+    local.get $v_f
+    i32.mul
+    local.set $v__temp15
+    local.get $v__temp14
+    local.get $v__temp15
+    i32.add
+    local.set $v__temp16
+    i32.const 7
+    local.get $v_g
+    i32.mul
+    local.set $v__temp17
+    local.get $v__temp16
+    local.get $v__temp17
+    i32.add
+    local.set $v__temp18
+    i32.const 8
+    local.get $v_h
+    i32.mul
+    local.set $v__temp19
+    local.get $v__temp18
+    local.get $v__temp19
+    i32.add
+    local.set $v__temp20
+    i32.const 9
+    local.get $v_i
+    i32.mul
+    local.set $v__temp21
+    local.get $v__temp20
+    local.get $v__temp21
+    i32.add
+    local.set $v__temp22
+    local.get $v__temp22
+    return
+    unreachable)
+
+  (func $f_fill (param $v_xs i32) (param $v__size_xs i32) (param $v_n i32) (param $v_start i32) (result i32)
+    (local $v_i i32)
+    (local $v__temp25 i32)
+    (local $v__temp23 i32)
+    (local $v__temp26 i32)
+    i32.const 0
+    local.set $v_i
+    loop $loopcond2
+      local.get $v_i
+      local.get $v_n
+      i32.lt_s
+      local.set $v__temp23
+      local.get $v__temp23
+      if
+        local.get $v_i
+        local.get $v__size_xs
+        i32.ge_u
+        if
+          local.get $v_i
+          local.get $v__size_xs
+          i32.const 0
+          i32.const 2
+          i32.const 14
+          call $out_of_bounds
+          unreachable
+        end
+        local.get $v_start
+        local.get $v_i
+        i32.add
+        local.set $v__temp26
+        local.get $v_xs
+        local.get $v_i
+        i32.const 4
+        i32.mul
+        i32.add
+        local.get $v__temp26
+        i32.store offset=0
+        local.get $v_i
+        i32.const 1
+        i32.add
+        local.set $v__temp25
+        local.get $v__temp25
+        local.set $v_i
+        br $loopcond2
+      else
+        local.get $v_n
+        return
+      end
+    end
+    unreachable)
+
+  (func $f_main (result i32)
+    (local $v_n i32)
+    (local $v__temp27 i32)
+    (local $v__inline7__temp21 i32)
+    (local $v__inline7__temp22 i32)
+    (local $v__inline8_n i32)
+    (local $v__inline8_start i32)
+    (local $v__inline8_i i32)
+    (local $v__inline8__temp25 i32)
+    (local $v__inline8__temp23 i32)
+    (local $v__inline8__temp26 i32)
+    (local $v__temp32 i32)
+    (local $v__temp33 i32)
+    (local $v__temp34 i32)
+    (local $fp i32)
+    global.get $sp
+    i32.const 16
+    i32.sub
+    local.tee $fp
+    global.set $sp
+    local.get $fp
+    i32.const 0
+    i32.const 16
+    memory.fill
+    call $input
+    local.set $v_n
+    local.get $v_n
+    call $f_fib
+    local.set $v__temp27
+    local.get $v__temp27
+    call $out
+    i32.const 0
+    local.set $v__inline7__temp21
+    i32.const 0
+    local.set $v__inline7__temp22
+    i32.const 9
+    local.get $v_n
+    i32.mul
+    local.set $v__inline7__temp21
+    i32.const 204
+    local.get $v__inline7__temp21
+    i32.add
+    local.set $v__inline7__temp22
+    local.get $v__inline7__temp22
+    call $out
+    i32.const 4
+    local.set $v__inline8_n
+    local.get $v_n
+    local.set $v__inline8_start
+    i32.const 0
+    local.set $v__inline8_i
+    i32.const 0
+    local.set $v__inline8__temp25
+    i32.const 0
+    local.set $v__inline8__temp23
+    i32.const 0
+    local.set $v__inline8__temp26
+    i32.const 0
+    local.set $v__inline8_i
+    loop $inline8_loopcond2
+      local.get $v__inline8_i
+      local.get $v__inline8_n
+      i32.lt_s
+      local.set $v__inline8__temp23
+      local.get $v__inline8__temp23
+      if
+        local.get $v__inline8_start
+        local.get $v__inline8_i
+        i32.add
+        local.set $v__inline8__temp26
+        local.get $fp
+        local.get $v__inline8_i
+        i32.const 4
+        i32.mul
+        i32.add
+        local.get $v__inline8__temp26
+        i32.store offset=0
+        local.get $v__inline8_i
+        i32.const 1
+        i32.add
+        local.set $v__inline8__temp25
+        local.get $v__inline8__temp25
+        local.set $v__inline8_i
+        br $inline8_loopcond2
+      else
+        local.get $v__inline8_n
+        local.set $v_n
+        local.get $fp
+        i32.const 0
+        i32.const 4
+        i32.mul
+        i32.add
+        i32.load offset=0
+        local.set $v__temp32
+        local.get $fp
+        i32.const 3
+        i32.const 4
+        i32.mul
+        i32.add
+        i32.load offset=0
+        local.set $v__temp33
+        local.get $v__temp32
+        local.get $v__temp33
+        i32.add
+        local.set $v__temp34
+        local.get $v__temp34
+        call $out
+        i32.const 0
+        local.get $fp
+        i32.const 16
+        i32.add
+        global.set $sp
+        return
+      end
+    end
+    unreachable)
+
+  (export "main" (func $f_main)))
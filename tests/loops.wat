@@ -0,0 +1,163 @@
+(module
+  (import "env" "out" (func $out (param i32)))
+  (import "env" "input" (func $input (result i32)))
+  (import "env" "division_by_zero" (func $division_by_zero))
+  (import "env" "out_of_bounds" (func $out_of_bounds (param i32 i32 i32 i32 i32)))
+  (memory (export "memory") 16)
+  (global $sp (mut i32) (i32.const 1048576))
+
+  (func $tt_div (param $a i32) (param $b i32) (result i32)
+    local.get $b
+    i32.eqz
+    if
+      call $division_by_zero
+      unreachable
+    end
+    local.get $b
+    i32.const -1
+    i32.eq
+    if
+      i32.const 0
+      local.get $a
+      call $tt_sub
+      return
+    end
+    local.get $a
+    local.get $b
+    i32.div_s)
+
+  (func $tt_mod (param $a i32) (param $b i32) (result i32)
+    local.get $b
+    i32.eqz
+    if
+      call $division_by_zero
+      unreachable
+    end
+    local.get $a
+    local.get $b
+    i32.rem_s)
+
+  (func $tt_add (param $a i32) (param $b i32) (result i32)
+    local.get $a
+    i64.extend_i32_s
+    local.get $b
+    i64.extend_i32_s
+    i64.add
+    call $tt_fit)
+
+  (func $tt_sub (param $a i32) (param $b i32) (result i32)
+    local.get $a
+    i64.extend_i32_s
+    local.get $b
+    i64.extend_i32_s
+    i64.sub
+    call $tt_fit)
+
+  (func $tt_mult (param $a i32) (param $b i32) (result i32)
+    local.get $a
+    i64.extend_i32_s
+    local.get $b
+    i64.extend_i32_s
+    i64.mul
+    call $tt_fit)
+
+  (func $tt_fit (param $x i64) (result i32)
+    local.get $x
+    i32.wrap_i64)
+
+  (func $f_main (result i32)
+    (local $v_n i32)
+    (local $v_total i32)
+    (local $v_i i32)
+    (local $v__temp1 i32)
+    (local $v__temp3 i32)
+    (local $v__temp5 i32)
+    (local $v_j i32)
+    (local $v__temp8 i32)
+    (local $v__temp6 i32)
+    (local $v__temp9 i32)
+    (local $v__temp11 i32)
+    (local $v__temp13 i32)
+    (local $v__temp14 i32)
+    i32.const 0
+    local.set $v_total
+    call $input
+    local.set $v_n
+    i32.const 0
+    local.set $v_i
+    loop $loopbegin1
+      local.get $v_i
+      local.get $v_n
+      i32.lt_s
+      local.set $v__temp1
+      local.get $v__temp1
+      if
+        local.get $v_total
+        local.get $v_i
+        i32.add
+        local.set $v__temp3
+        local.get $v__temp3
+        local.set $v_total
+        local.get $v_i
+        i32.const 1
+        i32.add
+        local.set $v__temp5
+        local.get $v__temp5
+        local.set $v_i
+        br $loopbegin1
+      else
+        local.get $v_n
+        local.set $v_j
+        loop $loopcond2
+          local.get $v_j
+          i32.const 0
+          i32.gt_s
+          local.set $v__temp6
+          local.get $v__temp6
+          if
+            local.get $v_j
+            local.get $v_j
+            i32.mul
+            local.set $v__temp9
+            local.get $v_total
+            local.get $v__temp9
+            i32.add
+            local.set $v__temp11
+            local.get $v__temp11
+            local.set $v_total
+            local.get $v_j
+            i32.const 1
+            i32.sub
+            local.set $v__temp8
+            local.get $v__temp8
+            local.set $v_j
+            br $loopcond2
+          else
+            loop $dobody3
+              local.get $v_total
+              i32.const 7
+              i32.sub
+              local.set $v__temp13
+              local.get $v__temp13
+              local.set $v_total
+              local.get $v__temp13
+              i32.const 100
+              i32.gt_s
+              local.set $v__temp14
+              local.get $v__temp14
+              if
+                br $dobody3
+              else
+                local.get $v_total
+                call $out
+                i32.const 0
+                return
+              end
+            end
+          end
+        end
+      end
+    end
+    unreachable)
+
+  (export "main" (func $f_main)))
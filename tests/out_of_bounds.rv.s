@@ -0,0 +1,134 @@
+    .text
+    .globl _start
+_start:
+    call tt_main
+    li a0, 0
+    li a7, 93
+    ecall
+
+tt_division_by_zero:
+    la a0, tt_division_message
+    li a7, 4
+    ecall
+    li a0, 1
+    li a7, 93
+    ecall
+
+tt_overflow:
+    la a0, tt_overflow_message
+    li a7, 4
+    ecall
+    li a0, 1
+    li a7, 93
+    ecall
+
+tt_out_of_bounds:
+    mv t0, a0
+    mv t1, a1
+    mv t2, a2
+    la a0, tt_bounds_line
+    li a7, 4
+    ecall
+    mv a0, a3
+    li a7, 1
+    ecall
+    la a0, tt_bounds_index
+    li a7, 4
+    ecall
+    mv a0, t0
+    li a7, 1
+    ecall
+    la a0, tt_bounds_array
+    li a7, 4
+    ecall
+    mv a0, t2
+    ecall
+    la a0, tt_bounds_size
+    ecall
+    mv a0, t1
+    li a7, 1
+    ecall
+    li a0, 10
+    li a7, 11
+    ecall
+    li a0, 1
+    li a7, 93
+    ecall
+
+    .globl tt_main
+tt_main:
+    mv t0, sp
+    addi sp, sp, -32
+    sw ra, -4(t0)
+    sw s0, -8(t0)
+    mv s0, t0
+    mv t0, sp
+    addi t1, s0, -8
+.Lmain__1:
+    sw zero, 0(t0)
+    addi t0, t0, 4
+    bne t0, t1, .Lmain__1
+    sw s1, -12(s0)
+    sw s2, -16(s0)
+    li s1, 0
+    li s2, 0
+    li a7, 5
+    ecall
+    mv s1, a0
+    mv a0, s1
+    li a1, 3
+    sltu t0, a0, a1
+    bnez t0, .Lmain__2
+    la a2, tt_name_xs
+    li a3, 5
+    call tt_out_of_bounds
+.Lmain__2:
+    mv t2, s1
+    slli t2, t2, 2
+    addi t3, s0, -28
+    add t2, t3, t2
+    li t0, 1
+    sw t0, 0(t2)
+    mv a0, s1
+    li a1, 3
+    sltu t0, a0, a1
+    bnez t0, .Lmain__3
+    la a2, tt_name_xs
+    li a3, 6
+    call tt_out_of_bounds
+.Lmain__3:
+    mv t2, s1
+    slli t2, t2, 2
+    addi t3, s0, -28
+    add t2, t3, t2
+    lw t0, 0(t2)
+    mv s2, t0
+    mv a0, s2
+    li a7, 1
+    ecall
+    li a0, 10
+    li a7, 11
+    ecall
+    li a0, 0
+    lw s1, -12(s0)
+    lw s2, -16(s0)
+    lw ra, -4(s0)
+    mv sp, s0
+    lw s0, -8(sp)
+    ret
+
+    .data
+tt_division_message:
+    .string "runtime error: division by zero\n"
+tt_overflow_message:
+    .string "runtime error: integer overflow\n"
+tt_bounds_line:
+    .string "runtime error: line "
+tt_bounds_index:
+    .string ": index "
+tt_bounds_array:
+    .string " out of bounds for array "
+tt_bounds_size:
+    .string " of size "
+tt_name_xs:
+    .string "xs"
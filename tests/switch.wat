@@ -0,0 +1,273 @@
+(module
+  (import "env" "out" (func $out (param i32)))
+  (import "env" "input" (func $input (result i32)))
+  (import "env" "division_by_zero" (func $division_by_zero))
+  (import "env" "out_of_bounds" (func $out_of_bounds (param i32 i32 i32 i32 i32)))
+  (memory (export "memory") 16)
+  (global $sp (mut i32) (i32.const 1048576))
+
+  (func $tt_div (param $a i32) (param $b i32) (result i32)
+    local.get $b
+    i32.eqz
+    if
+      call $division_by_zero
+      unreachable
+    end
+    local.get $b
+    i32.const -1
+    i32.eq
+    if
+      i32.const 0
+      local.get $a
+      call $tt_sub
+      return
+    end
+    local.get $a
+    local.get $b
+    i32.div_s)
+
+  (func $tt_mod (param $a i32) (param $b i32) (result i32)
+    local.get $b
+    i32.eqz
+    if
+      call $division_by_zero
+      unreachable
+    end
+    local.get $a
+    local.get $b
+    i32.rem_s)
+
+  (func $tt_add (param $a i32) (param $b i32) (result i32)
+    local.get $a
+    i64.extend_i32_s
+    local.get $b
+    i64.extend_i32_s
+    i64.add
+    call $tt_fit)
+
+  (func $tt_sub (param $a i32) (param $b i32) (result i32)
+    local.get $a
+    i64.extend_i32_s
+    local.get $b
+    i64.extend_i32_s
+    i64.sub
+    call $tt_fit)
+
+  (func $tt_mult (param $a i32) (param $b i32) (result i32)
+    local.get $a
+    i64.extend_i32_s
+    local.get $b
+    i64.extend_i32_s
+    i64.mul
+    call $tt_fit)
+
+  (func $tt_fit (param $x i64) (result i32)
+    local.get $x
+    i32.wrap_i64)
+
+  (func $f_dense (param $v_x i32) (result i32)
+    (local $v_r i32)
+    (local $v__temp3 i32)
+    (local $v__temp4 i32)
+    (local $v__temp5 i32)
+    (local $v__temp2 i32)
+    block $to_endswitch1
+      block $to_default1
+        i32.const 0
+        local.set $v_r
+        local.get $v_x
+        i32.const 1
+        i32.lt_s
+        local.set $v__temp3
+        local.get $v__temp3
+        if
+          br $to_default1
+        else
+          local.get $v_x
+          i32.const 5
+          i32.gt_s
+          local.set $v__temp4
+          local.get $v__temp4
+          if
+            br $to_default1
+          else
+            block $to_case1_0
+              block $to_case1_2
+                block $to_case1_1
+                  block $to_case1_3
+                    local.get $v_x
+                    i32.const 1
+                    i32.sub
+                    local.set $v__temp5
+                    local.get $v__temp5
+                    br_table $to_case1_0 $to_case1_1 $to_case1_2 $to_default1 $to_case1_3 $to_default1
+                  end
+                  i32.const 50
+                  local.set $v_r
+                  br $to_endswitch1
+                end
+                i32.const 20
+                local.set $v_r
+                br $to_case1_2
+              end
+              local.get $v_r
+              i32.const 30
+              i32.add
+              local.set $v__temp2
+              local.get $v__temp2
+              local.set $v_r
+              br $to_endswitch1
+            end
+            i32.const 10
+            local.set $v_r
+            br $to_endswitch1
+          end
+        end
+      end
+      i32.const 99
+      local.set $v_r
+      br $to_endswitch1
+    end
+    local.get $v_r
+    return
+    unreachable)
+
+  (func $f_sparse (param $v_x i32) (result i32)
+    (local $v__temp6 i32)
+    (local $v__temp7 i32)
+    local.get $v_x
+    i32.const 1000
+    i32.eq
+    local.set $v__temp6
+    local.get $v__temp6
+    if
+      i32.const 1
+      return
+    else
+      local.get $v_x
+      i32.const -4
+      i32.eq
+      local.set $v__temp7
+      local.get $v__temp7
+      if
+        i32.const 2
+        return
+      else
+        i32.const 3
+        return
+      end
+    end
+    unreachable)
+
+  (func $f_main (result i32)
+    (local $v_i i32)
+    (local $v__temp10 i32)
+    (local $v__temp8 i32)
+    (local $v__inline7_r i32)
+    (local $v__inline7__temp3 i32)
+    (local $v__inline7__temp4 i32)
+    (local $v__inline7__temp5 i32)
+    (local $v__inline7__temp2 i32)
+    (local $v__temp13 i32)
+    (local $v__temp15 i32)
+    i32.const 0
+    local.set $v_i
+    loop $loopcond3
+      local.get $v_i
+      i32.const 7
+      i32.lt_s
+      local.set $v__temp8
+      local.get $v__temp8
+      if
+        block $to_inline7_endswitch1
+          block $to_inline7_default1
+            i32.const 0
+            local.set $v__inline7_r
+            i32.const 0
+            local.set $v__inline7__temp3
+            i32.const 0
+            local.set $v__inline7__temp4
+            i32.const 0
+            local.set $v__inline7__temp5
+            i32.const 0
+            local.set $v__inline7__temp2
+            i32.const 0
+            local.set $v__inline7_r
+            local.get $v_i
+            i32.const 1
+            i32.lt_s
+            local.set $v__inline7__temp3
+            local.get $v__inline7__temp3
+            if
+              br $to_inline7_default1
+            else
+              local.get $v_i
+              i32.const 5
+              i32.gt_s
+              local.set $v__inline7__temp4
+              local.get $v__inline7__temp4
+              if
+                br $to_inline7_default1
+              else
+                block $to_inline7_case1_0
+                  block $to_inline7_case1_2
+                    block $to_inline7_case1_1
+                      block $to_inline7_case1_3
+                        local.get $v_i
+                        i32.const 1
+                        i32.sub
+                        local.set $v__inline7__temp5
+                        local.get $v__inline7__temp5
+                        br_table $to_inline7_case1_0 $to_inline7_case1_1 $to_inline7_case1_2 $to_inline7_default1 $to_inline7_case1_3 $to_inline7_default1
+                      end
+                      i32.const 50
+                      local.set $v__inline7_r
+                      br $to_inline7_endswitch1
+                    end
+                    i32.const 20
+                    local.set $v__inline7_r
+                    br $to_inline7_case1_2
+                  end
+                  local.get $v__inline7_r
+                  i32.const 30
+                  i32.add
+                  local.set $v__inline7__temp2
+                  local.get $v__inline7__temp2
+                  local.set $v__inline7_r
+                  br $to_inline7_endswitch1
+                end
+                i32.const 10
+                local.set $v__inline7_r
+                br $to_inline7_endswitch1
+              end
+            end
+          end
+          i32.const 99
+          local.set $v__inline7_r
+          br $to_inline7_endswitch1
+        end
+        local.get $v__inline7_r
+        call $out
+        local.get $v_i
+        i32.const 1
+        i32.add
+        local.set $v__temp10
+        local.get $v__temp10
+        local.set $v_i
+        br $loopcond3
+      else
+        i32.const 1
+        local.set $v__temp13
+        local.get $v__temp13
+        call $out
+        i32.const 2
+        local.set $v__temp15
+        local.get $v__temp15
+        call $out
+        i32.const 0
+        return
+      end
+    end
+    unreachable)
+
+  (export "main" (func $f_main)))
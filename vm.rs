@@ -0,0 +1,233 @@
+// dispatch loop for the bytecode in bytecode.rs. scalars of every active
+// call live in one value stack, each frame owning a window of `slots`
+// values; calls and returns push and pop frames instead of recursing, so
+// deep recursion only costs memory. arrays live on a heap of their own, the
+// globals first and then each frame's arrays above its caller's, so a frame
+// refers to its arrays by heap index and can be handed the caller's.
+use std::io::{BufRead, Write};
+use crate::bytecode::{Array, Op, Operand, Program};
+use crate::ir::{self, Overflow};
+
+const MAX_FRAMES: usize = 1_000_000;
+
+struct Frame {
+  function: usize,
+  pc: usize,
+  base: usize,
+  arrays: Vec<usize>, // heap index of each array
+  heap: usize,        // heap length on entry; everything above is this frame's
+  dest: u32,          // caller's slot for the return value
+}
+
+// whitespace-separated integers, 0 once they run out.
+struct Input<R: BufRead> {
+  source: R,
+  words: Vec<String>,
+}
+
+impl<R: BufRead> Input<R> {
+  fn next(&mut self) -> Result<i32, String> {
+    while self.words.is_empty() {
+      let mut line = String::new();
+      match self.source.read_line(&mut line) {
+        Ok(0) | Err(_) => return Ok(0),
+        Ok(_) => self.words = line.split_whitespace().rev().map(String::from).collect(),
+      }
+    }
+    let word = self.words.pop().unwrap();
+    match word.parse::<i32>() {
+      Ok(value) => Ok(value),
+      Err(_) => Err(format!("invalid input '{word}'")),
+    }
+  }
+}
+
+// codes follow the order of ir::BINARY_OPS.
+fn binary(code: u8, a: i32, b: i32, overflow: Overflow) -> Result<i32, &'static str> {
+  let (x, y) = (a as i64, b as i64);
+  let exact = match code {
+    0 => x + y,
+    1 => x - y,
+    2 => x * y,
+    3 | 4 if b == 0 => return Err("division by zero"),
+    3 => x / y,
+    4 => return Ok(a.wrapping_rem(b)),
+    5 => return Ok((a < b) as i32),
+    6 => return Ok((a <= b) as i32),
+    7 => return Ok((a > b) as i32),
+    8 => return Ok((a >= b) as i32),
+    9 => return Ok((a == b) as i32),
+    _ => return Ok((a != b) as i32),
+  };
+  return ir::fit(exact, overflow).ok_or("integer overflow");
+}
+
+fn index(array: &Vec<i32>, index: i32) -> Result<usize, String> {
+  if index < 0 || index as usize >= array.len() {
+    return Err(format!("array index {index} out of bounds for length {}", array.len()));
+  }
+  return Ok(index as usize);
+}
+
+pub fn run(program: &Program) -> Result<(), String> {
+  let mut stdout = std::io::BufWriter::new(std::io::stdout().lock());
+  let result = execute(program, std::io::stdin().lock(), &mut stdout, &mut 0);
+  let _ = stdout.flush();
+  return result;
+}
+
+// everything the program prints when it reads `input`, ending with the
+// runtime error if it stops on one. --check compares the backends with this.
+pub fn output(program: &Program, input: &str) -> String {
+  let mut stdout: Vec<u8> = vec![];
+  if let Err(message) = execute(program, input.as_bytes(), &mut stdout, &mut 0) {
+    stdout.extend(format!("runtime error: {message}\n").bytes());
+  }
+  return String::from_utf8_lossy(&stdout).to_string();
+}
+
+// how many ops the program executes when it reads `input`, up to and
+// including the one it stops on, as a measure of what the optimiser saves.
+pub fn steps(program: &Program, input: &str) -> u64 {
+  let mut steps = 0;
+  let _ = execute(program, input.as_bytes(), &mut std::io::sink(), &mut steps);
+  return steps;
+}
+
+fn execute(program: &Program, source: impl BufRead, stdout: &mut impl Write, steps: &mut u64) -> Result<(), String> {
+  let mut input = Input { source, words: vec![] };
+  let main = &program.functions[program.main as usize];
+  let mut stack: Vec<i32> = vec![0; main.slots as usize];
+  let mut heap: Vec<Vec<i32>> = program.globals.iter().map(|size| vec![0; *size as usize]).collect();
+  let start = heap.len();
+  heap.resize(start + main.arrays as usize, vec![]);
+  let mut frames: Vec<Frame> = vec![];
+  let mut frame = Frame { function: program.main as usize, pc: 0, base: 0, arrays: (start..heap.len()).collect(), heap: start, dest: 0 };
+  let mut ops = &main.ops;
+
+  loop {
+    let base = frame.base;
+    let value = |stack: &Vec<i32>, operand: &Operand| -> i32 {
+      match operand {
+        Operand::Slot(slot) => stack[base + *slot as usize],
+        Operand::Const(value) => *value,
+      }
+    };
+    let op = &ops[frame.pc];
+    frame.pc += 1;
+    *steps += 1;
+    match op {
+      Op::Mov(x, y) => stack[base + *x as usize] = value(&stack, y),
+      Op::Binary(code, x, y, z) => {
+        let (a, b) = (value(&stack, y), value(&stack, z));
+        let result = match binary(*code, a, b, program.overflow) {
+          Ok(result) => result,
+          Err(message) => return Err(String::from(message)),
+        };
+        stack[base + *x as usize] = result;
+      }
+      Op::Array(arr, size) => {
+        let size = value(&stack, size);
+        if size < 0 {
+          return Err(format!("negative array size {size}"));
+        }
+        // like scalars, arrays are zeroed once per call and not each time
+        // their declaration runs, so one declared in a loop keeps its values.
+        let array = &mut heap[frame.arrays[*arr as usize]];
+        if array.len() != size as usize {
+          *array = vec![0; size as usize];
+        }
+      }
+      Op::Store(arr, i, x) => {
+        let (i, x) = (value(&stack, i), value(&stack, x));
+        let array = &mut heap[frame.arrays[*arr as usize]];
+        let i = index(array, i)?;
+        array[i] = x;
+      }
+      Op::Load(x, arr, i) => {
+        let array = &heap[frame.arrays[*arr as usize]];
+        let i = index(array, value(&stack, i))?;
+        stack[base + *x as usize] = array[i];
+      }
+      Op::GlobalStore(arr, i, x) => {
+        let (i, x) = (value(&stack, i), value(&stack, x));
+        let array = &mut heap[*arr as usize];
+        let i = index(array, i)?;
+        array[i] = x;
+      }
+      Op::GlobalLoad(x, arr, i) => {
+        let array = &heap[*arr as usize];
+        let i = index(array, value(&stack, i))?;
+        stack[base + *x as usize] = array[i];
+      }
+      Op::Check(i, size, arr, line) => {
+        let (i, size) = (value(&stack, i), value(&stack, size));
+        if i < 0 || i >= size {
+          return Err(ir::bounds_error(arr, i, size, *line));
+        }
+      }
+      Op::Out(x) => {
+        let _ = writeln!(stdout, "{}", value(&stack, x));
+      }
+      Op::Input(x) => {
+        let _ = stdout.flush();
+        stack[base + *x as usize] = input.next()?;
+      }
+      Op::Call(x, f, args, arrays) => {
+        if frames.len() >= MAX_FRAMES {
+          return Err(String::from("call stack overflow"));
+        }
+        let callee = &program.functions[*f as usize];
+        let new_base = stack.len();
+        stack.resize(new_base + callee.slots as usize, 0);
+        for (k, arg) in args.iter().enumerate() {
+          stack[new_base + k] = value(&stack, arg);
+        }
+        let mut handles: Vec<usize> = arrays.iter().map(|arr| match arr {
+          Array::Local(arr) => frame.arrays[*arr as usize],
+          Array::Global(arr) => *arr as usize,
+        }).collect();
+        let heap_base = heap.len();
+        heap.resize(heap_base + (callee.arrays - callee.array_params) as usize, vec![]);
+        handles.extend(heap_base..heap.len());
+        let caller = std::mem::replace(&mut frame, Frame {
+          function: *f as usize,
+          pc: 0,
+          base: new_base,
+          arrays: handles,
+          heap: heap_base,
+          dest: *x,
+        });
+        frames.push(caller);
+        ops = &callee.ops;
+      }
+      Op::Ret(x) => {
+        let result = value(&stack, x);
+        let dest = frame.dest;
+        stack.truncate(base);
+        heap.truncate(frame.heap);
+        frame = match frames.pop() {
+          Some(caller) => caller,
+          None => return Ok(()),
+        };
+        stack[frame.base + dest as usize] = result;
+        ops = &program.functions[frame.function].ops;
+      }
+      Op::Jmp(target) => frame.pc = *target as usize,
+      Op::BranchIf(x, target) => {
+        if value(&stack, x) != 0 {
+          frame.pc = *target as usize;
+        }
+      }
+      Op::BranchIfn(x, target) => {
+        if value(&stack, x) == 0 {
+          frame.pc = *target as usize;
+        }
+      }
+      Op::JumpTable(x, default, targets) => {
+        let target = usize::try_from(value(&stack, x)).ok().and_then(|k| targets.get(k)).unwrap_or(default);
+        frame.pc = *target as usize;
+      }
+    }
+  }
+}
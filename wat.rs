@@ -0,0 +1,569 @@
+// WebAssembly backend. writes a text-format module: one wasm function per
+// %func with its variables as i32 locals, arrays in a stack frame in linear
+// memory, globals at the bottom of memory below the stack, array parameters
+// as i32 params holding an address, and %out/%input as
+// functions imported from the host ("env" "out", "env" "input",
+// "env" "division_by_zero", "env" "out_of_bounds", and "env" "overflow" when
+// overflow traps). the array names failed checks report are data right after
+// the globals. add, sub and mult go through helpers working in i64 unless
+// overflow wraps.
+//
+// wasm has no goto, so control flow is rebuilt as nested block/loop/if from
+// the dominator tree: a loop for every block that a back edge jumps to (the
+// :loopbegin labels), and a block ending right before every block with more
+// than one forward way in (:endloop, :endif, ...), so that every jump becomes
+// a br to an enclosing label. the parser only produces reducible control
+// flow, which is all this needs.
+use std::collections::HashMap;
+use crate::cfg::{self, Cfg};
+use crate::ir::{self, Function, Instr, Overflow};
+
+// the stack starts at the top of the 16 pages and grows down, so globals may
+// take at most half of memory.
+const GLOBALS_LIMIT: i32 = 524288;
+
+const PRELUDE: &str = "(module
+  (import \"env\" \"out\" (func $out (param i32)))
+  (import \"env\" \"input\" (func $input (result i32)))
+  (import \"env\" \"division_by_zero\" (func $division_by_zero))
+  (import \"env\" \"out_of_bounds\" (func $out_of_bounds (param i32 i32 i32 i32 i32)))
+";
+
+const RUNTIME: &str = "  (memory (export \"memory\") 16)
+  (global $sp (mut i32) (i32.const 1048576))
+
+  (func $tt_div (param $a i32) (param $b i32) (result i32)
+    local.get $b
+    i32.eqz
+    if
+      call $division_by_zero
+      unreachable
+    end
+    local.get $b
+    i32.const -1
+    i32.eq
+    if
+      i32.const 0
+      local.get $a
+      call $tt_sub
+      return
+    end
+    local.get $a
+    local.get $b
+    i32.div_s)
+
+  (func $tt_mod (param $a i32) (param $b i32) (result i32)
+    local.get $b
+    i32.eqz
+    if
+      call $division_by_zero
+      unreachable
+    end
+    local.get $a
+    local.get $b
+    i32.rem_s)
+
+  (func $tt_add (param $a i32) (param $b i32) (result i32)
+    local.get $a
+    i64.extend_i32_s
+    local.get $b
+    i64.extend_i32_s
+    i64.add
+    call $tt_fit)
+
+  (func $tt_sub (param $a i32) (param $b i32) (result i32)
+    local.get $a
+    i64.extend_i32_s
+    local.get $b
+    i64.extend_i32_s
+    i64.sub
+    call $tt_fit)
+
+  (func $tt_mult (param $a i32) (param $b i32) (result i32)
+    local.get $a
+    i64.extend_i32_s
+    local.get $b
+    i64.extend_i32_s
+    i64.mul
+    call $tt_fit)
+";
+
+// $tt_fit turns an exact i64 result into the i32 the overflow mode asks for.
+fn fit(overflow: Overflow) -> &'static str {
+  match overflow {
+    Overflow::Wrap => "
+  (func $tt_fit (param $x i64) (result i32)
+    local.get $x
+    i32.wrap_i64)
+",
+    Overflow::Trap => "
+  (func $tt_fit (param $x i64) (result i32)
+    local.get $x
+    local.get $x
+    i32.wrap_i64
+    i64.extend_i32_s
+    i64.ne
+    if
+      call $overflow
+      unreachable
+    end
+    local.get $x
+    i32.wrap_i64)
+",
+    Overflow::Saturate => "
+  (func $tt_fit (param $x i64) (result i32)
+    local.get $x
+    i64.const 2147483647
+    i64.gt_s
+    if
+      i32.const 2147483647
+      return
+    end
+    local.get $x
+    i64.const -2147483648
+    i64.lt_s
+    if
+      i32.const -2147483648
+      return
+    end
+    local.get $x
+    i32.wrap_i64)
+",
+  }
+}
+
+fn var(name: &String) -> String {
+  format!("$v_{name}")
+}
+
+struct Emitter<'a> {
+  function: &'a Function,
+  cfg: Cfg,
+  rpo: Vec<usize>, // position of each block in reverse post-order
+  labels: HashMap<String, usize>,
+  children: Vec<Vec<usize>>,
+  merge: Vec<bool>,
+  header: Vec<bool>,
+  arrays: HashMap<String, i32>, // array -> offset in the frame
+  globals: &'a HashMap<String, i32>, // global -> address in memory
+  overflow: Overflow,
+  names: &'a HashMap<String, i32>,   // array name -> address of its text
+  frame_size: i32,
+  code: String,
+  depth: usize,
+}
+
+impl<'a> Emitter<'a> {
+  fn line(&mut self, text: &str) {
+    self.code += &"  ".repeat(self.depth);
+    self.code += text;
+    self.code += "\n";
+  }
+
+  fn name(&self, b: usize) -> String {
+    match self.function.body.get(self.cfg.blocks[b].start) {
+      Some(Instr::Label(label)) => label[1..].to_string(),
+      _ => format!("block{b}"),
+    }
+  }
+
+  fn get(&mut self, operand: &String) {
+    if ir::is_constant(operand) {
+      self.line(&format!("i32.const {operand}"));
+    } else {
+      self.line(&format!("local.get {}", var(operand)));
+    }
+  }
+
+  // pushes the address of arr[index] and returns the offset for the load or store.
+  fn element(&mut self, arr: &String, index: &String) -> Result<i32, String> {
+    let offset = match self.arrays.get(arr) {
+      Some(offset) => {
+        let offset = *offset;
+        self.line("local.get $fp");
+        offset
+      }
+      None if self.function.array_params.contains(arr) => {
+        self.line(&format!("local.get {}", var(arr)));
+        0
+      }
+      None => match self.globals.get(arr) {
+        Some(address) => {
+          let address = *address;
+          self.get(index);
+          self.line("i32.const 4");
+          self.line("i32.mul");
+          return Ok(address);
+        }
+        None => return Err(format!("unknown array {arr} in {}", self.function.name)),
+      },
+    };
+    self.get(index);
+    self.line("i32.const 4");
+    self.line("i32.mul");
+    self.line("i32.add");
+    return Ok(offset);
+  }
+
+  // pushes the address of element 0, for passing an array to a call.
+  fn address(&mut self, arr: &String) -> Result<(), String> {
+    if let Some(offset) = self.arrays.get(arr) {
+      let offset = *offset;
+      self.line("local.get $fp");
+      self.line(&format!("i32.const {offset}"));
+      self.line("i32.add");
+    } else if self.function.array_params.contains(arr) {
+      self.line(&format!("local.get {}", var(arr)));
+    } else if let Some(address) = self.globals.get(arr) {
+      let address = *address;
+      self.line(&format!("i32.const {address}"));
+    } else {
+      return Err(format!("unknown array {arr} in {}", self.function.name));
+    }
+    Ok(())
+  }
+
+  fn ret(&mut self) {
+    if self.frame_size > 0 {
+      self.line("local.get $fp");
+      self.line(&format!("i32.const {}", self.frame_size));
+      self.line("i32.add");
+      self.line("global.set $sp");
+    }
+    self.line("return");
+  }
+
+  fn instr(&mut self, instr: &Instr) -> Result<(), String> {
+    match instr {
+      Instr::Int(_) | Instr::IntArray(_, _) | Instr::Label(_) => {}
+      Instr::Mov(x, y) => {
+        self.get(y);
+        self.line(&format!("local.set {}", var(x)));
+      }
+      Instr::Store(arr, i, x) => {
+        let offset = self.element(arr, i)?;
+        self.get(x);
+        self.line(&format!("i32.store offset={offset}"));
+      }
+      Instr::Load(x, arr, i) => {
+        let offset = self.element(arr, i)?;
+        self.line(&format!("i32.load offset={offset}"));
+        self.line(&format!("local.set {}", var(x)));
+      }
+      Instr::Binary(op, x, y, z) => {
+        self.get(y);
+        self.get(z);
+        let operation = match op.as_str() {
+          "add" if self.overflow == Overflow::Wrap => "i32.add",
+          "sub" if self.overflow == Overflow::Wrap => "i32.sub",
+          "mult" if self.overflow == Overflow::Wrap => "i32.mul",
+          "add" => "call $tt_add",
+          "sub" => "call $tt_sub",
+          "mult" => "call $tt_mult",
+          "div" => "call $tt_div",
+          "mod" => "call $tt_mod",
+          "lt" => "i32.lt_s",
+          "le" => "i32.le_s",
+          "gt" => "i32.gt_s",
+          "ge" => "i32.ge_s",
+          "eq" => "i32.eq",
+          _ => "i32.ne",
+        };
+        self.line(operation);
+        self.line(&format!("local.set {}", var(x)));
+      }
+      Instr::Out(x) => {
+        self.get(x);
+        self.line("call $out");
+      }
+      Instr::Input(x) => {
+        self.line("call $input");
+        self.line(&format!("local.set {}", var(x)));
+      }
+      Instr::Call(x, f, args) => {
+        for arg in args {
+          match ir::array_arg(arg) {
+            Some(arr) => self.address(&arr.to_string())?,
+            None => self.get(arg),
+          }
+        }
+        self.line(&format!("call $f_{f}"));
+        self.line(&format!("local.set {}", var(x)));
+      }
+      Instr::Check(i, size, arr, line) => {
+        // the host is given index, size, the name's address and length, and the line.
+        let address = self.names[arr];
+        self.get(i);
+        self.get(size);
+        self.line("i32.ge_u");
+        self.line("if");
+        self.depth += 1;
+        self.get(i);
+        self.get(size);
+        self.line(&format!("i32.const {address}"));
+        self.line(&format!("i32.const {}", arr.len()));
+        self.line(&format!("i32.const {line}"));
+        self.line("call $out_of_bounds");
+        self.line("unreachable");
+        self.depth -= 1;
+        self.line("end");
+      }
+      Instr::Ret(x) => {
+        self.get(x);
+        self.ret();
+      }
+      Instr::Jmp(_) | Instr::BranchIf(_, _) | Instr::BranchIfn(_, _) | Instr::JumpTable(_, _, _) => {}
+      Instr::Phi(_, _) => return Err(String::from("phi instructions must be removed before code generation")),
+    }
+    Ok(())
+  }
+
+  // code for block `x` and everything it dominates.
+  fn tree(&mut self, x: usize) -> Result<(), String> {
+    let mut merges: Vec<usize> = self.children[x].iter().cloned().filter(|c| self.merge[*c]).collect();
+    merges.sort_by(|a, b| self.rpo[*b].cmp(&self.rpo[*a]));
+    if self.header[x] {
+      self.line(&format!("loop ${}", self.name(x)));
+      self.depth += 1;
+      self.within(x, &merges)?;
+      self.depth -= 1;
+      self.line("end");
+      return Ok(());
+    }
+    return self.within(x, &merges);
+  }
+
+  // merge blocks come after the code that jumps to them, so each one is
+  // preceded by a block that the jumps can leave. the one that comes last
+  // gets the outermost block.
+  fn within(&mut self, x: usize, merges: &[usize]) -> Result<(), String> {
+    if let Some((&y, rest)) = merges.split_first() {
+      self.line(&format!("block $to_{}", self.name(y)));
+      self.depth += 1;
+      self.within(x, rest)?;
+      self.depth -= 1;
+      self.line("end");
+      return self.tree(y);
+    }
+
+    let (start, end) = (self.cfg.blocks[x].start, self.cfg.blocks[x].end);
+    for instr in &self.function.body[start..end] {
+      self.instr(instr)?;
+    }
+    let next = x + 1;
+    match self.function.body[start..end].last() {
+      Some(Instr::Ret(_)) => {}
+      Some(Instr::Jmp(l)) => {
+        let target = self.target(l)?;
+        self.branch(x, target)?;
+      }
+      Some(Instr::BranchIf(c, l)) | Some(Instr::BranchIfn(c, l)) => {
+        let target = self.target(l)?;
+        if target == next {
+          return self.branch(x, next);
+        }
+        let (taken, not_taken) = if matches!(self.function.body[end - 1], Instr::BranchIf(_, _)) { (target, next) } else { (next, target) };
+        self.get(c);
+        self.line("if");
+        self.depth += 1;
+        self.branch(x, taken)?;
+        self.depth -= 1;
+        self.line("else");
+        self.depth += 1;
+        self.branch(x, not_taken)?;
+        self.depth -= 1;
+        self.line("end");
+      }
+      Some(Instr::JumpTable(c, default, table)) => {
+        let mut names: Vec<String> = vec![];
+        for l in table.iter().chain(std::iter::once(default)) {
+          let target = self.target(l)?;
+          names.push(self.enclosing(x, target));
+        }
+        self.get(c);
+        self.line(&format!("br_table {}", names.join(" ")));
+      }
+      _ => {
+        if next < self.cfg.blocks.len() {
+          self.branch(x, next)?;
+        } else {
+          self.line("i32.const 0");
+          self.ret();
+        }
+      }
+    }
+    Ok(())
+  }
+
+  fn branch(&mut self, x: usize, target: usize) -> Result<(), String> {
+    if self.rpo[target] <= self.rpo[x] || self.merge[target] {
+      let name = self.enclosing(x, target);
+      self.line(&format!("br {name}"));
+    } else {
+      self.tree(target)?;
+    }
+    Ok(())
+  }
+
+  // label of the loop or block that a br from `x` to `target` leaves through.
+  fn enclosing(&self, x: usize, target: usize) -> String {
+    if self.rpo[target] <= self.rpo[x] {
+      return format!("${}", self.name(target));
+    }
+    return format!("$to_{}", self.name(target));
+  }
+
+  fn target(&self, label: &String) -> Result<usize, String> {
+    match self.labels.get(label) {
+      Some(b) => Ok(*b),
+      None => Err(format!("unknown label {label} in {}", self.function.name)),
+    }
+  }
+}
+
+fn generate_function(function: &Function, globals: &HashMap<String, i32>, names: &HashMap<String, i32>, overflow: Overflow) -> Result<String, String> {
+  let cfg = cfg::build(function);
+  let blocks = cfg.blocks.len();
+  let mut rpo = vec![usize::MAX; blocks];
+  for (position, &b) in cfg.order.iter().enumerate() {
+    rpo[b] = position;
+  }
+  let mut labels: HashMap<String, usize> = HashMap::new();
+  for (b, block) in cfg.blocks.iter().enumerate() {
+    if let Some(Instr::Label(label)) = function.body.get(block.start) {
+      labels.insert(label.clone(), b);
+    }
+  }
+
+  let mut merge = vec![false; blocks];
+  let mut header = vec![false; blocks];
+  for &b in &cfg.order {
+    let mut forward = 0;
+    for &p in &cfg.blocks[b].preds {
+      if !cfg.reachable(p) {
+        continue;
+      }
+      if rpo[p] < rpo[b] {
+        forward += 1;
+      } else if cfg.dominates(b, p) {
+        header[b] = true;
+      } else {
+        return Err(format!("{} has control flow that cannot be expressed in WebAssembly", function.name));
+      }
+    }
+    // br_table can only name enclosing labels, so jump table targets get a block like merges.
+    let from_table = cfg.blocks[b].preds.iter().any(|&p| {
+      let block = &cfg.blocks[p];
+      block.end > block.start && matches!(function.body[block.end - 1], Instr::JumpTable(_, _, _))
+    });
+    merge[b] = forward > 1 || from_table;
+  }
+
+  let mut arrays: HashMap<String, i32> = HashMap::new();
+  let mut frame_size = 0;
+  let mut locals: Vec<&String> = vec![];
+  for instr in &function.body {
+    if let Instr::IntArray(x, size) = instr {
+      let length: i32 = match size.parse() {
+        Ok(length) if length > 0 => length,
+        _ => return Err(format!("array {x} in {} needs a constant size for WebAssembly", function.name)),
+      };
+      if !arrays.contains_key(x) {
+        arrays.insert(x.clone(), frame_size);
+        frame_size += 4 * length;
+      }
+      continue;
+    }
+    let mut names: Vec<&String> = ir::uses(instr);
+    names.extend(ir::def(instr));
+    if let Instr::Int(x) = instr {
+      names.push(x);
+    }
+    for name in names {
+      if !ir::is_constant(name) && !function.params.contains(name) && !locals.contains(&name) {
+        locals.push(name);
+      }
+    }
+  }
+
+  let params: Vec<String> = function.params.iter().map(|p| format!(" (param {} i32)", var(p))).collect();
+  let mut code = format!("  (func $f_{}{} (result i32)\n", function.name, params.concat());
+  for name in locals {
+    code += &format!("    (local {} i32)\n", var(name));
+  }
+  let mut emitter = Emitter {
+    function,
+    children: cfg.dominator_tree(),
+    cfg,
+    rpo,
+    labels,
+    merge,
+    header,
+    arrays,
+    globals,
+    overflow,
+    names,
+    frame_size,
+    code,
+    depth: 2,
+  };
+  if frame_size > 0 {
+    // arrays start out as 0, like in the VM.
+    emitter.code += "    (local $fp i32)\n";
+    emitter.line("global.get $sp");
+    emitter.line(&format!("i32.const {frame_size}"));
+    emitter.line("i32.sub");
+    emitter.line("local.tee $fp");
+    emitter.line("global.set $sp");
+    emitter.line("local.get $fp");
+    emitter.line("i32.const 0");
+    emitter.line(&format!("i32.const {frame_size}"));
+    emitter.line("memory.fill");
+  }
+  emitter.tree(0)?;
+  // every path has returned or branched by now.
+  emitter.line("unreachable)");
+  return Ok(emitter.code);
+}
+
+pub fn generate(program: &ir::Program) -> Result<String, String> {
+  // memory starts out zeroed, so globals need no initialisation.
+  let mut globals: HashMap<String, i32> = HashMap::new();
+  let mut size = 0;
+  for global in &program.globals {
+    globals.insert(global.name.clone(), size);
+    size += 4 * global.size;
+    if size > GLOBALS_LIMIT {
+      return Err(String::from("globals do not fit in WebAssembly memory"));
+    }
+  }
+  let mut names: HashMap<String, i32> = HashMap::new();
+  let mut data = String::new();
+  for function in &program.functions {
+    for instr in &function.body {
+      if let Instr::Check(_, _, arr, _) = instr {
+        if !names.contains_key(arr) {
+          names.insert(arr.clone(), size);
+          data += &format!("  (data (i32.const {size}) \"{arr}\")\n");
+          size += arr.len() as i32;
+          if size > GLOBALS_LIMIT {
+            return Err(String::from("globals do not fit in WebAssembly memory"));
+          }
+        }
+      }
+    }
+  }
+  let mut code = String::from(PRELUDE);
+  if program.overflow == Overflow::Trap {
+    code += "  (import \"env\" \"overflow\" (func $overflow))\n";
+  }
+  code += RUNTIME;
+  code += fit(program.overflow);
+  code += &data;
+  for function in &program.functions {
+    code += "\n";
+    code += &generate_function(function, &globals, &names, program.overflow)?;
+  }
+  code += "\n  (export \"main\" (func $f_main)))\n";
+  return Ok(code);
+}
@@ -0,0 +1,536 @@
+// x86-64 backend. emits GNU assembler for Linux (System V ABI). variables
+// live in the callee-saved registers regalloc.rs assigns them, or in the
+// stack frame when they are spilled; arrays always live in the frame, array
+// parameters as 8-byte addresses in it, and globals in .bss. %out and %input
+// go through libc's printf and scanf (plus strtol to check what was read), and
+// the result links with the system `cc`.
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::process::Command;
+use crate::ir::{self, Function, Instr, Overflow};
+use crate::regalloc::{self, Allocation};
+
+const ARG_REGS: [&str; 6] = ["%edi", "%esi", "%edx", "%ecx", "%r8d", "%r9d"];
+const ARG_REGS_64: [&str; 6] = ["%rdi", "%rsi", "%rdx", "%rcx", "%r8", "%r9"];
+
+// callee-saved, so values in them survive calls, printf and scanf.
+pub const ALLOCATABLE: [&str; 5] = ["%ebx", "%r12d", "%r13d", "%r14d", "%r15d"];
+const SAVED: [&str; 5] = ["%rbx", "%r12", "%r13", "%r14", "%r15"];
+
+struct Frame {
+  slots: HashMap<String, i32>,  // variable -> offset from %rbp
+  arrays: HashMap<String, i32>, // array -> offset of element 0 from %rbp
+  pointers: HashMap<String, i32>, // array parameter -> offset of its address from %rbp
+  registers: HashMap<String, &'static str>,
+  saves: Vec<(&'static str, i32)>, // callee-saved register -> where it is kept
+  size: i32,
+}
+
+fn build_frame(function: &Function, allocation: &Allocation) -> Result<Frame, String> {
+  let mut frame = Frame { slots: HashMap::new(), arrays: HashMap::new(), pointers: HashMap::new(), registers: HashMap::new(), saves: vec![], size: 0 };
+  for (var, register) in &allocation.registers {
+    frame.registers.insert(var.clone(), ALLOCATABLE[*register]);
+  }
+  for (register, saved) in SAVED.iter().enumerate().take(ALLOCATABLE.len()) {
+    if allocation.registers.values().any(|r| *r == register) {
+      frame.size += 8;
+      frame.saves.push((*saved, -frame.size));
+    }
+  }
+  for param in &function.array_params {
+    frame.size += 8;
+    frame.pointers.insert(param.clone(), -frame.size);
+  }
+  let mut names: Vec<&String> = function.params.iter().filter(|param| !function.array_params.contains(param)).collect();
+  for instr in &function.body {
+    match instr {
+      Instr::Int(x) => names.push(x),
+      Instr::IntArray(x, size) => {
+        let length: i32 = match size.parse() {
+          Ok(length) if length > 0 => length,
+          _ => return Err(format!("array {x} in {} needs a constant size for native code", function.name)),
+        };
+        if !frame.arrays.contains_key(x) {
+          frame.size += 4 * length;
+          frame.arrays.insert(x.clone(), -frame.size);
+        }
+      }
+      _ => names.extend(ir::def(instr)),
+    }
+  }
+  for name in names {
+    if !frame.slots.contains_key(name) && !frame.arrays.contains_key(name) && !frame.registers.contains_key(name) {
+      frame.size += 4;
+      frame.slots.insert(name.clone(), -frame.size);
+    }
+  }
+  frame.size = (frame.size + 15) / 16 * 16;
+  return Ok(frame);
+}
+
+fn label(function: &Function, name: &String) -> String {
+  format!(".L{}_{}", function.name, &name[1..])
+}
+
+fn symbol(name: &String) -> String {
+  format!("tt_{name}")
+}
+
+fn global_symbol(name: &String) -> String {
+  format!("tt_global_{name}")
+}
+
+fn name_symbol(arr: &String) -> String {
+  format!("tt_name_{arr}")
+}
+
+struct Emitter<'a> {
+  function: &'a Function,
+  globals: &'a HashSet<String>,
+  overflow: Overflow,
+  frame: Frame,
+  code: String,
+  count: usize,
+}
+
+impl<'a> Emitter<'a> {
+  fn line(&mut self, text: &str) {
+    self.code += "    ";
+    self.code += text;
+    self.code += "\n";
+  }
+
+  fn slot(&self, name: &String) -> Result<String, String> {
+    match self.frame.slots.get(name) {
+      Some(offset) => Ok(format!("{offset}(%rbp)")),
+      None => Err(format!("unknown variable {name} in {}", self.function.name)),
+    }
+  }
+
+  fn load(&mut self, operand: &String, reg: &str) -> Result<(), String> {
+    if ir::is_constant(operand) {
+      self.line(&format!("movl ${operand}, {reg}"));
+    } else if let Some(register) = self.frame.registers.get(operand) {
+      let register = *register;
+      self.line(&format!("movl {register}, {reg}"));
+    } else {
+      let slot = self.slot(operand)?;
+      self.line(&format!("movl {slot}, {reg}"));
+    }
+    Ok(())
+  }
+
+  fn store(&mut self, reg: &str, dest: &String) -> Result<(), String> {
+    if let Some(register) = self.frame.registers.get(dest) {
+      let register = *register;
+      self.line(&format!("movl {reg}, {register}"));
+      return Ok(());
+    }
+    let slot = self.slot(dest)?;
+    self.line(&format!("movl {reg}, {slot}"));
+    Ok(())
+  }
+
+  // address of element 0 of arr in `reg`.
+  fn address(&mut self, arr: &String, reg: &str) -> Result<(), String> {
+    if let Some(base) = self.frame.arrays.get(arr) {
+      let base = *base;
+      self.line(&format!("leaq {base}(%rbp), {reg}"));
+    } else if let Some(offset) = self.frame.pointers.get(arr) {
+      let offset = *offset;
+      self.line(&format!("movq {offset}(%rbp), {reg}"));
+    } else if self.globals.contains(arr) {
+      self.line(&format!("leaq {}(%rip), {reg}", global_symbol(arr)));
+    } else {
+      return Err(format!("unknown array {arr} in {}", self.function.name));
+    }
+    Ok(())
+  }
+
+  // address of arr[index] with the index already in %rcx.
+  fn element(&mut self, arr: &String, index: &String) -> Result<String, String> {
+    self.load(index, "%ecx")?;
+    self.line("movslq %ecx, %rcx");
+    if let Some(base) = self.frame.arrays.get(arr) {
+      return Ok(format!("{base}(%rbp,%rcx,4)"));
+    }
+    self.address(arr, "%r11")?;
+    Ok(String::from("(%r11,%rcx,4)"))
+  }
+
+  fn fresh(&mut self) -> String {
+    self.count += 1;
+    format!(".L{}__{}", self.function.name, self.count)
+  }
+
+  // after an add, sub, imul or neg that set the overflow flag: stop, or
+  // replace %eax with the int closest to the exact result. that has the sign
+  // of y for add and sub, of y * z for mult, and is positive for a negated y.
+  fn overflowed(&mut self, op: &str, y: &String) -> Result<(), String> {
+    match self.overflow {
+      Overflow::Wrap => {}
+      Overflow::Trap => self.line("jo tt_overflow"),
+      Overflow::Saturate => {
+        let fits = self.fresh();
+        self.line(&format!("jno {fits}"));
+        if op == "neg" {
+          self.line("movl $2147483647, %eax");
+        } else {
+          self.load(y, "%eax")?;
+          if op == "mult" {
+            self.line("xorl %ecx, %eax");
+          }
+          self.line("sarl $31, %eax");
+          self.line("xorl $2147483647, %eax");
+        }
+        self.code += &format!("{fits}:\n");
+      }
+    }
+    Ok(())
+  }
+
+  fn binary(&mut self, op: &String, x: &String, y: &String, z: &String) -> Result<(), String> {
+    self.load(y, "%eax")?;
+    self.load(z, "%ecx")?;
+    match op.as_str() {
+      "add" | "sub" | "mult" => {
+        let instr = match op.as_str() {
+          "add" => "addl %ecx, %eax",
+          "sub" => "subl %ecx, %eax",
+          _ => "imull %ecx, %eax",
+        };
+        self.line(instr);
+        self.overflowed(op, y)?;
+      }
+      "div" | "mod" => {
+        // idiv faults on x / 0 and on INT_MIN / -1; the second is a negation that overflows.
+        let plain = self.fresh();
+        let done = self.fresh();
+        self.line("testl %ecx, %ecx");
+        self.line("je tt_division_by_zero");
+        self.line("cmpl $-1, %ecx");
+        self.line(&format!("jne {plain}"));
+        if op == "div" {
+          self.line("negl %eax");
+          self.overflowed("neg", y)?;
+        } else {
+          self.line("xorl %eax, %eax");
+        }
+        self.line(&format!("jmp {done}"));
+        self.code += &format!("{plain}:\n");
+        self.line("cltd");
+        self.line("idivl %ecx");
+        if op == "mod" {
+          self.line("movl %edx, %eax");
+        }
+        self.code += &format!("{done}:\n");
+      }
+      _ => {
+        let set = match op.as_str() {
+          "lt" => "setl",
+          "le" => "setle",
+          "gt" => "setg",
+          "ge" => "setge",
+          "eq" => "sete",
+          _ => "setne",
+        };
+        self.line("cmpl %ecx, %eax");
+        self.line(&format!("{set} %al"));
+        self.line("movzbl %al, %eax");
+      }
+    }
+    self.store("%eax", x)
+  }
+
+  fn call(&mut self, dest: &String, callee: &String, args: &Vec<String>) -> Result<(), String> {
+    let stacked = if args.len() > 6 { args.len() - 6 } else { 0 };
+    let padding = if stacked % 2 == 1 { 8 } else { 0 };
+    if padding > 0 {
+      self.line("subq $8, %rsp");
+    }
+    for arg in args.iter().skip(6).rev() {
+      match ir::array_arg(arg) {
+        Some(arr) => self.address(&arr.to_string(), "%rax")?,
+        None => self.load(arg, "%eax")?,
+      }
+      self.line("pushq %rax");
+    }
+    for (k, arg) in args.iter().enumerate().take(6) {
+      match ir::array_arg(arg) {
+        Some(arr) => self.address(&arr.to_string(), ARG_REGS_64[k])?,
+        None => self.load(arg, ARG_REGS[k])?,
+      }
+    }
+    self.line(&format!("call {}", symbol(callee)));
+    if stacked > 0 {
+      self.line(&format!("addq ${}, %rsp", 8 * stacked + padding));
+    }
+    self.store("%eax", dest)
+  }
+
+  fn epilogue(&mut self) {
+    for (register, offset) in self.frame.saves.clone() {
+      self.line(&format!("movq {offset}(%rbp), {register}"));
+    }
+    self.line("leave");
+    self.line("ret");
+  }
+
+  fn instr(&mut self, instr: &Instr) -> Result<(), String> {
+    match instr {
+      Instr::Int(_) | Instr::IntArray(_, _) => {}
+      Instr::Mov(x, y) => {
+        self.load(y, "%eax")?;
+        self.store("%eax", x)?;
+      }
+      Instr::Store(arr, i, x) => {
+        let address = self.element(arr, i)?;
+        self.load(x, "%eax")?;
+        self.line(&format!("movl %eax, {address}"));
+      }
+      Instr::Load(x, arr, i) => {
+        let address = self.element(arr, i)?;
+        self.line(&format!("movl {address}, %eax"));
+        self.store("%eax", x)?;
+      }
+      Instr::Binary(op, x, y, z) => self.binary(op, x, y, z)?,
+      Instr::Out(x) => {
+        self.load(x, "%esi")?;
+        self.line("leaq tt_out_format(%rip), %rdi");
+        self.line("xorl %eax, %eax");
+        self.line("call printf@PLT");
+      }
+      Instr::Input(x) => {
+        self.line("call tt_input");
+        self.store("%eax", x)?;
+      }
+      Instr::Call(x, f, args) => self.call(x, f, args)?,
+      Instr::Check(i, size, arr, line) => {
+        // the unsigned compare also catches negative indices.
+        let ok = self.fresh();
+        self.load(i, "%eax")?;
+        self.load(size, "%ecx")?;
+        self.line("cmpl %ecx, %eax");
+        self.line(&format!("jb {ok}"));
+        self.line("movl %ecx, %r8d");
+        self.line("movl %eax, %edx");
+        self.line(&format!("movl ${line}, %esi"));
+        self.line(&format!("leaq {}(%rip), %rcx", name_symbol(arr)));
+        self.line("jmp tt_out_of_bounds");
+        self.code += &format!("{ok}:\n");
+      }
+      Instr::Ret(x) => {
+        self.load(x, "%eax")?;
+        self.epilogue();
+      }
+      Instr::Jmp(l) => {
+        let target = label(self.function, l);
+        self.line(&format!("jmp {target}"));
+      }
+      Instr::BranchIf(x, l) | Instr::BranchIfn(x, l) => {
+        let jump = if matches!(instr, Instr::BranchIf(_, _)) { "jne" } else { "je" };
+        let target = label(self.function, l);
+        self.load(x, "%eax")?;
+        self.line("testl %eax, %eax");
+        self.line(&format!("{jump} {target}"));
+      }
+      Instr::JumpTable(x, default, table) => {
+        // the unsigned compare also sends negative indices to the default.
+        let table_label = self.fresh();
+        self.load(x, "%eax")?;
+        self.line(&format!("cmpl ${}, %eax", table.len()));
+        self.line(&format!("jae {}", label(self.function, default)));
+        self.line(&format!("leaq {table_label}(%rip), %rcx"));
+        self.line("movslq (%rcx,%rax,4), %rdx");
+        self.line("addq %rcx, %rdx");
+        self.line("jmp *%rdx");
+        self.code += &format!("{table_label}:\n");
+        for l in table {
+          self.line(&format!(".long {} - {table_label}", label(self.function, l)));
+        }
+      }
+      Instr::Label(l) => {
+        self.code += &format!("{}:\n", label(self.function, l));
+      }
+      Instr::Phi(_, _) => return Err(String::from("phi instructions must be removed before code generation")),
+    }
+    Ok(())
+  }
+}
+
+fn generate_function(function: &Function, globals: &HashSet<String>, overflow: Overflow) -> Result<String, String> {
+  let allocation = regalloc::allocate(function, ALLOCATABLE.len());
+  let frame = build_frame(function, &allocation)?;
+  let size = frame.size;
+  let mut emitter = Emitter { function, globals, overflow, frame, code: String::new(), count: 0 };
+  let name = symbol(&function.name);
+  emitter.code += &format!("    .globl {name}\n    .type {name}, @function\n{name}:\n");
+  emitter.line("pushq %rbp");
+  emitter.line("movq %rsp, %rbp");
+  if size > 0 {
+    // variables start out as 0, like in the VM.
+    let zero = emitter.fresh();
+    emitter.line(&format!("subq ${size}, %rsp"));
+    emitter.line(&format!("leaq -{size}(%rbp), %r10"));
+    emitter.code += &format!("{zero}:\n");
+    emitter.line("movq $0, (%r10)");
+    emitter.line("addq $8, %r10");
+    emitter.line("cmpq %rbp, %r10");
+    emitter.line(&format!("jne {zero}"));
+  }
+  for (register, offset) in emitter.frame.saves.clone() {
+    emitter.line(&format!("movq {register}, {offset}(%rbp)"));
+  }
+  for register in ALLOCATABLE {
+    if emitter.frame.registers.iter().any(|(var, r)| *r == register && !function.params.contains(var)) {
+      emitter.line(&format!("xorl {register}, {register}"));
+    }
+  }
+  for (k, param) in function.params.iter().enumerate() {
+    if let Some(offset) = emitter.frame.pointers.get(param) {
+      let offset = *offset;
+      if k < 6 {
+        emitter.line(&format!("movq {}, {offset}(%rbp)", ARG_REGS_64[k]));
+      } else {
+        emitter.line(&format!("movq {}(%rbp), %rax", 16 + 8 * (k - 6)));
+        emitter.line(&format!("movq %rax, {offset}(%rbp)"));
+      }
+    } else if k < 6 {
+      emitter.store(ARG_REGS[k], param)?;
+    } else {
+      emitter.line(&format!("movl {}(%rbp), %eax", 16 + 8 * (k - 6)));
+      emitter.store("%eax", param)?;
+    }
+  }
+  for instr in &function.body {
+    emitter.instr(instr)?;
+  }
+  emitter.line("xorl %eax, %eax");
+  emitter.epilogue();
+  emitter.code += &format!("    .size {name}, .-{name}\n");
+  return Ok(emitter.code);
+}
+
+pub fn generate(program: &ir::Program) -> Result<String, String> {
+  let globals = ir::global_names(program);
+  let mut code = String::from("    .text\n");
+  for function in &program.functions {
+    code += &generate_function(function, &globals, program.overflow)?;
+  }
+  code += "
+    .globl main
+    .type main, @function
+main:
+    pushq %rbp
+    movq %rsp, %rbp
+    call tt_main
+    xorl %eax, %eax
+    popq %rbp
+    ret
+
+# the next whitespace-separated word as an int, like the VM reads it: 0 at
+# the end of input, and a runtime error unless the whole word is an int.
+tt_input:
+    pushq %rbp
+    movq %rsp, %rbp
+    subq $80, %rsp
+    leaq -64(%rbp), %rsi
+    leaq tt_in_format(%rip), %rdi
+    xorl %eax, %eax
+    call scanf@PLT
+    cmpl $1, %eax
+    jne .Ltt_input_end
+    leaq -64(%rbp), %rdi
+    leaq -72(%rbp), %rsi
+    movl $10, %edx
+    call strtol@PLT
+    movq -72(%rbp), %rcx
+    leaq -64(%rbp), %rdx
+    cmpq %rdx, %rcx
+    je .Ltt_input_invalid
+    cmpb $0, (%rcx)
+    jne .Ltt_input_invalid
+    movslq %eax, %rdx
+    cmpq %rax, %rdx
+    jne .Ltt_input_invalid
+    leave
+    ret
+.Ltt_input_end:
+    xorl %eax, %eax
+    leave
+    ret
+.Ltt_input_invalid:
+    leaq -64(%rbp), %rsi
+    leaq tt_input_format(%rip), %rdi
+    xorl %eax, %eax
+    call printf@PLT
+    movl $1, %edi
+    call exit@PLT
+
+tt_division_by_zero:
+    andq $-16, %rsp
+    leaq tt_division_message(%rip), %rdi
+    call puts@PLT
+    movl $1, %edi
+    call exit@PLT
+
+tt_out_of_bounds:
+    andq $-16, %rsp
+    leaq tt_bounds_format(%rip), %rdi
+    xorl %eax, %eax
+    call printf@PLT
+    movl $1, %edi
+    call exit@PLT
+
+tt_overflow:
+    andq $-16, %rsp
+    leaq tt_overflow_message(%rip), %rdi
+    call puts@PLT
+    movl $1, %edi
+    call exit@PLT
+
+    .section .rodata
+tt_out_format:
+    .string \"%d\\n\"
+tt_in_format:
+    .string \"%63s\"
+tt_input_format:
+    .string \"runtime error: invalid input '%s'\\n\"
+tt_division_message:
+    .string \"runtime error: division by zero\"
+tt_bounds_format:
+    .string \"runtime error: line %d: index %d out of bounds for array %s of size %d\\n\"
+tt_overflow_message:
+    .string \"runtime error: integer overflow\"
+";
+  // the array names failed checks report.
+  let mut names: Vec<&String> = vec![];
+  for function in &program.functions {
+    for instr in &function.body {
+      if let Instr::Check(_, _, arr, _) = instr {
+        if !names.contains(&arr) {
+          names.push(arr);
+          code += &format!("{}:\n    .string \"{arr}\"\n", name_symbol(arr));
+        }
+      }
+    }
+  }
+  if !program.globals.is_empty() {
+    code += "    .bss\n    .align 4\n";
+    for global in &program.globals {
+      code += &format!("{}:\n    .zero {}\n", global_symbol(&global.name), 4 * global.size);
+    }
+  }
+  code += "    .section .note.GNU-stack,\"\",@progbits\n";
+  return Ok(code);
+}
+
+// assemble and link with the system C compiler driver.
+pub fn link(assembly: &Path, output: &Path) -> Result<(), String> {
+  let status = Command::new("cc").arg("-o").arg(output).arg(assembly).status();
+  match status {
+    Ok(status) if status.success() => Ok(()),
+    Ok(status) => Err(format!("cc failed with {status}")),
+    Err(error) => Err(format!("could not run cc: {error}")),
+  }
+}